@@ -0,0 +1,206 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::expr::Expr;
+use crate::json;
+use crate::resolver::SymbolKind;
+use crate::stmt::{FunctionDecl, Stmt};
+
+// Caller name of code outside any function, as in profiles
+pub const SCRIPT: &str = "<script>";
+
+// A function, class or method declaration, with the declarations nested in it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind, // Function, Class or Method
+    pub line: usize,
+    pub column: usize,
+    pub children: Vec<Symbol>,
+}
+
+// Declaration outline of a program, in source order
+pub fn outline(statements: &[Stmt]) -> Vec<Symbol> {
+    let mut symbols = Vec::new();
+    for stmt in statements {
+        match stmt {
+            Stmt::Function(declaration) => symbols.push(function_symbol(declaration, SymbolKind::Function)),
+            Stmt::Class(declaration) => {
+                let mut methods: Vec<Symbol> = declaration
+                    .methods
+                    .iter()
+                    .chain(&declaration.class_methods)
+                    .map(|method| function_symbol(method, SymbolKind::Method))
+                    .collect();
+                methods.sort_by_key(|method| (method.line, method.column));
+                symbols.push(Symbol {
+                    name: declaration.name.lexeme.clone(),
+                    kind: SymbolKind::Class,
+                    line: declaration.name.line,
+                    column: declaration.name.column,
+                    children: methods,
+                });
+            }
+            Stmt::Block(statements) => symbols.extend(outline(statements)),
+            Stmt::ForIn { body, .. } => symbols.extend(outline(std::slice::from_ref(body))),
+            _ => {}
+        }
+    }
+    symbols
+}
+
+fn function_symbol(declaration: &FunctionDecl, kind: SymbolKind) -> Symbol {
+    Symbol {
+        name: declaration.name.lexeme.clone(),
+        kind,
+        line: declaration.name.line,
+        column: declaration.name.column,
+        children: outline(&declaration.body),
+    }
+}
+
+// Static call graph: (caller, callee) for every call whose callee is a plain name,
+// sorted and without duplicates. Methods are named `Class.method`; top-level code
+// is SCRIPT. Calls through properties or computed values are not followed.
+pub fn call_graph(statements: &[Stmt]) -> Vec<(String, String)> {
+    let mut calls = BTreeSet::new();
+    collect_calls(SCRIPT, statements, &mut calls);
+    calls.into_iter().collect()
+}
+
+fn collect_calls(caller: &str, statements: &[Stmt], calls: &mut BTreeSet<(String, String)>) {
+    for stmt in statements {
+        match stmt {
+            Stmt::Expression(expr) | Stmt::Print(expr) | Stmt::Defer { expression: expr, .. } => expr_calls(caller, expr, calls),
+            Stmt::Var { initializer: value, .. } | Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    expr_calls(caller, value, calls);
+                }
+            }
+            Stmt::Block(statements) => collect_calls(caller, statements, calls),
+            Stmt::ForIn { iterable, body, .. } => {
+                expr_calls(caller, iterable, calls);
+                collect_calls(caller, std::slice::from_ref(body), calls);
+            }
+            Stmt::Function(declaration) => collect_calls(&declaration.name.lexeme, &declaration.body, calls),
+            Stmt::Class(declaration) => {
+                let class = &declaration.name.lexeme;
+                for field in &declaration.fields {
+                    if let Some(initializer) = &field.initializer {
+                        expr_calls(class, initializer, calls);
+                    }
+                }
+                for method in declaration.methods.iter().chain(&declaration.class_methods) {
+                    collect_calls(&format!("{}.{}", class, method.name.lexeme), &method.body, calls);
+                }
+            }
+        }
+    }
+}
+
+fn expr_calls(caller: &str, expr: &Expr, calls: &mut BTreeSet<(String, String)>) {
+    match expr {
+        Expr::Call { callee, arguments, .. } => {
+            if let Expr::Variable(name, _) = callee.as_ref() {
+                calls.insert((caller.to_string(), name.lexeme.clone()));
+            }
+            expr_calls(caller, callee, calls);
+            arguments.iter().for_each(|argument| expr_calls(caller, argument, calls));
+        }
+        Expr::Assign { value, .. } => expr_calls(caller, value, calls),
+        Expr::Binary { left, right, .. } => {
+            expr_calls(caller, left, calls);
+            expr_calls(caller, right, calls);
+        }
+        Expr::Array { elements, .. } => elements.iter().for_each(|element| expr_calls(caller, element, calls)),
+        Expr::Index { object, index, .. } => {
+            expr_calls(caller, object, calls);
+            expr_calls(caller, index, calls);
+        }
+        Expr::Slice { object, start, end, .. } => {
+            expr_calls(caller, object, calls);
+            start.iter().chain(end).for_each(|bound| expr_calls(caller, bound, calls));
+        }
+        Expr::SetIndex { object, index, value, .. } => {
+            expr_calls(caller, object, calls);
+            expr_calls(caller, index, calls);
+            expr_calls(caller, value, calls);
+        }
+        Expr::Get { object, .. } => expr_calls(caller, object, calls),
+        Expr::Set { object, value, .. } => {
+            expr_calls(caller, object, calls);
+            expr_calls(caller, value, calls);
+        }
+        Expr::Grouping(inner) | Expr::Unary { right: inner, .. } => expr_calls(caller, inner, calls),
+        Expr::Match { subject, arms, .. } => {
+            expr_calls(caller, subject, calls);
+            arms.iter().for_each(|arm| expr_calls(caller, &arm.body, calls));
+        }
+        Expr::This(..) | Expr::Literal { .. } | Expr::Variable(..) => {}
+    }
+}
+
+// `{"symbols": [...], "calls": [...]}`, with only the sections that are given
+pub fn to_json(symbols: Option<&[Symbol]>, calls: Option<&[(String, String)]>) -> String {
+    let mut sections = Vec::new();
+    if let Some(symbols) = symbols {
+        sections.push(format!("\"symbols\":{}", symbols_json(symbols)));
+    }
+    if let Some(calls) = calls {
+        let calls: Vec<String> = calls
+            .iter()
+            .map(|(caller, callee)| format!("{{\"caller\":{},\"callee\":{}}}", json::quote(caller), json::quote(callee)))
+            .collect();
+        sections.push(format!("\"calls\":[{}]", calls.join(",")));
+    }
+    format!("{{{}}}", sections.join(","))
+}
+
+fn symbols_json(symbols: &[Symbol]) -> String {
+    let symbols: Vec<String> = symbols
+        .iter()
+        .map(|symbol| {
+            format!(
+                "{{\"name\":{},\"kind\":\"{}\",\"line\":{},\"column\":{},\"children\":{}}}",
+                json::quote(&symbol.name),
+                symbol.kind.name(),
+                symbol.line,
+                symbol.column,
+                symbols_json(&symbol.children)
+            )
+        })
+        .collect();
+    format!("[{}]", symbols.join(","))
+}
+
+// Graphviz digraph: declarations as boxes linked to what they contain by dotted
+// edges, calls as solid edges
+pub fn to_dot(symbols: Option<&[Symbol]>, calls: Option<&[(String, String)]>) -> String {
+    let mut out = String::from("digraph lox {\n");
+    if let Some(symbols) = symbols {
+        dot_symbols(None, symbols, &mut out);
+    }
+    if let Some(calls) = calls {
+        for (caller, callee) in calls {
+            writeln!(out, "    {} -> {};", json::quote(caller), json::quote(callee)).unwrap();
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn dot_symbols(parent: Option<&str>, symbols: &[Symbol], out: &mut String) {
+    for symbol in symbols {
+        // Methods are named as in the call graph, so both kinds of edge meet
+        let id = match (symbol.kind, parent) {
+            (SymbolKind::Method, Some(class)) => format!("{}.{}", class, symbol.name),
+            _ => symbol.name.clone(),
+        };
+        let label = format!("{} ({})", symbol.name, symbol.kind.name());
+        writeln!(out, "    {} [shape=box, label={}];", json::quote(&id), json::quote(&label)).unwrap();
+        if let Some(parent) = parent {
+            writeln!(out, "    {} -> {} [style=dotted];", json::quote(parent), json::quote(&id)).unwrap();
+        }
+        dot_symbols(Some(&id), &symbol.children, out);
+    }
+}
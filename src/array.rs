@@ -0,0 +1,76 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::expr::LiteralValue;
+use crate::stats;
+
+// A growable array shared by reference: copies of the value alias the same elements
+#[derive(Clone)]
+pub struct LoxArray(Arc<Mutex<Vec<LiteralValue>>>);
+
+impl LoxArray {
+    pub fn new(elements: Vec<LiteralValue>) -> Self {
+        stats::object_created();
+        LoxArray(Arc::new(Mutex::new(elements)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Identity of the shared storage, the same for every copy of this array
+    pub fn id(&self) -> usize {
+        Arc::as_ptr(&self.0) as *const () as usize
+    }
+
+    pub fn get(&self, index: usize) -> Option<LiteralValue> {
+        self.0.lock().unwrap().get(index).cloned()
+    }
+
+    // Returns false if the index is out of bounds
+    pub fn set(&self, index: usize, value: LiteralValue) -> bool {
+        match self.0.lock().unwrap().get_mut(index) {
+            Some(slot) => {
+                *slot = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn push(&self, value: LiteralValue) {
+        self.0.lock().unwrap().push(value);
+    }
+
+    // Snapshot of the elements. Callers that run Lox code per element must iterate a
+    // snapshot rather than hold the lock, since the callback may touch the array.
+    pub fn to_vec(&self) -> Vec<LiteralValue> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+// Arrays are reference values: equal only when they are the same array
+impl PartialEq for LoxArray {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+// The last copy going away frees the shared storage
+impl Drop for LoxArray {
+    fn drop(&mut self) {
+        if Arc::strong_count(&self.0) == 1 {
+            stats::object_freed();
+        }
+    }
+}
+
+impl fmt::Debug for LoxArray {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<array len {}>", self.len())
+    }
+}
@@ -0,0 +1,245 @@
+use crate::expr::{Expr, Pattern};
+use crate::stmt::{ClassDecl, FieldDecl, FunctionDecl, Stmt};
+
+// A node of the syntax tree, for walking it without caring about its type. Function
+// and class declarations are Stmts at the top level and in blocks; methods and fields
+// only appear inside a class.
+#[derive(Debug, Clone, Copy)]
+pub enum Node<'a> {
+    Stmt(&'a Stmt),
+    Expr(&'a Expr),
+    Method(&'a FunctionDecl),
+    Field(&'a FieldDecl),
+}
+
+impl<'a> Node<'a> {
+    // See Stmt::span for what a span leaves out
+    pub fn span(&self) -> Option<(usize, usize)> {
+        match self {
+            Node::Stmt(stmt) => stmt.span(),
+            Node::Expr(expr) => Some(expr.span()),
+            Node::Method(method) => Some(method.span()),
+            Node::Field(field) => Some(field.span()),
+        }
+    }
+
+    // Short name of the node's kind: "print", "call", "variable", ...
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Node::Stmt(Stmt::Expression(_)) => "expression statement",
+            Node::Stmt(Stmt::Print(_)) => "print",
+            Node::Stmt(Stmt::Var { .. }) => "var",
+            Node::Stmt(Stmt::Block(_)) => "block",
+            Node::Stmt(Stmt::Function(_)) => "function",
+            Node::Stmt(Stmt::Class(_)) => "class",
+            Node::Stmt(Stmt::Return { .. }) => "return",
+            Node::Stmt(Stmt::ForIn { .. }) => "for",
+            Node::Stmt(Stmt::Defer { .. }) => "defer",
+            Node::Expr(Expr::Assign { .. }) => "assign",
+            Node::Expr(Expr::Binary { .. }) => "binary",
+            Node::Expr(Expr::Call { .. }) => "call",
+            Node::Expr(Expr::Array { .. }) => "array",
+            Node::Expr(Expr::Index { .. }) => "index",
+            Node::Expr(Expr::Slice { .. }) => "slice",
+            Node::Expr(Expr::SetIndex { .. }) => "set index",
+            Node::Expr(Expr::Get { .. }) => "get",
+            Node::Expr(Expr::Set { .. }) => "set",
+            Node::Expr(Expr::This(..)) => "this",
+            Node::Expr(Expr::Grouping(_)) => "grouping",
+            Node::Expr(Expr::Literal { .. }) => "literal",
+            Node::Expr(Expr::Unary { .. }) => "unary",
+            Node::Expr(Expr::Variable(..)) => "variable",
+            Node::Expr(Expr::Match { .. }) => "match",
+            Node::Method(_) => "method",
+            Node::Field(_) => "field",
+        }
+    }
+
+    // Direct children, in source order
+    pub fn children(&self) -> Vec<Node<'a>> {
+        let exprs = |exprs: &[&'a Expr]| exprs.iter().map(|expr| Node::Expr(expr)).collect();
+        match *self {
+            Node::Stmt(stmt) => match stmt {
+                Stmt::Expression(expr) | Stmt::Print(expr) => vec![Node::Expr(expr)],
+                Stmt::Var { initializer, .. } => initializer.iter().map(Node::Expr).collect(),
+                Stmt::Block(statements) => statements.iter().map(Node::Stmt).collect(),
+                Stmt::Function(declaration) => declaration.body.iter().map(Node::Stmt).collect(),
+                Stmt::Class(declaration) => class_members(declaration),
+                Stmt::Return { value, .. } => value.iter().map(Node::Expr).collect(),
+                Stmt::ForIn { iterable, body, .. } => vec![Node::Expr(iterable), Node::Stmt(body)],
+                Stmt::Defer { expression, .. } => vec![Node::Expr(expression)],
+            },
+            Node::Expr(expr) => match expr {
+                Expr::Assign { value, .. } => exprs(&[value]),
+                Expr::Binary { left, right, .. } => exprs(&[left, right]),
+                Expr::Call { callee, arguments, .. } => {
+                    std::iter::once(&**callee).chain(arguments).map(Node::Expr).collect()
+                }
+                Expr::Array { elements, .. } => elements.iter().map(Node::Expr).collect(),
+                Expr::Index { object, index, .. } => exprs(&[object, index]),
+                Expr::Slice { object, start, end, .. } => {
+                    std::iter::once(object).chain(start).chain(end).map(|expr| Node::Expr(expr)).collect()
+                }
+                Expr::SetIndex { object, index, value, .. } => exprs(&[object, index, value]),
+                Expr::Get { object, .. } => exprs(&[object]),
+                Expr::Set { object, value, .. } => exprs(&[object, value]),
+                Expr::Grouping(expr) => exprs(&[expr]),
+                Expr::Unary { right, .. } => exprs(&[right]),
+                Expr::This(..) | Expr::Literal { .. } | Expr::Variable(..) => Vec::new(),
+                // Patterns aren't nodes: only the arms' bodies are
+                Expr::Match { subject, arms, .. } => {
+                    std::iter::once(&**subject).chain(arms.iter().map(|arm| &arm.body)).map(Node::Expr).collect()
+                }
+            },
+            Node::Method(method) => method.body.iter().map(Node::Stmt).collect(),
+            Node::Field(field) => field.initializer.iter().map(Node::Expr).collect(),
+        }
+    }
+}
+
+fn class_members(declaration: &ClassDecl) -> Vec<Node<'_>> {
+    let mut members: Vec<Node> = declaration.fields.iter().map(Node::Field).collect();
+    members.extend(declaration.methods.iter().chain(&declaration.class_methods).map(|method| Node::Method(method)));
+    members.sort_by_key(|member| member.span().map_or(0, |span| span.0));
+    members
+}
+
+// The nodes containing a source position, outermost (a top-level statement) first
+#[derive(Debug, Clone, Default)]
+pub struct NodePath<'a> {
+    pub nodes: Vec<Node<'a>>,
+}
+
+impl<'a> NodePath<'a> {
+    pub fn innermost(&self) -> Option<Node<'a>> {
+        self.nodes.last().copied()
+    }
+
+    // The nodes around the innermost one, nearest first
+    pub fn ancestors(&self) -> impl Iterator<Item = Node<'a>> + '_ {
+        self.nodes.iter().rev().skip(1).copied()
+    }
+}
+
+// The innermost node whose span covers a byte offset, with its ancestry. Empty when
+// the offset is outside every statement, e.g. in a comment between them. For hover,
+// go-to-definition, evaluating the expression under the cursor and refactoring.
+pub fn find_at(statements: &[Stmt], offset: usize) -> NodePath<'_> {
+    let mut path = NodePath::default();
+    let mut candidates: Vec<Node> = statements.iter().map(Node::Stmt).collect();
+    while let Some(node) = candidates.into_iter().find(|node| covers(node, offset)) {
+        candidates = node.children();
+        path.nodes.push(node);
+    }
+    path
+}
+
+fn covers(node: &Node, offset: usize) -> bool {
+    node.span().is_some_and(|(start, end)| start <= offset && offset < end)
+}
+
+// Whether two trees are the same program: the same statements and expressions with
+// the same names, operators and literal values. Where tokens are in the source,
+// parentheses and how literals were spelled (`1` and `1.0`) don't matter, so a
+// program printed by printer::to_source and parsed again is the same program.
+pub fn same_program(a: &[Stmt], b: &[Stmt]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_stmt(a, b))
+}
+
+pub fn same_stmt(a: &Stmt, b: &Stmt) -> bool {
+    match (a, b) {
+        (Stmt::Expression(a), Stmt::Expression(b)) | (Stmt::Print(a), Stmt::Print(b)) => same_expr(a, b),
+        (Stmt::Var { name, initializer }, Stmt::Var { name: other_name, initializer: other }) => {
+            name.lexeme == other_name.lexeme && same_optional(initializer.as_ref(), other.as_ref())
+        }
+        (Stmt::Block(a), Stmt::Block(b)) => same_program(a, b),
+        (Stmt::Function(a), Stmt::Function(b)) => same_function(a, b),
+        (Stmt::Class(a), Stmt::Class(b)) => {
+            let same_field = |a: &FieldDecl, b: &FieldDecl| {
+                a.name.lexeme == b.name.lexeme && same_optional(a.initializer.as_ref(), b.initializer.as_ref())
+            };
+            a.name.lexeme == b.name.lexeme
+                && same_list(&a.fields, &b.fields, same_field)
+                && same_list(&a.methods, &b.methods, |a, b| same_function(a, b))
+                && same_list(&a.class_methods, &b.class_methods, |a, b| same_function(a, b))
+        }
+        (Stmt::Return { value, .. }, Stmt::Return { value: other, .. }) => same_optional(value.as_ref(), other.as_ref()),
+        (
+            Stmt::ForIn { name, iterable, body, .. },
+            Stmt::ForIn { name: other_name, iterable: other_iterable, body: other_body, .. },
+        ) => name.lexeme == other_name.lexeme && same_expr(iterable, other_iterable) && same_stmt(body, other_body),
+        (Stmt::Defer { expression, .. }, Stmt::Defer { expression: other, .. }) => same_expr(expression, other),
+        _ => false,
+    }
+}
+
+pub fn same_expr(a: &Expr, b: &Expr) -> bool {
+    match (a, b) {
+        (Expr::Grouping(a), b) | (b, Expr::Grouping(a)) => same_expr(a, b),
+        (Expr::Assign { name, value, .. }, Expr::Assign { name: other_name, value: other, .. }) => {
+            name.lexeme == other_name.lexeme && same_expr(value, other)
+        }
+        (Expr::Binary { left, operator, right }, Expr::Binary { left: l, operator: o, right: r }) => {
+            operator.lexeme == o.lexeme && same_expr(left, l) && same_expr(right, r)
+        }
+        (Expr::Unary { operator, right }, Expr::Unary { operator: o, right: r }) => {
+            operator.lexeme == o.lexeme && same_expr(right, r)
+        }
+        (Expr::Call { callee, arguments, .. }, Expr::Call { callee: c, arguments: a, .. }) => {
+            same_expr(callee, c) && same_list(arguments, a, same_expr)
+        }
+        (Expr::Array { elements, .. }, Expr::Array { elements: other, .. }) => same_list(elements, other, same_expr),
+        (Expr::Index { object, index, .. }, Expr::Index { object: o, index: i, .. }) => {
+            same_expr(object, o) && same_expr(index, i)
+        }
+        (Expr::Slice { object, start, end, .. }, Expr::Slice { object: o, start: s, end: e, .. }) => {
+            same_expr(object, o) && same_optional(start.as_deref(), s.as_deref()) && same_optional(end.as_deref(), e.as_deref())
+        }
+        (Expr::SetIndex { object, index, value, .. }, Expr::SetIndex { object: o, index: i, value: v, .. }) => {
+            same_expr(object, o) && same_expr(index, i) && same_expr(value, v)
+        }
+        (Expr::Get { object, name }, Expr::Get { object: o, name: n }) => name.lexeme == n.lexeme && same_expr(object, o),
+        (Expr::Set { object, name, value }, Expr::Set { object: o, name: n, value: v }) => {
+            name.lexeme == n.lexeme && same_expr(object, o) && same_expr(value, v)
+        }
+        (Expr::This(..), Expr::This(..)) => true,
+        (Expr::Literal { value, .. }, Expr::Literal { value: other, .. }) => value == other,
+        (Expr::Variable(name, _), Expr::Variable(other, _)) => name.lexeme == other.lexeme,
+        (Expr::Match { subject, arms, .. }, Expr::Match { subject: s, arms: a, .. }) => {
+            same_expr(subject, s) && same_list(arms, a, |a, b| same_pattern(&a.pattern, &b.pattern) && same_expr(&a.body, &b.body))
+        }
+        _ => false,
+    }
+}
+
+fn same_pattern(a: &Pattern, b: &Pattern) -> bool {
+    match (a, b) {
+        (Pattern::Wildcard(_), Pattern::Wildcard(_)) => true,
+        (Pattern::Literal(a), Pattern::Literal(b)) => same_expr(a, b),
+        (Pattern::Binding(a), Pattern::Binding(b)) => a.lexeme == b.lexeme,
+        (Pattern::Array { elements, .. }, Pattern::Array { elements: other, .. }) => same_list(elements, other, same_pattern),
+        (Pattern::Map { entries, .. }, Pattern::Map { entries: other, .. }) => {
+            same_list(entries, other, |(a, p), (b, q)| a.literal == b.literal && same_pattern(p, q))
+        }
+        _ => false,
+    }
+}
+
+fn same_function(a: &FunctionDecl, b: &FunctionDecl) -> bool {
+    a.name.lexeme == b.name.lexeme
+        && a.getter == b.getter
+        && same_list(&a.params, &b.params, |a, b| a.lexeme == b.lexeme)
+        && same_program(&a.body, &b.body)
+}
+
+fn same_optional(a: Option<&Expr>, b: Option<&Expr>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => same_expr(a, b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn same_list<T>(a: &[T], b: &[T], same: impl Fn(&T, &T) -> bool) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(a, b))
+}
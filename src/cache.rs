@@ -0,0 +1,510 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::expr::{Expr, LiteralValue, LocalSlot, MatchArm, Pattern};
+use crate::stmt::{ClassDecl, FieldDecl, FunctionDecl, Stmt};
+use crate::token::{Token, TokenType};
+
+// Bump whenever Expr, Stmt or Token change shape: older cache files then miss
+pub const SCHEMA_VERSION: u32 = 6;
+
+const MAGIC: &[u8; 4] = b"LOXC";
+
+// Parsed programs stored on disk (`run --cache-dir=<dir>`), so running an unchanged
+// file again skips scanning and parsing. Only programs without syntax errors are
+// stored; a missing, stale or unreadable entry is simply a miss.
+pub struct ParseCache {
+    dir: PathBuf,
+}
+
+impl ParseCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        ParseCache { dir: dir.into() }
+    }
+
+    // Cache key of a source file. `settings` must describe everything else that
+    // changes the parse, such as the scanner options, `--lang` and `--extensions`.
+    pub fn key(source: &str, settings: &str) -> String {
+        // FNV-1a: unlike the std hasher, stable across Rust releases
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in settings.bytes().chain([0]).chain(source.bytes()) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        format!("{:016x}", hash)
+    }
+
+    pub fn load(&self, key: &str) -> Option<Vec<Stmt>> {
+        let bytes = fs::read(self.path(key)).ok()?;
+        decode(&bytes)
+    }
+
+    pub fn store(&self, key: &str, statements: &[Stmt]) {
+        let Some(bytes) = encode(statements) else {
+            return;
+        };
+        let result = fs::create_dir_all(&self.dir).and_then(|_| fs::write(self.path(key), bytes));
+        if let Err(error) = result {
+            log::warn!("Failed to write parse cache {}: {}", self.path(key).display(), error);
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.ast", key))
+    }
+}
+
+// Serialize a program; None if it holds a value with no source form
+pub fn encode(statements: &[Stmt]) -> Option<Vec<u8>> {
+    let mut encoder = Encoder { out: MAGIC.to_vec() };
+    encoder.u32(SCHEMA_VERSION);
+    encoder.statements(statements)?;
+    Some(encoder.out)
+}
+
+// Deserialize a program; None if the bytes are not a program of this schema version
+pub fn decode(bytes: &[u8]) -> Option<Vec<Stmt>> {
+    let mut decoder = Decoder { bytes, position: 0 };
+    if decoder.take(MAGIC.len())? != MAGIC || decoder.u32()? != SCHEMA_VERSION {
+        return None;
+    }
+    let statements = decoder.statements()?;
+    (decoder.position == bytes.len()).then_some(statements)
+}
+
+// Little-endian primitives shared with other binary formats, see trace.rs
+pub(crate) struct Encoder {
+    pub(crate) out: Vec<u8>,
+}
+
+impl Encoder {
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.out.push(value);
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.out.extend(value.to_le_bytes());
+    }
+
+    pub(crate) fn usize(&mut self, value: usize) {
+        self.out.extend((value as u64).to_le_bytes());
+    }
+
+    pub(crate) fn string(&mut self, value: &str) {
+        self.usize(value.len());
+        self.out.extend(value.as_bytes());
+    }
+
+    fn token(&mut self, token: &Token) {
+        self.u8(token.token_type as u8);
+        self.string(&token.lexeme);
+        match &token.literal {
+            Some(literal) => {
+                self.u8(1);
+                self.string(literal);
+            }
+            None => self.u8(0),
+        }
+        self.usize(token.line);
+        self.usize(token.start);
+        self.usize(token.column);
+    }
+
+    fn tokens(&mut self, tokens: &[Token]) {
+        self.usize(tokens.len());
+        tokens.iter().for_each(|token| self.token(token));
+    }
+
+    fn statements(&mut self, statements: &[Stmt]) -> Option<()> {
+        self.usize(statements.len());
+        statements.iter().try_for_each(|stmt| self.stmt(stmt))
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> Option<()> {
+        match stmt {
+            Stmt::Expression(expr) => {
+                self.u8(0);
+                self.expr(expr)
+            }
+            Stmt::Print(expr) => {
+                self.u8(1);
+                self.expr(expr)
+            }
+            Stmt::Var { name, initializer } => {
+                self.u8(2);
+                self.token(name);
+                self.optional_expr(initializer.as_ref())
+            }
+            Stmt::Block(statements) => {
+                self.u8(3);
+                self.statements(statements)
+            }
+            Stmt::Function(declaration) => {
+                self.u8(4);
+                self.function(declaration)
+            }
+            Stmt::Class(declaration) => {
+                self.u8(5);
+                self.token(&declaration.name);
+                self.usize(declaration.fields.len());
+                for field in &declaration.fields {
+                    self.token(&field.name);
+                    self.optional_expr(field.initializer.as_ref())?;
+                }
+                self.usize(declaration.methods.len());
+                declaration.methods.iter().try_for_each(|method| self.function(method))?;
+                self.usize(declaration.class_methods.len());
+                declaration.class_methods.iter().try_for_each(|method| self.function(method))
+            }
+            Stmt::Return { keyword, value } => {
+                self.u8(6);
+                self.token(keyword);
+                self.optional_expr(value.as_ref())
+            }
+            Stmt::ForIn { keyword, name, iterable, body } => {
+                self.u8(7);
+                self.token(keyword);
+                self.token(name);
+                self.expr(iterable)?;
+                self.stmt(body)
+            }
+            Stmt::Defer { keyword, expression } => {
+                self.u8(8);
+                self.token(keyword);
+                self.expr(expression)
+            }
+        }
+    }
+
+    fn function(&mut self, declaration: &FunctionDecl) -> Option<()> {
+        self.token(&declaration.name);
+        self.tokens(&declaration.params);
+        self.u8(u8::from(declaration.getter));
+        self.statements(&declaration.body)
+    }
+
+    fn optional_expr(&mut self, expr: Option<&Expr>) -> Option<()> {
+        match expr {
+            Some(expr) => {
+                self.u8(1);
+                self.expr(expr)
+            }
+            None => {
+                self.u8(0);
+                Some(())
+            }
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) -> Option<()> {
+        match pattern {
+            Pattern::Wildcard(token) => {
+                self.u8(0);
+                self.token(token);
+                Some(())
+            }
+            Pattern::Literal(literal) => {
+                self.u8(1);
+                self.expr(literal)
+            }
+            Pattern::Binding(name) => {
+                self.u8(2);
+                self.token(name);
+                Some(())
+            }
+            Pattern::Array { bracket, elements } => {
+                self.u8(3);
+                self.token(bracket);
+                self.usize(elements.len());
+                elements.iter().try_for_each(|element| self.pattern(element))
+            }
+            Pattern::Map { brace, entries } => {
+                self.u8(4);
+                self.token(brace);
+                self.usize(entries.len());
+                entries.iter().try_for_each(|(key, pattern)| {
+                    self.token(key);
+                    self.pattern(pattern)
+                })
+            }
+        }
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) -> Option<()> {
+        self.usize(exprs.len());
+        exprs.iter().try_for_each(|expr| self.expr(expr))
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Option<()> {
+        match expr {
+            Expr::Assign { name, value, .. } => {
+                self.u8(0);
+                self.token(name);
+                self.expr(value)
+            }
+            Expr::Binary { left, operator, right } => {
+                self.u8(1);
+                self.expr(left)?;
+                self.token(operator);
+                self.expr(right)
+            }
+            Expr::Call { callee, paren, arguments } => {
+                self.u8(2);
+                self.expr(callee)?;
+                self.token(paren);
+                self.exprs(arguments)
+            }
+            Expr::Array { bracket, elements } => {
+                self.u8(3);
+                self.token(bracket);
+                self.exprs(elements)
+            }
+            Expr::Index { object, bracket, index } => {
+                self.u8(4);
+                self.expr(object)?;
+                self.token(bracket);
+                self.expr(index)
+            }
+            Expr::Slice { object, bracket, start, end } => {
+                self.u8(13);
+                self.expr(object)?;
+                self.token(bracket);
+                self.optional_expr(start.as_deref())?;
+                self.optional_expr(end.as_deref())
+            }
+            Expr::SetIndex { object, bracket, index, value } => {
+                self.u8(5);
+                self.expr(object)?;
+                self.token(bracket);
+                self.expr(index)?;
+                self.expr(value)
+            }
+            Expr::Get { object, name } => {
+                self.u8(6);
+                self.expr(object)?;
+                self.token(name);
+                Some(())
+            }
+            Expr::Set { object, name, value } => {
+                self.u8(7);
+                self.expr(object)?;
+                self.token(name);
+                self.expr(value)
+            }
+            Expr::This(keyword, _) => {
+                self.u8(8);
+                self.token(keyword);
+                Some(())
+            }
+            Expr::Grouping(expr) => {
+                self.u8(9);
+                self.expr(expr)
+            }
+            Expr::Literal { value, token } => {
+                self.u8(10);
+                self.token(token);
+                match value {
+                    LiteralValue::StringLiteral(s) => {
+                        self.u8(0);
+                        self.string(s);
+                    }
+                    LiteralValue::NumberLiteral(n) => {
+                        self.u8(1);
+                        self.out.extend(n.to_le_bytes());
+                    }
+                    LiteralValue::BooleanLiteral(b) => {
+                        self.u8(2);
+                        self.u8(u8::from(*b));
+                    }
+                    LiteralValue::Nil => self.u8(3),
+                    _ => return None, // Runtime values never come from the parser
+                }
+                Some(())
+            }
+            Expr::Unary { operator, right } => {
+                self.u8(11);
+                self.token(operator);
+                self.expr(right)
+            }
+            Expr::Variable(name, _) => {
+                self.u8(12);
+                self.token(name);
+                Some(())
+            }
+            Expr::Match { keyword, subject, arms } => {
+                self.u8(14);
+                self.token(keyword);
+                self.expr(subject)?;
+                self.usize(arms.len());
+                arms.iter().try_for_each(|arm| {
+                    self.pattern(&arm.pattern)?;
+                    self.expr(&arm.body)
+                })
+            }
+        }
+    }
+}
+
+pub(crate) struct Decoder<'a> {
+    pub(crate) bytes: &'a [u8],
+    pub(crate) position: usize,
+}
+
+impl Decoder<'_> {
+    pub(crate) fn take(&mut self, len: usize) -> Option<&[u8]> {
+        let bytes = self.bytes.get(self.position..self.position.checked_add(len)?)?;
+        self.position += len;
+        Some(bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    pub(crate) fn usize(&mut self) -> Option<usize> {
+        usize::try_from(u64::from_le_bytes(self.take(8)?.try_into().ok()?)).ok()
+    }
+
+    fn bool(&mut self) -> Option<bool> {
+        match self.u8()? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn string(&mut self) -> Option<String> {
+        let len = self.usize()?;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+
+    // Decode `count` items, without trusting the count for a preallocation
+    pub(crate) fn list<T>(&mut self, mut item: impl FnMut(&mut Self) -> Option<T>) -> Option<Vec<T>> {
+        let count = self.usize()?;
+        let mut items = Vec::new();
+        for _ in 0..count {
+            items.push(item(self)?);
+        }
+        Some(items)
+    }
+
+    fn token(&mut self) -> Option<Token> {
+        let token_type = *TokenType::ALL.get(usize::from(self.u8()?))?;
+        let lexeme = self.string()?;
+        let literal = if self.bool()? { Some(self.string()?) } else { None };
+        let line = self.usize()?;
+        let start = self.usize()?;
+        let column = self.usize()?;
+        Some(Token::new(token_type, lexeme, literal, line).at(start, column))
+    }
+
+    fn statements(&mut self) -> Option<Vec<Stmt>> {
+        self.list(Self::stmt)
+    }
+
+    fn stmt(&mut self) -> Option<Stmt> {
+        Some(match self.u8()? {
+            0 => Stmt::Expression(self.expr()?),
+            1 => Stmt::Print(self.expr()?),
+            2 => Stmt::Var { name: self.token()?, initializer: self.optional_expr()? },
+            3 => Stmt::Block(self.statements()?),
+            4 => Stmt::Function(self.function()?),
+            5 => {
+                let name = self.token()?;
+                let fields = self.list(|decoder| Some(FieldDecl { name: decoder.token()?, initializer: decoder.optional_expr()? }))?;
+                let methods = self.list(Self::function)?;
+                let class_methods = self.list(Self::function)?;
+                Stmt::Class(Arc::new(ClassDecl { name, fields, methods, class_methods }))
+            }
+            6 => Stmt::Return { keyword: self.token()?, value: self.optional_expr()? },
+            7 => Stmt::ForIn {
+                keyword: self.token()?,
+                name: self.token()?,
+                iterable: self.expr()?,
+                body: Box::new(self.stmt()?),
+            },
+            8 => Stmt::Defer { keyword: self.token()?, expression: self.expr()? },
+            _ => return None,
+        })
+    }
+
+    fn function(&mut self) -> Option<Arc<FunctionDecl>> {
+        let name = self.token()?;
+        let params = self.list(Self::token)?;
+        let getter = self.bool()?;
+        let body = self.statements()?;
+        Some(Arc::new(FunctionDecl { name, params, body, getter }))
+    }
+
+    // Some(None) for an absent expression, None for malformed input
+    fn optional_expr(&mut self) -> Option<Option<Expr>> {
+        if self.bool()? {
+            Some(Some(self.expr()?))
+        } else {
+            Some(None)
+        }
+    }
+
+    fn boxed(&mut self) -> Option<Box<Expr>> {
+        Some(Box::new(self.expr()?))
+    }
+
+    fn expr(&mut self) -> Option<Expr> {
+        Some(match self.u8()? {
+            0 => Expr::Assign { name: self.token()?, value: self.boxed()?, slot: LocalSlot::default() },
+            1 => Expr::Binary { left: self.boxed()?, operator: self.token()?, right: self.boxed()? },
+            2 => Expr::Call { callee: self.boxed()?, paren: self.token()?, arguments: self.list(Self::expr)? },
+            3 => Expr::Array { bracket: self.token()?, elements: self.list(Self::expr)? },
+            4 => Expr::Index { object: self.boxed()?, bracket: self.token()?, index: self.boxed()? },
+            5 => Expr::SetIndex { object: self.boxed()?, bracket: self.token()?, index: self.boxed()?, value: self.boxed()? },
+            6 => Expr::Get { object: self.boxed()?, name: self.token()? },
+            7 => Expr::Set { object: self.boxed()?, name: self.token()?, value: self.boxed()? },
+            8 => Expr::This(self.token()?, LocalSlot::default()),
+            9 => Expr::Grouping(self.boxed()?),
+            10 => {
+                let token = self.token()?;
+                let value = match self.u8()? {
+                    0 => LiteralValue::string(self.string()?),
+                    1 => LiteralValue::NumberLiteral(f64::from_le_bytes(self.take(8)?.try_into().ok()?)),
+                    2 => LiteralValue::BooleanLiteral(self.bool()?),
+                    3 => LiteralValue::Nil,
+                    _ => return None,
+                };
+                Expr::Literal { value, token }
+            }
+            11 => Expr::Unary { operator: self.token()?, right: self.boxed()? },
+            12 => Expr::Variable(self.token()?, LocalSlot::default()),
+            13 => Expr::Slice {
+                object: self.boxed()?,
+                bracket: self.token()?,
+                start: self.optional_expr()?.map(Box::new),
+                end: self.optional_expr()?.map(Box::new),
+            },
+            14 => Expr::Match {
+                keyword: self.token()?,
+                subject: self.boxed()?,
+                arms: self.list(|decoder| Some(MatchArm { pattern: decoder.pattern()?, body: decoder.expr()? }))?,
+            },
+            _ => return None,
+        })
+    }
+
+    fn pattern(&mut self) -> Option<Pattern> {
+        Some(match self.u8()? {
+            0 => Pattern::Wildcard(self.token()?),
+            1 => Pattern::Literal(self.expr()?),
+            2 => Pattern::Binding(self.token()?),
+            3 => Pattern::Array { bracket: self.token()?, elements: self.list(Self::pattern)? },
+            4 => Pattern::Map {
+                brace: self.token()?,
+                entries: self.list(|decoder| Some((decoder.token()?, decoder.pattern()?)))?,
+            },
+            _ => return None,
+        })
+    }
+}
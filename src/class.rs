@@ -0,0 +1,350 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::expr::LiteralValue;
+use crate::function::{Arity, Callable, HostMethod, HostMethodFn, LoxFunction, NativeContext};
+use crate::interpreter::{Environment, Interpreter, RuntimeError, Signal};
+use crate::stats;
+use crate::stmt::ClassDecl;
+
+// A class value. Cloning shares the class, so two values are equal only if they are
+// the same declaration evaluation.
+#[derive(Clone)]
+pub struct LoxClass {
+    inner: Arc<ClassData>,
+}
+
+enum ClassData {
+    Declared {
+        declaration: Arc<ClassDecl>,
+        closure: Arc<Mutex<Environment>>, // Scope the class was declared in
+        methods: BTreeMap<String, LoxFunction>,
+        class_methods: BTreeMap<String, LoxFunction>,
+    },
+    Host(HostClass),
+}
+
+impl LoxClass {
+    pub fn new(declaration: Arc<ClassDecl>, closure: Arc<Mutex<Environment>>) -> Self {
+        let mut methods = BTreeMap::new();
+        for method in &declaration.methods {
+            let function = LoxFunction {
+                is_initializer: method.name.lexeme == "init",
+                ..LoxFunction::new(Arc::clone(method), Arc::clone(&closure))
+            };
+            methods.insert(method.name.lexeme.clone(), function);
+        }
+        let mut class_methods = BTreeMap::new();
+        for method in &declaration.class_methods {
+            let function = LoxFunction::new(Arc::clone(method), Arc::clone(&closure));
+            class_methods.insert(method.name.lexeme.clone(), function);
+        }
+
+        LoxClass {
+            inner: Arc::new(ClassData::Declared {
+                declaration,
+                closure,
+                methods,
+                class_methods,
+            }),
+        }
+    }
+
+    pub fn host(class: HostClass) -> Self {
+        LoxClass { inner: Arc::new(ClassData::Host(class)) }
+    }
+
+    // The class's declaration in the script; None for host classes
+    pub fn declaration(&self) -> Option<&Arc<ClassDecl>> {
+        match &*self.inner {
+            ClassData::Declared { declaration, .. } => Some(declaration),
+            ClassData::Host(_) => None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match &*self.inner {
+            ClassData::Declared { declaration, .. } => &declaration.name.lexeme,
+            ClassData::Host(class) => &class.name,
+        }
+    }
+
+    // A method declared in Lox; host classes have none
+    pub fn find_method(&self, name: &str) -> Option<&LoxFunction> {
+        match &*self.inner {
+            ClassData::Declared { methods, .. } => methods.get(name),
+            ClassData::Host(_) => None,
+        }
+    }
+
+    // Names of the class methods, for suggestions
+    pub fn class_method_names(&self) -> Vec<String> {
+        match &*self.inner {
+            ClassData::Declared { class_methods, .. } => class_methods.keys().cloned().collect(),
+            ClassData::Host(_) => Vec::new(),
+        }
+    }
+
+    // Property access on the class itself: a class method bound to the class
+    pub fn get(&self, name: &str) -> Option<LiteralValue> {
+        let ClassData::Declared { class_methods, .. } = &*self.inner else {
+            return None;
+        };
+        let method = class_methods.get(name)?;
+        Some(LiteralValue::Function(method.bind(LiteralValue::Class(self.clone()))))
+    }
+
+    // The setter a host class runs when the property is assigned
+    pub fn host_setter(&self, name: &str) -> Option<HostSetter> {
+        match &*self.inner {
+            ClassData::Host(class) => class.setters.get(name).cloned(),
+            ClassData::Declared { .. } => None,
+        }
+    }
+
+    fn method_names(&self) -> Vec<String> {
+        match &*self.inner {
+            ClassData::Declared { methods, .. } => methods.keys().cloned().collect(),
+            ClassData::Host(class) => class.methods.keys().cloned().collect(),
+        }
+    }
+}
+
+// Calling a class constructs an instance, initializes its declared fields (in
+// declaration order, with `this` bound) and runs its `init` method, if any
+impl Callable for LoxClass {
+    fn name(&self) -> &str {
+        LoxClass::name(self)
+    }
+
+    fn arity(&self) -> Arity {
+        match &*self.inner {
+            ClassData::Declared { .. } => self.find_method("init").map_or(Arity::Fixed(0), LoxFunction::arity),
+            ClassData::Host(class) => class.arity,
+        }
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<LiteralValue>) -> Result<LiteralValue, Signal> {
+        let (declaration, closure) = match &*self.inner {
+            ClassData::Declared { declaration, closure, .. } => (declaration, closure),
+            ClassData::Host(class) => {
+                let line = interpreter.call_line();
+                let data = (class.constructor)(&mut NativeContext::new(interpreter, line), &arguments)?;
+                return Ok(LiteralValue::Instance(LoxInstance::with_boxed_data(self.clone(), data)));
+            }
+        };
+        let instance = LoxInstance::new(self.clone());
+        for field in &declaration.fields {
+            let value = match &field.initializer {
+                Some(initializer) => {
+                    let mut environment = Environment::from_enclosing(Arc::clone(closure));
+                    environment.define("this".to_string(), LiteralValue::Instance(instance.clone()));
+                    interpreter.evaluate_in(initializer, Arc::new(Mutex::new(environment)))?
+                }
+                None => LiteralValue::Nil,
+            };
+            instance.set(&field.name.lexeme, value);
+        }
+        if let Some(initializer) = self.find_method("init") {
+            initializer.bind(LiteralValue::Instance(instance.clone())).call(interpreter, arguments)?;
+        }
+        Ok(LiteralValue::Instance(instance))
+    }
+}
+
+impl PartialEq for LoxClass {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl fmt::Debug for LoxClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// An instance of a class. Instances are reference types: cloning shares the fields.
+// Fields are kept sorted by name, like map keys, so listing them is deterministic.
+#[derive(Clone)]
+pub struct LoxInstance {
+    inner: Arc<Mutex<InstanceData>>,
+}
+
+struct InstanceData {
+    class: LoxClass,
+    fields: BTreeMap<String, LiteralValue>,
+    data: Option<Box<dyn Any + Send>>, // What a host class's constructor returned
+}
+
+impl LoxInstance {
+    pub fn new(class: LoxClass) -> Self {
+        stats::object_created();
+        LoxInstance {
+            inner: Arc::new(Mutex::new(InstanceData {
+                class,
+                fields: BTreeMap::new(),
+                data: None,
+            })),
+        }
+    }
+
+    // An instance of a host class around a Rust value, without running the class's
+    // constructor: for natives that hand out host objects, like range()
+    pub fn with_host_data<T: Any + Send>(class: LoxClass, data: T) -> Self {
+        Self::with_boxed_data(class, Box::new(data))
+    }
+
+    fn with_boxed_data(class: LoxClass, data: Box<dyn Any + Send>) -> Self {
+        let instance = LoxInstance::new(class);
+        instance.inner.lock().unwrap().data = Some(data);
+        instance
+    }
+
+    pub fn class(&self) -> LoxClass {
+        self.inner.lock().unwrap().class.clone()
+    }
+
+    // Fields shadow methods; methods come back bound to this instance
+    pub fn get(&self, name: &str) -> Option<LiteralValue> {
+        let data = self.inner.lock().unwrap();
+        if let Some(value) = data.fields.get(name) {
+            return Some(value.clone());
+        }
+        let class = data.class.clone();
+        drop(data);
+        if let ClassData::Host(host) = &*class.inner {
+            let (arity, getter, function) = host.methods.get(name)?.clone();
+            return Some(LiteralValue::HostMethod(HostMethod {
+                name: name.to_string(),
+                arity,
+                getter,
+                receiver: self.clone(),
+                function,
+            }));
+        }
+        let method = class.find_method(name)?.clone();
+        Some(LiteralValue::Function(method.bind(LiteralValue::Instance(self.clone()))))
+    }
+
+    pub fn set(&self, name: &str, value: LiteralValue) {
+        self.inner.lock().unwrap().fields.insert(name.to_string(), value);
+    }
+
+    // Every name `get` would find: fields and methods
+    pub fn property_names(&self) -> Vec<String> {
+        let data = self.inner.lock().unwrap();
+        data.fields.keys().cloned().chain(data.class.method_names()).collect()
+    }
+
+    // Run `f` on the Rust value behind an instance of a host class. None if the
+    // instance has no value of type T. The instance is locked meanwhile, so `f` must
+    // not touch it through Lox.
+    pub fn with_data<T: 'static, R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let mut data = self.inner.lock().unwrap();
+        data.data.as_mut()?.downcast_mut::<T>().map(f)
+    }
+
+    // Names of the instance's fields, sorted
+    pub fn field_names(&self) -> Vec<String> {
+        self.inner.lock().unwrap().fields.keys().cloned().collect()
+    }
+}
+
+impl PartialEq for LoxInstance {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+// The last copy going away frees the fields
+impl Drop for LoxInstance {
+    fn drop(&mut self) {
+        if Arc::strong_count(&self.inner) == 1 {
+            stats::object_freed();
+        }
+    }
+}
+
+// Fields may refer back to the instance, so never print them
+impl fmt::Debug for LoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} instance", self.class().name())
+    }
+}
+
+// Signatures of the Rust closures behind a host class, see HostClass
+pub type HostConstructor = Arc<dyn Fn(&mut NativeContext, &[LiteralValue]) -> Result<Box<dyn Any + Send>, RuntimeError> + Send + Sync>;
+pub type HostSetter = Arc<dyn Fn(&mut NativeContext, &LoxInstance, LiteralValue) -> Result<(), RuntimeError> + Send + Sync>;
+
+// A class implemented in Rust, so embedders can hand their own types to scripts:
+//
+//     let class = HostClass::new("Vec2", Arity::Fixed(2), |context, arguments| Ok(Vec2::new(...)))
+//         .method("length", Arity::Fixed(0), |_, this, _| Ok(this.with_data(|v: &mut Vec2| v.length()).into()))
+//         .getter("x", |_, this| Ok(this.with_data(|v: &mut Vec2| v.x).into()))
+//         .setter("x", |context, this, value| ...);
+//     interpreter.register_class(class);
+//
+// Each instance holds the value its constructor returned, reached with
+// LoxInstance::with_data. Scripts can still set fields of their own on instances.
+#[derive(Clone)]
+pub struct HostClass {
+    name: String,
+    arity: Arity, // Of the constructor
+    constructor: HostConstructor,
+    methods: BTreeMap<String, (Arity, bool, HostMethodFn)>, // The bool marks getters
+    setters: BTreeMap<String, HostSetter>,
+}
+
+impl HostClass {
+    pub fn new<T: Any + Send>(
+        name: &str,
+        arity: Arity,
+        constructor: impl Fn(&mut NativeContext, &[LiteralValue]) -> Result<T, RuntimeError> + Send + Sync + 'static,
+    ) -> Self {
+        HostClass {
+            name: name.to_string(),
+            arity,
+            constructor: Arc::new(move |context, arguments| Ok(Box::new(constructor(context, arguments)?) as Box<dyn Any + Send>)),
+            methods: BTreeMap::new(),
+            setters: BTreeMap::new(),
+        }
+    }
+
+    pub fn method(
+        mut self,
+        name: &str,
+        arity: Arity,
+        method: impl Fn(&mut NativeContext, &LoxInstance, &[LiteralValue]) -> Result<LiteralValue, RuntimeError> + Send + Sync + 'static,
+    ) -> Self {
+        self.methods.insert(name.to_string(), (arity, false, Arc::new(method)));
+        self
+    }
+
+    // A property computed on access, like a Lox getter
+    pub fn getter(
+        mut self,
+        name: &str,
+        getter: impl Fn(&mut NativeContext, &LoxInstance) -> Result<LiteralValue, RuntimeError> + Send + Sync + 'static,
+    ) -> Self {
+        let method: HostMethodFn = Arc::new(move |context, this, _| getter(context, this));
+        self.methods.insert(name.to_string(), (Arity::Fixed(0), true, method));
+        self
+    }
+
+    // Runs instead of storing a field when the script assigns the property
+    pub fn setter(
+        mut self,
+        name: &str,
+        setter: impl Fn(&mut NativeContext, &LoxInstance, LiteralValue) -> Result<(), RuntimeError> + Send + Sync + 'static,
+    ) -> Self {
+        self.setters.insert(name.to_string(), Arc::new(setter));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
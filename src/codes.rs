@@ -0,0 +1,436 @@
+use crate::diagnostic::Stage;
+
+// Stable codes for the diagnostics of every phase, shown in JSON diagnostics and
+// explained by `explain <code>`. A diagnostic's code is found from its message, so
+// the phases need not pass codes around. Codes are never reused: E00xx scan, E01xx
+// parse, E02xx resolve (W02xx lints), E03xx runtime.
+#[derive(Debug)]
+pub struct ErrorCode {
+    pub code: &'static str,
+    pub stage: Stage,
+    // Messages with this code; `{}` stands for any text
+    pub messages: &'static [&'static str],
+    pub explanation: &'static str,
+    pub example: &'static str,
+}
+
+pub const CODES: &[ErrorCode] = &[
+    ErrorCode {
+        code: "E0001",
+        stage: Stage::Scan,
+        messages: &["Unexpected character: {}"],
+        explanation: "The character can't start any token. Lox has no use for characters such as '@', '#' or '$' outside of strings and comments.",
+        example: "var price = $5;",
+    },
+    ErrorCode {
+        code: "E0002",
+        stage: Stage::Scan,
+        messages: &["Unterminated string."],
+        explanation: "A string was opened with '\"' but the file ended before the closing '\"'. Strings may span lines, so the error can be reported far below the opening quote.",
+        example: "print \"hello;",
+    },
+    ErrorCode {
+        code: "E0003",
+        stage: Stage::Scan,
+        messages: &["String literal too long (limit is {} bytes)."],
+        explanation: "A string literal is longer than --max-string allows.",
+        example: "print \"...a very long string...\";",
+    },
+    ErrorCode {
+        code: "E0004",
+        stage: Stage::Scan,
+        messages: &["Too many tokens (limit is {})."],
+        explanation: "The program has more tokens than --max-tokens allows. Scanning stops at the limit.",
+        example: "print 1 + 1 + 1 + 1; // with --max-tokens=4",
+    },
+    ErrorCode {
+        code: "E0005",
+        stage: Stage::Scan,
+        messages: &["Failed to read file {}"],
+        explanation: "The file doesn't exist or can't be read.",
+        example: "",
+    },
+    ErrorCode {
+        code: "E0101",
+        stage: Stage::Parse,
+        messages: &["Expect expression."],
+        explanation: "An expression was expected here, such as a literal, a name, a call or a parenthesized expression. Often an operator is missing an operand.",
+        example: "print 1 + ;",
+    },
+    ErrorCode {
+        code: "E0102",
+        stage: Stage::Parse,
+        messages: &["Expect ';' after {}"],
+        explanation: "Every statement that doesn't end with a block ends with ';'. The error is reported at the token after the place the ';' is missing.",
+        example: "print 1\nprint 2;",
+    },
+    ErrorCode {
+        code: "E0103",
+        stage: Stage::Parse,
+        messages: &["Unexpected identifier after '{}'. Expect ';' after expression."],
+        explanation: "A statement starts with two names in a row, which is never valid. Either a ';' is missing between two statements or a keyword is misspelled.",
+        example: "vr count = 1;",
+    },
+    ErrorCode {
+        code: "E0104",
+        stage: Stage::Parse,
+        messages: &["Expect ')' after {}"],
+        explanation: "A '(' of a grouping, a call or a parameter list is not closed.",
+        example: "print (1 + 2;",
+    },
+    ErrorCode {
+        code: "E0105",
+        stage: Stage::Parse,
+        messages: &["Expect ']' after {}"],
+        explanation: "A '[' of an array literal or an index is not closed.",
+        example: "var a = [1, 2;",
+    },
+    ErrorCode {
+        code: "E0106",
+        stage: Stage::Parse,
+        messages: &["Expect '{' before {}"],
+        explanation: "Function, method and class bodies are blocks and start with '{'.",
+        example: "fun f() return 1;",
+    },
+    ErrorCode {
+        code: "E0107",
+        stage: Stage::Parse,
+        messages: &["Expect '}' after {}"],
+        explanation: "A block or class body is not closed before the end of the file.",
+        example: "{ print 1;",
+    },
+    ErrorCode {
+        code: "E0108",
+        stage: Stage::Parse,
+        messages: &["Expect '(' after {} name."],
+        explanation: "A function or method name is followed by its parameter list in parentheses, even when it takes no parameters. Only getters, which are methods, leave it out.",
+        example: "fun f { return 1; }",
+    },
+    ErrorCode {
+        code: "E0109",
+        stage: Stage::Parse,
+        messages: &["Expect {} name.", "Expect property name after '.'."],
+        explanation: "A name was expected: after 'var', 'fun' or 'class', in a parameter list, or after '.'. Keywords can't be used as names.",
+        example: "var class = 1;",
+    },
+    ErrorCode {
+        code: "E0110",
+        stage: Stage::Parse,
+        messages: &["Invalid assignment target."],
+        explanation: "Only variables, properties and array or map elements can be assigned to. The left-hand side here is some other expression, such as a literal or a call.",
+        example: "1 = 2;",
+    },
+    ErrorCode {
+        code: "E0111",
+        stage: Stage::Parse,
+        messages: &["Can't have more than 255 {}"],
+        explanation: "A function can have at most 255 parameters, and a call at most 255 arguments.",
+        example: "f(a1, a2, /* ... */ a256);",
+    },
+    ErrorCode {
+        code: "E0112",
+        stage: Stage::Parse,
+        messages: &["Too much nesting (limit is {})."],
+        explanation: "Expressions or blocks are nested more deeply than --max-depth allows.",
+        example: "print ((((((((((1)))))))))); // with --max-depth=5",
+    },
+    ErrorCode {
+        code: "E0113",
+        stage: Stage::Parse,
+        messages: &["Expect '(' after 'for'.", "Expect 'var' after 'for ('.", "Expect 'in' after loop variable."],
+        explanation: "A for loop goes over the elements of an array, the keys of a map or the characters of a string, and is written `for (var name in collection) statement`. The C-style `for (init; condition; step)` isn't supported.",
+        example: "for (var i = 0; i < 3; i = i + 1) print i;",
+    },
+    ErrorCode {
+        code: "E0114",
+        stage: Stage::Parse,
+        messages: &["{} are not part of the book's Lox; use --lang=extended."],
+        explanation: "With `--lang=book` only the language of Crafting Interpreters is accepted, so that output matches the CodeCrafters tests exactly. For-in loops, class fields and class methods are additions; so are arrays, whose brackets are then unexpected characters.",
+        example: "class Counter { var count = 0; }",
+    },
+    ErrorCode {
+        code: "E0115",
+        stage: Stage::Parse,
+        messages: &[
+            "Expect '{' after match value.",
+            "Expect '=>' after pattern.",
+            "Expect pattern.",
+            "Expect string key in map pattern.",
+            "Expect ':' after map pattern key.",
+        ],
+        explanation: "A match expression is written `match value { pattern => result, ... }`. A pattern is `_`, a name to bind, a number, string, `true`, `false` or `nil`, an array of patterns `[a, _]`, or a map of string keys to patterns `{\"op\": op}`.",
+        example: "print match 1 { 1 -> \"one\" };",
+    },
+    ErrorCode {
+        code: "E0201",
+        stage: Stage::Resolve,
+        messages: &["Can't read local variable in its own initializer."],
+        explanation: "A local variable's initializer refers to the variable itself, which is not defined until the initializer has run.",
+        example: "{ var a = a + 1; }",
+    },
+    ErrorCode {
+        code: "E0202",
+        stage: Stage::Resolve,
+        messages: &["Already a variable with this name in this scope."],
+        explanation: "A local scope declares the same name twice. Assign to the existing variable or pick another name. Globals may be redeclared.",
+        example: "{ var a = 1; var a = 2; }",
+    },
+    ErrorCode {
+        code: "E0203",
+        stage: Stage::Resolve,
+        messages: &["Can't use 'this' outside of a class."],
+        explanation: "'this' refers to the instance a method was called on, so it only exists inside methods.",
+        example: "print this;",
+    },
+    ErrorCode {
+        code: "E0204",
+        stage: Stage::Resolve,
+        messages: &["Can't return a value from an initializer."],
+        explanation: "init() always returns the new instance. A bare 'return;' is allowed to leave it early.",
+        example: "class A { init() { return 1; } }",
+    },
+    ErrorCode {
+        code: "E0205",
+        stage: Stage::Resolve,
+        messages: &["An initializer can't be a getter."],
+        explanation: "init() is called with the constructor's arguments, so it needs a parameter list.",
+        example: "class A { init { this.x = 1; } }",
+    },
+    ErrorCode {
+        code: "E0206",
+        stage: Stage::Resolve,
+        messages: &["Can't use 'defer' outside of a block."],
+        explanation: "A deferred expression runs when the block or function body around it exits. The top level of a program has no enclosing block.",
+        example: "defer clock();",
+    },
+    ErrorCode {
+        code: "E0207",
+        stage: Stage::Resolve,
+        messages: &["Can't return from top-level code."],
+        explanation: "With `--lang=book`, as in the book, `return` only leaves a function. The extended language allows it at the top level, where it ends the script.",
+        example: "return;",
+    },
+    ErrorCode {
+        code: "W0201",
+        stage: Stage::Resolve,
+        messages: &["Variable '{}' shadows a variable in an enclosing scope."],
+        explanation: "The shadowing lint (--warn=shadowing): a local declaration hides a variable of the same name from an enclosing scope, which is then out of reach. Silence it for one statement with `// lox-ignore: shadowing`.",
+        example: "var a = 1;\n{ var a = 2; }",
+    },
+    ErrorCode {
+        code: "W0202",
+        stage: Stage::Resolve,
+        messages: &["Parameter '{}' is never read."],
+        explanation: "The unused-parameter lint (--warn=unused-parameter): the function never uses a parameter.",
+        example: "fun f(a, b) { return a; }",
+    },
+    ErrorCode {
+        code: "W0203",
+        stage: Stage::Resolve,
+        messages: &["Unknown lint '{}' in lox-ignore comment."],
+        explanation: "A `// lox-ignore:` comment names a lint that doesn't exist. The names are those of --warn: shadowing, unused-parameter, or all.",
+        example: "fun f(a) {} // lox-ignore: unused-param",
+    },
+    ErrorCode {
+        code: "E0301",
+        stage: Stage::Runtime,
+        messages: &["Undefined variable '{}'.", "Undefined variable '{}'. Did you mean '{}'?"],
+        explanation: "No variable of this name is in scope. Check the spelling, or declare it with 'var' before it is used.",
+        example: "print count;",
+    },
+    ErrorCode {
+        code: "E0302",
+        stage: Stage::Runtime,
+        messages: &["Variable '{}' used before assignment."],
+        explanation: "With --strict-init, a variable declared without an initializer must be assigned before it is read.",
+        example: "var a;\nprint a;",
+    },
+    ErrorCode {
+        code: "E0303",
+        stage: Stage::Runtime,
+        messages: &["Undefined property '{}'.", "Undefined property '{}'. Did you mean '{}'?"],
+        explanation: "The instance has no field and its class no method of this name.",
+        example: "class A {}\nprint A().x;",
+    },
+    ErrorCode {
+        code: "E0304",
+        stage: Stage::Runtime,
+        messages: &["Operand must be a number.", "Operands must be numbers."],
+        explanation: "'-', '*', '/', unary '-' and the comparisons work on numbers only.",
+        example: "print -\"a\";",
+    },
+    ErrorCode {
+        code: "E0305",
+        stage: Stage::Runtime,
+        messages: &["Operands must be two numbers or two strings."],
+        explanation: "'+' adds two numbers or joins two strings; it doesn't convert one to the other.",
+        example: "print \"a\" + 1;",
+    },
+    ErrorCode {
+        code: "E0306",
+        stage: Stage::Runtime,
+        messages: &["Division by zero."],
+        explanation: "The divisor of '/' is zero.",
+        example: "print 1 / 0;",
+    },
+    ErrorCode {
+        code: "E0307",
+        stage: Stage::Runtime,
+        messages: &["Can only call functions and classes."],
+        explanation: "The value before '(' is not a function, method or class.",
+        example: "var a = 1;\na();",
+    },
+    ErrorCode {
+        code: "E0308",
+        stage: Stage::Runtime,
+        messages: &["Expected {} arguments but got {}."],
+        explanation: "A function or class was called with the wrong number of arguments.",
+        example: "fun f(a) {}\nf(1, 2);",
+    },
+    ErrorCode {
+        code: "E0309",
+        stage: Stage::Runtime,
+        messages: &["Only instances have properties.", "Only instances have fields."],
+        explanation: "'.' reads or sets a property, which only instances of classes have.",
+        example: "var a = 1;\nprint a.x;",
+    },
+    ErrorCode {
+        code: "E0310",
+        stage: Stage::Runtime,
+        messages: &["Only arrays, maps and strings can be indexed.", "Only array and map elements can be assigned."],
+        explanation: "'[...]' reads an element of an array, map or string, and assigns one of an array or map.",
+        example: "var a = 1;\nprint a[0];",
+    },
+    ErrorCode {
+        code: "E0311",
+        stage: Stage::Runtime,
+        messages: &["Index must be an integer.", "Index {} out of bounds for length {}."],
+        explanation: "Array and string indexes are whole numbers from 0 to the length minus one.",
+        example: "print [1, 2][2];",
+    },
+    ErrorCode {
+        code: "E0312",
+        stage: Stage::Runtime,
+        messages: &["Map keys must be strings."],
+        explanation: "Maps are indexed by strings only.",
+        example: "var m = {\"a\": 1};\nprint m[1];",
+    },
+    ErrorCode {
+        code: "E0313",
+        stage: Stage::Runtime,
+        messages: &["toString() must return a string."],
+        explanation: "A class's toString() method is used when an instance is printed, so it must return a string.",
+        example: "class A { toString() { return 1; } }\nprint A();",
+    },
+    ErrorCode {
+        code: "E0314",
+        stage: Stage::Runtime,
+        messages: &["{}() requires the '{}' capability."],
+        explanation: "The native function needs a capability the script wasn't granted: read, write, env, args or sleep. Grant it with --allow-<capability>, or in lox.toml's allow list.",
+        example: "print env(\"HOME\"); // with --sandbox",
+    },
+    ErrorCode {
+        code: "E0315",
+        stage: Stage::Runtime,
+        messages: &["{}() expects {}", "{}() comparator must return a number.", "Cannot convert '{}' to a number."],
+        explanation: "A native function was given an argument of the wrong type or value.",
+        example: "print len(1);",
+    },
+    ErrorCode {
+        code: "E0316",
+        stage: Stage::Runtime,
+        messages: &["Invalid JSON: {}", "Cannot convert to JSON: {}"],
+        explanation: "jsonParse() was given malformed JSON, or jsonStringify() a value JSON can't represent, such as a function or a value that contains itself.",
+        example: "print jsonParse(\"{\");",
+    },
+    ErrorCode {
+        code: "E0317",
+        stage: Stage::Runtime,
+        messages: &["Failed to read input: {}", "Failed to write output: {}", "Failed to write error output: {}"],
+        explanation: "Reading standard input or writing standard output or error failed, for example because a pipe was closed.",
+        example: "",
+    },
+    ErrorCode {
+        code: "E0318",
+        stage: Stage::Runtime,
+        messages: &[
+            "Only arrays and strings can be sliced.",
+            "Slice index {} out of bounds for length {}.",
+            "Slice start {} is after end {}.",
+        ],
+        explanation: "'[start:end]' copies part of an array or string. Negative bounds count from the end, so the bounds run from minus the length to the length, and start can't come after end.",
+        example: "print \"abc\"[1:5];",
+    },
+    ErrorCode {
+        code: "E0319",
+        stage: Stage::Runtime,
+        messages: &[
+            "Only arrays, maps, strings and instances with an iter() method can be looped over.",
+            "iter() must return an instance with a next() method.",
+        ],
+        explanation: "A for loop goes over the elements of an array, the keys of a map or the characters of a string. An instance can be looped over if its class has an iter() method returning an iterator: an instance whose next() method returns each value in turn, then nil when there are no more.",
+        example: "for (var x in 3) print x;",
+    },
+    ErrorCode {
+        code: "E0320",
+        stage: Stage::Runtime,
+        messages: &["No pattern matches the {} value."],
+        explanation: "None of a match expression's arms matched its value. End the arms with `_ => ...` to handle everything else.",
+        example: "print match 3 { 1 => \"one\", 2 => \"two\" };",
+    },
+    ErrorCode {
+        code: "E0321",
+        stage: Stage::Runtime,
+        messages: &["Failed to read '{}': {}", "Failed to write '{}': {}"],
+        explanation: "readFile() or writeFile() couldn't reach the file: it doesn't exist, its directory doesn't, or the process may not read or write it.",
+        example: "print readFile(\"no/such/file.txt\");",
+    },
+];
+
+pub fn lookup(code: &str) -> Option<&'static ErrorCode> {
+    CODES.iter().find(|entry| entry.code.eq_ignore_ascii_case(code))
+}
+
+// The code of a diagnostic message, if it is one of the catalog's
+pub fn code_for(stage: Stage, message: &str) -> Option<&'static str> {
+    CODES
+        .iter()
+        .find(|entry| entry.stage == stage && entry.messages.iter().any(|pattern| matches(pattern, message)))
+        .map(|entry| entry.code)
+}
+
+// A message matches when it has the pattern's text, in order, with any text in the
+// place of each `{}`
+fn matches(pattern: &str, message: &str) -> bool {
+    let mut parts = pattern.split("{}");
+    let Some(mut rest) = message.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    // Without a `{}` the message is the pattern; otherwise it ends like it
+    let Some(last) = parts.pop() else {
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+// The text printed by `explain <code>`
+pub fn explain(entry: &ErrorCode) -> String {
+    let kind = if entry.code.starts_with('W') { "warning" } else { "error" };
+    let mut out = format!("{} ({} {})\n\n{}\n", entry.code, entry.stage.name(), kind, entry.explanation);
+    if !entry.example.is_empty() {
+        out.push_str("\nFor example:\n\n");
+        for line in entry.example.lines() {
+            out.push_str("    ");
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
@@ -0,0 +1,369 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::{self, Command};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Flags that change what a command prints, and so can't be combined with
+// `--conformance`. A prefix ending in `=` matches any value.
+pub const OUTPUT_FLAGS: &[&str] = &[
+    "--diagnostics=",
+    "--color=",
+    "--lang=extended",
+    "--extensions",
+    "--comments",
+    "--recover-strings",
+    "--program",
+    "--source-literals",
+    "--precision=",
+    "--display-depth=",
+    "--display-width=",
+    "--warn=",
+    "--deny-warnings",
+    "--timings",
+    "--stats",
+];
+
+// The first flag of `flags` that `--conformance` rejects
+pub fn output_flag<'a>(flags: &[&'a String]) -> Option<&'a str> {
+    flags.iter().map(|flag| flag.as_str()).find(|flag| {
+        OUTPUT_FLAGS.iter().any(|output| if output.ends_with('=') { flag.starts_with(output) } else { flag == output })
+    })
+}
+
+// One command run on one program, with exactly what the CodeCrafters tester expects
+// to see. Runtime errors keep the interpreter's ` [line N]` form, which the tester
+// accepts.
+#[derive(Debug, Clone, Copy)]
+pub struct Check {
+    pub stage: &'static str, // The tester's stage it mirrors
+    pub command: &'static str,
+    pub source: &'static str,
+    pub stdout: &'static str,
+    pub stderr: &'static str,
+    pub exit_code: i32,
+}
+
+// The graded output of every stage, in the tester's order
+pub const CHECKLIST: &[Check] = &[
+    Check { stage: "scanning: empty file", command: "tokenize", source: "", stdout: "EOF  null\n", stderr: "", exit_code: 0 },
+    Check {
+        stage: "scanning: parentheses and braces",
+        command: "tokenize",
+        source: "({})",
+        stdout: "LEFT_PAREN ( null\nLEFT_BRACE { null\nRIGHT_BRACE } null\nRIGHT_PAREN ) null\nEOF  null\n",
+        stderr: "",
+        exit_code: 0,
+    },
+    Check {
+        stage: "scanning: other single-character tokens",
+        command: "tokenize",
+        source: "*.,+-;",
+        stdout: "STAR * null\nDOT . null\nCOMMA , null\nPLUS + null\nMINUS - null\nSEMICOLON ; null\nEOF  null\n",
+        stderr: "",
+        exit_code: 0,
+    },
+    Check {
+        stage: "scanning: lexical errors",
+        command: "tokenize",
+        source: ",.$(#",
+        stdout: "COMMA , null\nDOT . null\nLEFT_PAREN ( null\nEOF  null\n",
+        stderr: "[line 1] Error: Unexpected character: $\n[line 1] Error: Unexpected character: #\n",
+        exit_code: 65,
+    },
+    Check {
+        stage: "scanning: book-only characters",
+        command: "tokenize",
+        source: "[:]",
+        stdout: "EOF  null\n",
+        stderr: "[line 1] Error: Unexpected character: [\n[line 1] Error: Unexpected character: :\n[line 1] Error: Unexpected character: ]\n",
+        exit_code: 65,
+    },
+    Check {
+        stage: "scanning: operators",
+        command: "tokenize",
+        source: "={===}!!=<<=>>=/",
+        stdout: "EQUAL = null\nLEFT_BRACE { null\nEQUAL_EQUAL == null\nEQUAL = null\nRIGHT_BRACE } null\nBANG ! null\n\
+                 BANG_EQUAL != null\nLESS < null\nLESS_EQUAL <= null\nGREATER > null\nGREATER_EQUAL >= null\nSLASH / null\nEOF  null\n",
+        stderr: "",
+        exit_code: 0,
+    },
+    Check {
+        stage: "scanning: comments and whitespace",
+        command: "tokenize",
+        source: "(\t)\n// comment\n{ }",
+        stdout: "LEFT_PAREN ( null\nRIGHT_PAREN ) null\nLEFT_BRACE { null\nRIGHT_BRACE } null\nEOF  null\n",
+        stderr: "",
+        exit_code: 0,
+    },
+    Check {
+        stage: "scanning: multi-line errors",
+        command: "tokenize",
+        source: "()\n\t@",
+        stdout: "LEFT_PAREN ( null\nRIGHT_PAREN ) null\nEOF  null\n",
+        stderr: "[line 2] Error: Unexpected character: @\n",
+        exit_code: 65,
+    },
+    Check {
+        stage: "scanning: strings",
+        command: "tokenize",
+        source: "\"foo baz\" \"bar",
+        stdout: "STRING \"foo baz\" foo baz\nEOF  null\n",
+        stderr: "[line 1] Error: Unterminated string.\n",
+        exit_code: 65,
+    },
+    Check {
+        stage: "scanning: numbers",
+        command: "tokenize",
+        source: "42 1234.1234 200.00",
+        stdout: "NUMBER 42 42.0\nNUMBER 1234.1234 1234.1234\nNUMBER 200.00 200.0\nEOF  null\n",
+        stderr: "",
+        exit_code: 0,
+    },
+    Check {
+        stage: "scanning: identifiers and reserved words",
+        command: "tokenize",
+        source: "foo_1 and while",
+        stdout: "IDENTIFIER foo_1 null\nAND and null\nWHILE while null\nEOF  null\n",
+        stderr: "",
+        exit_code: 0,
+    },
+    Check { stage: "parsing: literals", command: "parse", source: "nil", stdout: "nil\n", stderr: "", exit_code: 0 },
+    Check { stage: "parsing: numbers", command: "parse", source: "42", stdout: "42.0\n", stderr: "", exit_code: 0 },
+    Check {
+        stage: "parsing: parentheses",
+        command: "parse",
+        source: "(\"foo\")",
+        stdout: "(group foo)\n",
+        stderr: "",
+        exit_code: 0,
+    },
+    Check { stage: "parsing: unary operators", command: "parse", source: "!!true", stdout: "(! (! true))\n", stderr: "", exit_code: 0 },
+    Check {
+        stage: "parsing: arithmetic",
+        command: "parse",
+        source: "16 * 38 / 58 - 2",
+        stdout: "(- (/ (* 16.0 38.0) 58.0) 2.0)\n",
+        stderr: "",
+        exit_code: 0,
+    },
+    Check {
+        stage: "parsing: comparison and equality",
+        command: "parse",
+        source: "83 < 99 == 115 >= 1",
+        stdout: "(== (< 83.0 99.0) (>= 115.0 1.0))\n",
+        stderr: "",
+        exit_code: 0,
+    },
+    Check {
+        stage: "parsing: syntax errors",
+        command: "parse",
+        source: "(72 +)",
+        stdout: "",
+        stderr: "[line 1] Error at ')': Expect expression.\n",
+        exit_code: 65,
+    },
+    Check {
+        stage: "parsing: invalid assignment target",
+        command: "parse",
+        source: "1 = 2",
+        stdout: "",
+        stderr: "[line 1] Error at '=': Invalid assignment target.\n",
+        exit_code: 65,
+    },
+    Check {
+        stage: "evaluating: invalid assignment target",
+        command: "evaluate",
+        source: "1 = 2",
+        stdout: "",
+        stderr: "[line 1] Error at '=': Invalid assignment target.\n",
+        exit_code: 65,
+    },
+    Check { stage: "evaluating: literals", command: "evaluate", source: "nil", stdout: "nil\n", stderr: "", exit_code: 0 },
+    Check { stage: "evaluating: numbers", command: "evaluate", source: "10.40", stdout: "10.4\n", stderr: "", exit_code: 0 },
+    Check { stage: "evaluating: strings", command: "evaluate", source: "((\"hello\"))", stdout: "hello\n", stderr: "", exit_code: 0 },
+    Check { stage: "evaluating: unary operators", command: "evaluate", source: "!nil", stdout: "true\n", stderr: "", exit_code: 0 },
+    Check { stage: "evaluating: arithmetic", command: "evaluate", source: "42 / 5", stdout: "8.4\n", stderr: "", exit_code: 0 },
+    Check {
+        stage: "evaluating: string concatenation",
+        command: "evaluate",
+        source: "\"foo\" + \"bar\"",
+        stdout: "foobar\n",
+        stderr: "",
+        exit_code: 0,
+    },
+    Check { stage: "evaluating: equality", command: "evaluate", source: "\"10\" == 10", stdout: "false\n", stderr: "", exit_code: 0 },
+    Check {
+        stage: "evaluating: binary operand errors",
+        command: "evaluate",
+        source: "1 < \"a\"",
+        stdout: "",
+        stderr: "Operands must be numbers.\n [line 1]\n",
+        exit_code: 70,
+    },
+    Check {
+        stage: "evaluating: runtime errors",
+        command: "evaluate",
+        source: "-\"foo\"",
+        stdout: "",
+        stderr: "Operand must be a number.\n [line 1]\n",
+        exit_code: 70,
+    },
+    Check {
+        stage: "statements: print",
+        command: "run",
+        source: "print \"Hello\";\nprint 1 + 2;",
+        stdout: "Hello\n3\n",
+        stderr: "",
+        exit_code: 0,
+    },
+    Check {
+        stage: "statements: missing semicolon",
+        command: "run",
+        source: "print \"a\" print;",
+        stdout: "",
+        stderr: "[line 1] Error at 'print': Expect ';' after value.\n",
+        exit_code: 65,
+    },
+    Check {
+        stage: "statements: variables and assignment",
+        command: "run",
+        source: "var a = 1;\nvar b;\nb = a = 2;\nprint a + b;\nprint b;",
+        stdout: "4\n2\n",
+        stderr: "",
+        exit_code: 0,
+    },
+    Check {
+        stage: "statements: undefined variables",
+        command: "run",
+        source: "print 1;\nprint b;",
+        stdout: "1\n",
+        stderr: "Undefined variable 'b'.\n [line 2]\n",
+        exit_code: 70,
+    },
+    Check {
+        stage: "statements: undefined variable with a close name",
+        command: "run",
+        source: "var a = 1;\nprint b;",
+        stdout: "",
+        stderr: "Undefined variable 'b'.\n [line 2]\n",
+        exit_code: 70,
+    },
+    Check {
+        stage: "statements: missing semicolon between names",
+        command: "run",
+        source: "foo bar;",
+        stdout: "",
+        stderr: "[line 1] Error at 'bar': Expect ';' after expression.\n",
+        exit_code: 65,
+    },
+    Check {
+        stage: "statements: block scopes",
+        command: "run",
+        source: "var a = \"outer\";\n{\n  var a = \"inner\";\n  print a;\n}\nprint a;",
+        stdout: "inner\nouter\n",
+        stderr: "",
+        exit_code: 0,
+    },
+    Check {
+        stage: "resolving: redeclared local",
+        command: "run",
+        source: "{ var a = 1; var a = 2; }",
+        stdout: "",
+        stderr: "[line 1] Error at 'a': Already a variable with this name in this scope.\n",
+        exit_code: 65,
+    },
+    Check {
+        stage: "resolving: local in its own initializer",
+        command: "run",
+        source: "{ var a = a; }",
+        stdout: "",
+        stderr: "[line 1] Error at 'a': Can't read local variable in its own initializer.\n",
+        exit_code: 65,
+    },
+    Check {
+        stage: "resolving: top-level return",
+        command: "run",
+        source: "return 1;",
+        stdout: "",
+        stderr: "[line 1] Error at 'return': Can't return from top-level code.\n",
+        exit_code: 65,
+    },
+    Check {
+        stage: "functions: closures bind at declaration",
+        command: "run",
+        source: "var a = \"global\";\n{\n  fun showA() { print a; }\n  showA();\n  var a = \"block\";\n  showA();\n}",
+        stdout: "global\nglobal\n",
+        stderr: "",
+        exit_code: 0,
+    },
+    Check {
+        stage: "functions: closures",
+        command: "run",
+        source: "fun counter() {\n  var n = 0;\n  fun next() { n = n + 1; return n; }\n  return next;\n}\nvar c = counter();\nc();\nprint c();",
+        stdout: "2\n",
+        stderr: "",
+        exit_code: 0,
+    },
+    Check {
+        stage: "classes: methods and initializers",
+        command: "run",
+        source: "class Point {\n  init(x) { this.x = x; }\n  get() { return this.x; }\n}\nprint Point(3).get();\nprint Point;",
+        stdout: "3\nPoint\n",
+        stderr: "",
+        exit_code: 0,
+    },
+    Check {
+        stage: "book only: no extensions",
+        command: "run",
+        source: "class A { var x; }",
+        stdout: "",
+        stderr: "[line 1] Error at 'var': Class fields are not part of the book's Lox; use --lang=extended.\n",
+        exit_code: 65,
+    },
+];
+
+// Output the tester also sees without any flag, as it runs the interpreter: the
+// defaults must be book-exact too. `tokenize` scans the book's Lox unless told
+// otherwise.
+pub const UNFLAGGED: &[Check] = &[Check {
+    stage: "scanning: book-only characters, without flags",
+    command: "tokenize",
+    source: "[1]:",
+    stdout: "NUMBER 1 1.0\nEOF  null\n",
+    stderr: "[line 1] Error: Unexpected character: [\n[line 1] Error: Unexpected character: ]\n[line 1] Error: Unexpected character: :\n",
+    exit_code: 65,
+}];
+
+// Numbers the programs `run` writes, so checks run from several threads at once
+// don't overwrite each other's file
+static NEXT_PROGRAM: AtomicUsize = AtomicUsize::new(0);
+
+// Run `executable <command> --conformance <file>` on a check's program, or without
+// `--conformance`, and compare everything it printed and its exit code. The error
+// names the first difference.
+pub fn run(executable: &Path, check: &Check, conformance: bool) -> Result<(), String> {
+    let number = NEXT_PROGRAM.fetch_add(1, Ordering::Relaxed);
+    let path = env::temp_dir().join(format!("lox-conformance-{}-{}.lox", process::id(), number));
+    fs::write(&path, check.source).map_err(|error| format!("can't write {}: {}", path.display(), error))?;
+    let mut command = Command::new(executable);
+    command.arg(check.command);
+    if conformance {
+        command.arg("--conformance");
+    }
+    let output = command.arg(&path).output();
+    let _ = fs::remove_file(&path);
+    let output = output.map_err(|error| format!("can't run {}: {}", executable.display(), error))?;
+
+    let code = output.status.code().unwrap_or(-1);
+    if code != check.exit_code {
+        return Err(format!("exit code {}, expected {}", code, check.exit_code));
+    }
+    for (name, actual, expected) in [("stdout", &output.stdout, check.stdout), ("stderr", &output.stderr, check.stderr)] {
+        let actual = String::from_utf8_lossy(actual);
+        if actual != expected {
+            return Err(format!("{} was:\n{}expected:\n{}", name, actual, expected));
+        }
+    }
+    Ok(())
+}
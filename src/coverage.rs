@@ -0,0 +1,89 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::stmt::Stmt;
+
+// Counts how often each line's statements run, through the interpreter's statement
+// hook (`run --coverage=<path>`). Lox has no conditional statements, so line
+// coverage is all there is to record.
+#[derive(Debug, Default)]
+pub struct Coverage {
+    hits: BTreeMap<usize, u64>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Coverage::default()
+    }
+
+    pub fn record_line(&mut self, line: usize) {
+        *self.hits.entry(line).or_insert(0) += 1;
+    }
+
+    pub fn hits(&self, line: usize) -> u64 {
+        self.hits.get(&line).copied().unwrap_or(0)
+    }
+
+    // LCOV tracefile for one source file; `lines` are the executable lines, see
+    // executable_lines
+    pub fn lcov(&self, source_file: &str, lines: &BTreeSet<usize>) -> String {
+        let mut out = String::new();
+        writeln!(out, "TN:").unwrap();
+        writeln!(out, "SF:{}", source_file).unwrap();
+        for &line in lines {
+            writeln!(out, "DA:{},{}", line, self.hits(line)).unwrap();
+        }
+        writeln!(out, "LF:{}", lines.len()).unwrap();
+        writeln!(out, "LH:{}", lines.iter().filter(|&&line| self.hits(line) > 0).count()).unwrap();
+        writeln!(out, "end_of_record").unwrap();
+        out
+    }
+
+    // The source with each line prefixed by its hit count, in the style of gcov:
+    // `-` for lines without statements and `#####` for lines that never ran
+    pub fn annotate(&self, source: &str, lines: &BTreeSet<usize>) -> String {
+        let mut out = String::new();
+        for (index, text) in source.lines().enumerate() {
+            let line = index + 1;
+            let count = match self.hits(line) {
+                _ if !lines.contains(&line) => "-".to_string(),
+                0 => "#####".to_string(),
+                hits => hits.to_string(),
+            };
+            writeln!(out, "{:>9}: {:>4}: {}", count, line, text).unwrap();
+        }
+        out
+    }
+}
+
+// Lines holding at least one statement, function and method bodies included
+pub fn executable_lines(statements: &[Stmt]) -> BTreeSet<usize> {
+    let mut lines = BTreeSet::new();
+    collect_lines(statements, &mut lines);
+    lines
+}
+
+fn collect_lines(statements: &[Stmt], lines: &mut BTreeSet<usize>) {
+    for stmt in statements {
+        match stmt {
+            Stmt::Block(statements) => collect_lines(statements, lines),
+            Stmt::ForIn { body, .. } => {
+                lines.insert(stmt.line());
+                collect_lines(std::slice::from_ref(body), lines);
+            }
+            Stmt::Function(declaration) => {
+                lines.insert(stmt.line());
+                collect_lines(&declaration.body, lines);
+            }
+            Stmt::Class(declaration) => {
+                lines.insert(stmt.line());
+                for method in declaration.methods.iter().chain(&declaration.class_methods) {
+                    collect_lines(&method.body, lines);
+                }
+            }
+            _ => {
+                lines.insert(stmt.line());
+            }
+        }
+    }
+}
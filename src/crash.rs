@@ -0,0 +1,53 @@
+use std::any::Any;
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::fmt::Write;
+use std::panic::Location;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// What the interpreter was doing, so that a panic (a bug in the interpreter, never in
+// the script) can be reported as an internal error rather than a raw Rust panic.
+// Process-wide, like the stats counters, and cheap enough to update per statement.
+static PHASE: Mutex<&str> = Mutex::new("starting");
+static LINE: AtomicUsize = AtomicUsize::new(0);
+
+// Called as each pipeline phase starts: "scan", "parse", "resolve", "execute"
+pub fn enter_phase(phase: &'static str) {
+    *PHASE.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = phase;
+    LINE.store(0, Ordering::Relaxed);
+}
+
+// Source line being processed in the current phase
+pub fn set_line(line: usize) {
+    LINE.store(line, Ordering::Relaxed);
+}
+
+// The report printed instead of the panic message:
+//   internal interpreter error: index out of bounds: the len is 0 but the index is 0
+//     phase: execute, at line 12
+//     panicked at src/interpreter.rs:310:20
+//   This is a bug in the interpreter, not in your program. Please report it, with the program.
+// With RUST_BACKTRACE=1 the Rust backtrace follows. Takes the panic hook's payload and
+// location.
+pub fn report(payload: &(dyn Any + Send), location: Option<&Location>) -> String {
+    let message = match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+        (Some(message), _) => message.to_string(),
+        (_, Some(message)) => message.clone(),
+        _ => "panic".to_string(),
+    };
+    let phase = *PHASE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut out = format!("internal interpreter error: {}\n  phase: {}", message, phase);
+    match LINE.load(Ordering::Relaxed) {
+        0 => out.push('\n'),
+        line => writeln!(out, ", at line {}", line).unwrap(),
+    }
+    if let Some(location) = location {
+        writeln!(out, "  panicked at {}", location).unwrap();
+    }
+    out.push_str("This is a bug in the interpreter, not in your program. Please report it, with the program.\n");
+    let backtrace = Backtrace::capture();
+    if backtrace.status() == BacktraceStatus::Captured {
+        writeln!(out, "{}", backtrace).unwrap();
+    }
+    out
+}
@@ -0,0 +1,328 @@
+use std::env;
+use std::fmt::Write;
+use std::io::{self, IsTerminal};
+
+use crate::codes;
+use crate::document::TextEdit;
+use crate::json;
+use crate::token::{Token, TokenType};
+
+// Pipeline phase that produced a diagnostic
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stage {
+    Scan,
+    Parse,
+    Resolve,
+    Runtime,
+}
+
+impl Stage {
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Scan => "scan",
+            Stage::Parse => "parse",
+            Stage::Resolve => "resolve",
+            Stage::Runtime => "runtime",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note, // Information about other diagnostics, such as how many were left out
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
+}
+
+// Errors each phase reports by default; past it they are only counted (`--max-errors=N`)
+pub const DEFAULT_MAX_ERRORS: usize = 20;
+
+// A single error or warning from any phase
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub stage: Stage,
+    pub severity: Severity,
+    pub message: String,
+    pub line: usize,
+    pub column: Option<usize>,        // 1-based, in characters
+    pub span: Option<(usize, usize)>, // Byte range in the source
+    pub code: Option<&'static str>,   // See codes::CODES
+    // Where a syntax error was found, as the book prints it: `'x'`, or `end` at EOF
+    pub location: Option<String>,
+    pub fix: Option<Fix>,
+}
+
+// A suggested edit that resolves a diagnostic, printed as a `help:` line and offered
+// to editors as a quick fix
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fix {
+    pub message: String, // What the edit does: "insert ';'"
+    pub edit: TextEdit,
+}
+
+impl Diagnostic {
+    pub fn error(stage: Stage, message: impl Into<String>, line: usize) -> Self {
+        let message = message.into();
+        Diagnostic {
+            stage,
+            severity: Severity::Error,
+            code: codes::code_for(stage, &message),
+            message,
+            line,
+            column: None,
+            span: None,
+            location: None,
+            fix: None,
+        }
+    }
+
+    pub fn warning(stage: Stage, message: impl Into<String>, line: usize) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(stage, message, line)
+        }
+    }
+
+    pub fn note(stage: Stage, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Note,
+            ..Diagnostic::error(stage, message, 0)
+        }
+    }
+
+    // Closing note of a phase that stopped collecting errors at `limit`
+    pub fn suppressed(stage: Stage, count: usize, limit: usize) -> Self {
+        let plural = if count == 1 { "" } else { "s" };
+        Diagnostic::note(stage, format!("{} more error{} not shown (limit is {}, see --max-errors).", count, plural, limit))
+    }
+
+    // Locate the diagnostic at a token's lexeme
+    pub fn at(self, token: &Token) -> Self {
+        Diagnostic {
+            column: Some(token.column),
+            span: Some(token.span()),
+            ..self
+        }
+    }
+
+    // Locate the diagnostic at a token and name it in the text, for syntax errors
+    pub fn near(self, token: &Token) -> Self {
+        let location = match token.token_type {
+            TokenType::EOF => "end".to_string(),
+            _ => format!("'{}'", token.lexeme),
+        };
+        Diagnostic {
+            location: Some(location),
+            ..self.at(token)
+        }
+    }
+
+    // Start the span (and column) at an earlier token, for errors about a run of tokens
+    pub fn from(self, token: &Token) -> Self {
+        let end = self.span.map_or(token.span().1, |(_, end)| end);
+        Diagnostic {
+            column: Some(token.column),
+            span: Some((token.start, end)),
+            ..self
+        }
+    }
+
+    pub fn with_fix(self, message: impl Into<String>, edit: TextEdit) -> Self {
+        Diagnostic {
+            fix: Some(Fix { message: message.into(), edit }),
+            ..self
+        }
+    }
+
+    // The classic human-readable form each phase has always printed
+    pub fn to_text(&self) -> String {
+        self.render(false)
+    }
+
+    // to_text with ANSI colors: the severity in red or yellow, the location of a
+    // syntax error underlined
+    pub fn to_colored_text(&self) -> String {
+        self.render(true)
+    }
+
+    fn render(&self, color: bool) -> String {
+        let paint = |style: &str, text: &str| if color { paint(style, text) } else { text.to_string() };
+        match (self.stage, self.severity) {
+            (_, Severity::Note) => paint(DIM, &format!("note: {}", self.message)),
+            (Stage::Runtime, _) => format!("{}\n [line {}]", paint(RED, &self.message), self.line),
+            (_, Severity::Error) if self.location.is_some() => format!(
+                "[line {}] {} at {}: {}",
+                self.line,
+                paint(RED, "Error"),
+                paint(UNDERLINE, self.location.as_deref().unwrap_or_default()),
+                self.message
+            ),
+            (_, Severity::Error) => format!("[line {}] {}: {}", self.line, paint(RED, "Error"), self.message),
+            (_, Severity::Warning) => format!("[line {}] {}: {}", self.line, paint(YELLOW, "Warning"), self.message),
+        }
+    }
+}
+
+// ANSI styles of colored diagnostics
+const RED: &str = "1;31";
+const YELLOW: &str = "1;33";
+const UNDERLINE: &str = "4";
+const DIM: &str = "2";
+const BOLD: &str = "1";
+
+fn paint(style: &str, text: &str) -> String {
+    format!("\x1b[{}m{}\x1b[0m", style, text)
+}
+
+// When text diagnostics are colored (`--color=always|never|auto`)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ColorChoice {
+    #[default]
+    Auto, // When stderr is a terminal and NO_COLOR is not set
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn from_name(name: &str) -> Option<ColorChoice> {
+        match name {
+            "auto" => Some(ColorChoice::Auto),
+            "always" => Some(ColorChoice::Always),
+            "never" => Some(ColorChoice::Never),
+            _ => None,
+        }
+    }
+
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            // See no-color.org: any non-empty value turns colors off
+            ColorChoice::Auto => io::stderr().is_terminal() && !env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DiagnosticFormat {
+    #[default]
+    Text,
+    Json, // One JSON array per emit, for editors and graders
+}
+
+// Renders diagnostics to stderr in the format chosen on the command line
+#[derive(Debug, Clone, Default)]
+pub struct Reporter {
+    pub format: DiagnosticFormat,
+    pub file: String,
+    // Text diagnostics start with the file name (`a.lox: [line 1] Error: ...`), for
+    // commands that report on several files
+    pub show_file: bool,
+    pub color: ColorChoice,
+    // Only errors, as the book prints them: no help lines, warnings or notes
+    // (`--conformance`)
+    pub errors_only: bool,
+}
+
+impl Reporter {
+    pub fn new(format: DiagnosticFormat, file: &str) -> Self {
+        Reporter {
+            format,
+            file: file.to_string(),
+            show_file: false,
+            color: ColorChoice::Auto,
+            errors_only: false,
+        }
+    }
+
+    // Text prints nothing for an empty list; JSON always prints an array
+    pub fn emit(&self, diagnostics: &[Diagnostic]) {
+        match self.format {
+            DiagnosticFormat::Text => {
+                let color = self.color.enabled();
+                let shown = diagnostics.iter().filter(|diagnostic| !self.errors_only || diagnostic.severity == Severity::Error);
+                for diagnostic in shown {
+                    let text = if color { diagnostic.to_colored_text() } else { diagnostic.to_text() };
+                    match (self.show_file, color) {
+                        (true, true) => eprintln!("{}: {}", paint(BOLD, &self.file), text),
+                        (true, false) => eprintln!("{}: {}", self.file, text),
+                        (false, _) => eprintln!("{}", text),
+                    }
+                    if let Some(fix) = diagnostic.fix.as_ref().filter(|_| !self.errors_only) {
+                        let help = format!("  help: {}", fix.message);
+                        eprintln!("{}", if color { paint(DIM, &help) } else { help });
+                    }
+                }
+            }
+            DiagnosticFormat::Json => eprintln!("{}", self.to_json(diagnostics)),
+        }
+    }
+
+    pub fn to_json(&self, diagnostics: &[Diagnostic]) -> String {
+        let mut out = String::from("[");
+        for (i, diagnostic) in diagnostics.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let optional = |value: Option<usize>| value.map_or("null".to_string(), |v| v.to_string());
+            let span = diagnostic
+                .span
+                .map_or("null".to_string(), |(start, end)| format!("{{\"start\":{},\"end\":{}}}", start, end));
+            let fix = diagnostic.fix.as_ref().map_or("null".to_string(), |fix| {
+                format!(
+                    "{{\"message\":{},\"span\":{{\"start\":{},\"end\":{}}},\"replacement\":{}}}",
+                    json::quote(&fix.message),
+                    fix.edit.start,
+                    fix.edit.end,
+                    json::quote(&fix.edit.text)
+                )
+            });
+            write!(
+                out,
+                "{{\"stage\":\"{}\",\"severity\":\"{}\",\"code\":{},\"message\":{},\"file\":{},\"line\":{},\"column\":{},\"span\":{},\"fix\":{}}}",
+                diagnostic.stage.name(),
+                diagnostic.severity.name(),
+                diagnostic.code.map_or("null".to_string(), json::quote),
+                json::quote(&diagnostic.message),
+                json::quote(&self.file),
+                diagnostic.line,
+                optional(diagnostic.column),
+                span,
+                fix
+            )
+            .unwrap();
+        }
+        out.push(']');
+        out
+    }
+}
+
+// An LSP CodeAction (kind "quickfix") applying a diagnostic's fix to the document at
+// `uri`, with positions in the UTF-16 code units the protocol counts in
+pub fn lsp_code_action(source: &str, uri: &str, diagnostic: &Diagnostic) -> Option<String> {
+    let fix = diagnostic.fix.as_ref()?;
+    let position = |offset: usize| {
+        let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line = source[..line_start].matches('\n').count();
+        let character = source[line_start..offset].encode_utf16().count();
+        format!("{{\"line\":{},\"character\":{}}}", line, character)
+    };
+    Some(format!(
+        "{{\"title\":{},\"kind\":\"quickfix\",\"edit\":{{\"changes\":{{{}:[{{\"range\":{{\"start\":{},\"end\":{}}},\"newText\":{}}}]}}}}}}",
+        json::quote(&fix.message),
+        json::quote(uri),
+        position(fix.edit.start),
+        position(fix.edit.end),
+        json::quote(&fix.edit.text)
+    ))
+}
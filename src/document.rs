@@ -0,0 +1,160 @@
+use crate::diagnostic::Diagnostic;
+use crate::parser::Parser;
+use crate::scanner::{ScanOptions, Scanner};
+use crate::stmt::Stmt;
+use crate::token::{Token, TokenType};
+
+// Replace the bytes `start..end` of the current text with `text`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+// A source file kept open in an editor. Edits re-scan only the lines they touch and
+// reuse the tokens before and after them; parsing always starts over from the
+// tokens, which is cheap next to scanning.
+pub struct Document {
+    source: String,
+    tokens: Vec<Token>, // Without the EOF token
+    diagnostics: Vec<Diagnostic>,
+    options: ScanOptions,
+}
+
+impl Document {
+    pub fn new(source: String) -> Self {
+        Document::with_options(source, ScanOptions::default())
+    }
+
+    pub fn with_options(source: String, options: ScanOptions) -> Self {
+        let mut document = Document {
+            source: String::new(),
+            tokens: Vec::new(),
+            diagnostics: Vec::new(),
+            options,
+        };
+        document.edit(&TextEdit { start: 0, end: 0, text: source });
+        document
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    // The tokens of the current text, ending with EOF like Scanner::get_tokens
+    pub fn tokens(&self) -> Vec<Token> {
+        let end = self.source.len();
+        let line = self.source.matches('\n').count() + 1;
+        let column = self.source[line_start(&self.source, end)..].chars().count() + 1;
+        let mut tokens = self.tokens.clone();
+        tokens.push(Token::new(TokenType::EOF, String::new(), None, line).at(end, column));
+        tokens
+    }
+
+    // Lexical errors in the current text, in source order
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    // Parse the current tokens, keeping the statements that parsed despite syntax errors
+    pub fn parse(&self) -> (Vec<Stmt>, Vec<Diagnostic>) {
+        let mut parser = Parser::new(self.tokens());
+        let statements = parser.parse_program();
+        (statements, parser.diagnostics().to_vec())
+    }
+
+    // Apply an edit. The range must lie on character boundaries of the current text.
+    pub fn edit(&mut self, edit: &TextEdit) {
+        // Re-scan whole lines: from the start of the line the edit begins on (or of a
+        // multi-line token or unterminated string spanning it) to the end of the line
+        // the new text ends on. A string left open at the end of the file spans any
+        // text added after it.
+        let old_len = self.source.len();
+        let mut resume = line_start(&self.source, edit.start);
+        while let Some((start, _)) = self.spans().find(|&(start, end)| start < resume && (end > resume || end == old_len)) {
+            resume = line_start(&self.source, start);
+        }
+        let resume_line = self.source[..resume].matches('\n').count() + 1;
+        let mut old_end = line_end(&self.source, edit.end);
+        let old_lines = self.source[resume..old_end].matches('\n').count() as isize;
+
+        self.source.replace_range(edit.start..edit.end, &edit.text);
+        let mut new_end = line_end(&self.source, edit.start + edit.text.len());
+        let (mut tokens, mut diagnostics) = self.scan(resume, new_end, resume_line);
+
+        // A token crossing the end of the region, before or after the edit, means the
+        // rest of the file splits differently now, e.g. because a string was opened or
+        // closed: fall back to scanning to the end
+        let crossed = self.spans().any(|(start, end)| start < old_end && end > old_end);
+        let unterminated = diagnostics.iter().any(|diagnostic| diagnostic.span.is_some_and(|(_, end)| end >= new_end));
+        if (crossed || unterminated) && new_end < self.source.len() {
+            old_end = old_len;
+            new_end = self.source.len();
+            (tokens, diagnostics) = self.scan(resume, new_end, resume_line);
+        }
+
+        // Everything after the region only moves
+        let delta = new_end as isize - old_end as isize;
+        let line_delta = self.source[resume..new_end].matches('\n').count() as isize - old_lines;
+        let shift = |value: usize, by: isize| (value as isize + by) as usize;
+
+        let old_tokens = std::mem::take(&mut self.tokens);
+        self.tokens = old_tokens.iter().take_while(|token| token.span().1 <= resume).cloned().collect();
+        self.tokens.extend(tokens);
+        for mut token in old_tokens.into_iter().filter(|token| token.start >= old_end) {
+            token.start = shift(token.start, delta);
+            token.line = shift(token.line, line_delta);
+            self.tokens.push(token);
+        }
+
+        let old_diagnostics = std::mem::take(&mut self.diagnostics);
+        self.diagnostics = old_diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.span.is_some_and(|(_, end)| end <= resume))
+            .cloned()
+            .collect();
+        self.diagnostics.extend(diagnostics);
+        for mut diagnostic in old_diagnostics.into_iter().filter(|diagnostic| diagnostic.span.is_some_and(|(start, _)| start >= old_end)) {
+            diagnostic.span = diagnostic.span.map(|(start, end)| (shift(start, delta), shift(end, delta)));
+            diagnostic.line = shift(diagnostic.line, line_delta);
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    // Byte ranges of the tokens and lexical errors
+    fn spans(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let tokens = self.tokens.iter().map(|token| token.span());
+        tokens.chain(self.diagnostics.iter().filter_map(|diagnostic| diagnostic.span))
+    }
+
+    // Scan the lines `start..end` of the current text, which begin on line `line`
+    fn scan(&self, start: usize, end: usize, line: usize) -> (Vec<Token>, Vec<Diagnostic>) {
+        let mut scanner = Scanner::new(self.source[start..end].to_string());
+        scanner.set_options(self.options.clone());
+        scanner.scan_tokens();
+
+        let mut tokens = scanner.get_tokens().clone();
+        tokens.pop(); // EOF
+        for token in &mut tokens {
+            token.start += start;
+            token.line += line - 1;
+        }
+        let mut diagnostics = scanner.diagnostics().to_vec();
+        for diagnostic in &mut diagnostics {
+            diagnostic.span = diagnostic.span.map(|(span_start, span_end)| (span_start + start, span_end + start));
+            diagnostic.line += line - 1;
+        }
+        (tokens, diagnostics)
+    }
+}
+
+// Offset of the first character of the line containing `offset`
+fn line_start(source: &str, offset: usize) -> usize {
+    source[..offset].rfind('\n').map_or(0, |i| i + 1)
+}
+
+// Offset just past the newline ending the line containing `offset`
+fn line_end(source: &str, offset: usize) -> usize {
+    source[offset..].find('\n').map_or(source.len(), |i| offset + i + 1)
+}
@@ -0,0 +1,200 @@
+use std::time::Instant;
+
+use crate::cache::ParseCache;
+use crate::crash;
+use crate::diagnostic::{Diagnostic, Reporter, DEFAULT_MAX_ERRORS};
+use crate::expr::Expr;
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::options::LanguageLevel;
+use crate::parser::{Parser, DEFAULT_MAX_DEPTH};
+use crate::report::RunReport;
+use crate::resolver::{Lint, Lints, Resolver, Suppressions};
+use crate::scanner::{ScanOptions, Scanner};
+use crate::stats::Stats;
+use crate::stmt::Stmt;
+use crate::token::Token;
+
+// Exit codes, as in sysexits.h
+pub const EXIT_USAGE: i32 = 64;
+pub const EXIT_SYNTAX: i32 = 65; // Lexical, syntax or resolution errors
+pub const EXIT_NO_INPUT: i32 = 66;
+pub const EXIT_RUNTIME: i32 = 70;
+pub const EXIT_IO: i32 = 74;
+pub const EXIT_CONFIG: i32 = 78; // Invalid lox.toml
+// Not from sysexits.h. A check that found a problem (`fmt --check`, `conformance`,
+// `selftest`, `generate --round-trip`) exits 1, as other `--check` tools do.
+pub const EXIT_CHECK_FAILED: i32 = 1;
+// A bug in the interpreter; stderr says "internal interpreter error". EX_SOFTWARE
+// would be 70, which the book gives runtime errors in the script, so this is the
+// code Rust exits with on a panic instead.
+pub const EXIT_INTERNAL: i32 = 101;
+
+// Why a phase stopped the pipeline: what to report, and the exit code to end with
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub diagnostics: Vec<Diagnostic>,
+    pub code: i32,
+}
+
+impl Failure {
+    fn syntax(diagnostics: &[Diagnostic]) -> Self {
+        Failure { diagnostics: diagnostics.to_vec(), code: EXIT_SYNTAX }
+    }
+}
+
+// Source -> tokens -> AST -> resolved program -> result, with the settings every
+// command shares. Each phase is timed into `report`.
+pub struct Pipeline {
+    pub scan_options: ScanOptions,
+    pub language: LanguageLevel,
+    pub extensions: bool,
+    pub max_depth: usize,
+    pub max_errors: usize, // Per phase, see Parser::set_max_errors
+    pub lints: Lints,
+    // From the lox-ignore comments of the program load() read last, for resolve()
+    suppressions: Suppressions,
+    suppression_warnings: Vec<Diagnostic>,
+    pub cache: Option<ParseCache>, // Used by load()
+    pub reporter: Reporter,        // How the session emits diagnostics
+    pub report: RunReport,
+}
+
+impl Pipeline {
+    pub fn new(reporter: Reporter) -> Self {
+        Pipeline {
+            scan_options: ScanOptions::default(),
+            language: LanguageLevel::default(),
+            extensions: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_errors: DEFAULT_MAX_ERRORS,
+            lints: Lints::default(),
+            suppressions: Suppressions::default(),
+            suppression_warnings: Vec::new(),
+            cache: None,
+            reporter,
+            report: RunReport::default(),
+        }
+    }
+
+    // Every token, even after a lexical error, with the scanner's diagnostics
+    pub fn tokenize(&mut self, source: String) -> (Vec<Token>, Vec<Diagnostic>) {
+        crash::enter_phase("scan");
+        let start = Instant::now();
+        let mut scanner = Scanner::new(source);
+        scanner.set_options(self.scan_options.clone());
+        scanner.set_language(self.language);
+        scanner.scan_tokens();
+        self.report.phase("scan", start.elapsed());
+        (scanner.get_tokens().to_vec(), scanner.diagnostics().to_vec())
+    }
+
+    pub fn scan(&mut self, source: String) -> Result<Vec<Token>, Failure> {
+        let (tokens, diagnostics) = self.tokenize(source);
+        if diagnostics.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(Failure::syntax(&diagnostics))
+        }
+    }
+
+    // A parser with this pipeline's settings
+    pub fn parser(&self, tokens: Vec<Token>) -> Parser {
+        let mut parser = Parser::new(tokens);
+        parser.set_language(self.language);
+        parser.set_extensions(self.extensions);
+        parser.set_max_depth(self.max_depth);
+        parser.set_max_errors(self.max_errors);
+        parser
+    }
+
+    pub fn parse(&mut self, tokens: Vec<Token>) -> Result<Vec<Stmt>, Failure> {
+        crash::enter_phase("parse");
+        let start = Instant::now();
+        let mut parser = self.parser(tokens);
+        let statements = parser.parse_statements();
+        self.report.phase("parse", start.elapsed());
+        statements.map_err(|diagnostics| Failure::syntax(&diagnostics))
+    }
+
+    pub fn parse_expression(&mut self, tokens: Vec<Token>) -> Result<Expr, Failure> {
+        crash::enter_phase("parse");
+        let start = Instant::now();
+        let mut parser = self.parser(tokens);
+        let expression = parser.parse_expression();
+        self.report.phase("parse", start.elapsed());
+        // An invalid assignment target is reported without giving up the expression
+        match expression {
+            Some(expression) if !parser.has_error() => Ok(expression),
+            _ => Err(Failure::syntax(parser.diagnostics())),
+        }
+    }
+
+    // Scan and parse a program, through the parse cache when there is one. With lints
+    // enabled, also read its lox-ignore comments.
+    pub fn load(&mut self, source: &str) -> Result<Vec<Stmt>, Failure> {
+        self.read_suppressions(source);
+        let settings = format!("{:?} {:?} {} {}", self.scan_options, self.language, self.extensions, self.max_depth);
+        let key = ParseCache::key(source, &settings);
+        if let Some(statements) = self.cache.as_ref().and_then(|cache| cache.load(&key)) {
+            return Ok(statements);
+        }
+        let tokens = self.scan(source.to_string())?;
+        let statements = self.parse(tokens)?;
+        if let Some(cache) = &self.cache {
+            cache.store(&key, &statements);
+        }
+        Ok(statements)
+    }
+
+    fn read_suppressions(&mut self, source: &str) {
+        (self.suppressions, self.suppression_warnings) = (Suppressions::default(), Vec::new());
+        if !Lint::ALL.into_iter().any(|lint| self.lints.enabled(lint)) {
+            return;
+        }
+        let mut scanner = Scanner::new(source.to_string());
+        scanner.set_options(ScanOptions { comments: true, ..self.scan_options.clone() });
+        scanner.scan_tokens();
+        (self.suppressions, self.suppression_warnings) = Suppressions::from_comments(scanner.get_tokens());
+    }
+
+    // The lint warnings of a program that resolved without errors
+    pub fn resolve(&mut self, statements: &[Stmt]) -> Result<Vec<Diagnostic>, Failure> {
+        crash::enter_phase("resolve");
+        let start = Instant::now();
+        let mut resolver = Resolver::with_lints(self.lints.clone());
+        resolver.set_language(self.language);
+        resolver.set_max_errors(self.max_errors);
+        resolver.set_suppressions(self.suppressions.clone());
+        resolver.resolve(statements);
+        self.report.phase("resolve", start.elapsed());
+        let mut diagnostics = self.suppression_warnings.clone();
+        diagnostics.extend_from_slice(resolver.diagnostics());
+        if resolver.has_error() {
+            return Err(Failure::syntax(&diagnostics));
+        }
+        Ok(diagnostics)
+    }
+
+    // Run a program, recording the interpreter's counters in the report. The error is
+    // returned as is, so the caller can inspect the interpreter's backtrace first.
+    pub fn execute(&mut self, interpreter: &mut Interpreter, statements: Vec<Stmt>) -> Result<(), RuntimeError> {
+        crash::enter_phase("execute");
+        let start = Instant::now();
+        let result = interpreter.interpret(statements);
+        self.report.phase("execute", start.elapsed());
+        (self.report.output_bytes, self.report.error_output_bytes) = interpreter.output_bytes();
+        self.report.statements = Stats::snapshot().statements;
+        self.report.peak_call_depth = interpreter.peak_call_depth();
+        result
+    }
+
+    // Evaluate a single expression to the text `print` would show for it
+    pub fn evaluate(&mut self, interpreter: &mut Interpreter, expression: &Expr) -> Result<String, Failure> {
+        crash::enter_phase("execute");
+        let start = Instant::now();
+        let line = expression.line();
+        let result = interpreter.evaluate(expression).and_then(|value| interpreter.literal_to_string(value, line));
+        self.report.phase("execute", start.elapsed());
+        result.map_err(|error| Failure { diagnostics: vec![error.to_diagnostic()], code: EXIT_RUNTIME })
+    }
+}
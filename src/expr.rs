@@ -1,12 +1,122 @@
+use crate::array::LoxArray;
+use crate::class::{LoxClass, LoxInstance};
+use crate::function::{HostMethod, LoxFunction, NativeFunction};
+use crate::map::LoxMap;
+use crate::number;
+use crate::stats;
 use crate::token::Token;
 use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(PartialEq, Debug)]
 pub enum LiteralValue {
-    StringLiteral(String),
+    // Shared, so copying a string value doesn't copy its text. See visit_binary for
+    // when `+` appends in place.
+    StringLiteral(Arc<String>),
     NumberLiteral(f64), // f64 can handle both integers and floats
     BooleanLiteral(bool),
     Nil,
+    Function(LoxFunction),
+    NativeFunction(NativeFunction),
+    HostMethod(HostMethod),
+    Array(LoxArray),
+    Map(LoxMap),
+    Class(LoxClass),
+    Instance(LoxInstance),
+}
+
+// Written out so that clones are counted for `run --stats`
+impl Clone for LiteralValue {
+    fn clone(&self) -> Self {
+        stats::value_cloned();
+        match self {
+            LiteralValue::StringLiteral(s) => LiteralValue::StringLiteral(s.clone()),
+            LiteralValue::NumberLiteral(n) => LiteralValue::NumberLiteral(*n),
+            LiteralValue::BooleanLiteral(b) => LiteralValue::BooleanLiteral(*b),
+            LiteralValue::Nil => LiteralValue::Nil,
+            LiteralValue::Function(function) => LiteralValue::Function(function.clone()),
+            LiteralValue::NativeFunction(native) => LiteralValue::NativeFunction(native.clone()),
+            LiteralValue::HostMethod(method) => LiteralValue::HostMethod(method.clone()),
+            LiteralValue::Array(array) => LiteralValue::Array(array.clone()),
+            LiteralValue::Map(map) => LiteralValue::Map(map.clone()),
+            LiteralValue::Class(class) => LiteralValue::Class(class.clone()),
+            LiteralValue::Instance(instance) => LiteralValue::Instance(instance.clone()),
+        }
+    }
+}
+
+impl LiteralValue {
+    pub fn string(text: impl Into<String>) -> Self {
+        LiteralValue::StringLiteral(Arc::new(text.into()))
+    }
+
+    // What type() returns for the value
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            LiteralValue::NumberLiteral(_) => "number",
+            LiteralValue::StringLiteral(_) => "string",
+            LiteralValue::BooleanLiteral(_) => "boolean",
+            LiteralValue::Nil => "nil",
+            LiteralValue::Function(_) | LiteralValue::NativeFunction(_) | LiteralValue::HostMethod(_) => "function",
+            LiteralValue::Array(_) => "array",
+            LiteralValue::Map(_) => "map",
+            LiteralValue::Class(_) => "class",
+            LiteralValue::Instance(_) => "instance",
+        }
+    }
+}
+
+// Where the resolver found the local variable an expression names: in the scope
+// `depth` levels out from the use, at `slot` in that scope's declaration order. A name
+// the resolver found no local for is marked global, and lives in the global scope
+// whatever a closure's scopes declare later. Code that was never resolved (a debugger
+// expression, say) is left unset and looks the name up instead.
+// The resolver only borrows the tree, so the slot is set through a shared reference.
+pub struct LocalSlot(AtomicU64);
+
+impl LocalSlot {
+    const UNRESOLVED: u64 = u64::MAX;
+    const GLOBAL: u64 = u64::MAX - 1;
+
+    pub fn get(&self) -> Option<(usize, usize)> {
+        let packed = self.0.load(Ordering::Relaxed);
+        (packed != Self::UNRESOLVED && packed != Self::GLOBAL).then_some(((packed >> 32) as usize, (packed & 0xffff_ffff) as usize))
+    }
+
+    pub fn is_global(&self) -> bool {
+        self.0.load(Ordering::Relaxed) == Self::GLOBAL
+    }
+
+    pub fn set_global(&self) {
+        self.0.store(Self::GLOBAL, Ordering::Relaxed);
+    }
+
+    pub fn set(&self, depth: usize, slot: usize) {
+        self.0.store(((depth as u64) << 32) | slot as u64, Ordering::Relaxed);
+    }
+}
+
+impl Default for LocalSlot {
+    fn default() -> Self {
+        LocalSlot(AtomicU64::new(Self::UNRESOLVED))
+    }
+}
+
+impl Clone for LocalSlot {
+    fn clone(&self) -> Self {
+        LocalSlot(AtomicU64::new(self.0.load(Ordering::Relaxed)))
+    }
+}
+
+impl fmt::Debug for LocalSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.get() {
+            Some((depth, slot)) => write!(f, "LocalSlot({}, {})", depth, slot),
+            None if self.is_global() => write!(f, "LocalSlot(global)"),
+            None => write!(f, "LocalSlot(unresolved)"),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -14,55 +124,277 @@ pub enum Expr {
     Assign {
         name: Token,
         value: Box<Expr>,
+        slot: LocalSlot,
     },
     Binary {
         left: Box<Expr>,
         operator: Token,
         right: Box<Expr>,
     },
+    Call {
+        callee: Box<Expr>,
+        paren: Token, // Closing paren, used to report errors at the call site
+        arguments: Vec<Expr>,
+    },
+    Array {
+        bracket: Token, // Opening bracket
+        elements: Vec<Expr>,
+    },
+    Index {
+        object: Box<Expr>,
+        bracket: Token, // Closing bracket, used to report errors
+        index: Box<Expr>,
+    },
+    // `object[start:end]`, either bound optional
+    Slice {
+        object: Box<Expr>,
+        bracket: Token, // Closing bracket, used to report errors
+        start: Option<Box<Expr>>,
+        end: Option<Box<Expr>>,
+    },
+    SetIndex {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+        value: Box<Expr>,
+    },
+    Get {
+        object: Box<Expr>,
+        name: Token,
+    },
+    Set {
+        object: Box<Expr>,
+        name: Token,
+        value: Box<Expr>,
+    },
+    This(Token, LocalSlot),
     Grouping(Box<Expr>),
-    Literal(LiteralValue),
+    Literal {
+        value: LiteralValue,
+        token: Token, // As written, for the source-faithful printer
+    },
     Unary {
         operator: Token,
         right: Box<Expr>,
     },
-    Variable(Token),
+    Variable(Token, LocalSlot),
+    // `match subject { pattern => body, ... }`: the body of the first arm whose
+    // pattern matches
+    Match {
+        keyword: Token,
+        subject: Box<Expr>,
+        arms: Vec<MatchArm>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Expr,
 }
 
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Wildcard(Token), // `_`, matches anything
+    Literal(Expr),   // A number, string, `true`, `false` or `nil` literal, matched by equality
+    Binding(Token),  // A name, matches anything and binds it in the arm's body
+    // An array of exactly as many elements, each matching its pattern
+    Array { bracket: Token, elements: Vec<Pattern> },
+    // A map with at least these string keys, each value matching its pattern
+    Map { brace: Token, entries: Vec<(Token, Pattern)> },
+}
+
+impl Pattern {
+    // Whether `value` matches. On a match, `bindings` gets what each binding pattern
+    // matched, in source order.
+    pub fn matches<'a>(&'a self, value: &LiteralValue, bindings: &mut Vec<(&'a Token, LiteralValue)>) -> bool {
+        match (self, value) {
+            (Pattern::Wildcard(_), _) => true,
+            (Pattern::Literal(Expr::Literal { value: literal, .. }), value) => literal == value,
+            (Pattern::Literal(_), _) => false,
+            (Pattern::Binding(name), value) => {
+                bindings.push((name, value.clone()));
+                true
+            }
+            (Pattern::Array { elements, .. }, LiteralValue::Array(array)) => {
+                let values = array.to_vec();
+                values.len() == elements.len() && elements.iter().zip(&values).all(|(element, value)| element.matches(value, bindings))
+            }
+            (Pattern::Map { entries, .. }, LiteralValue::Map(map)) => entries.iter().all(|(key, pattern)| {
+                let key = key.literal.as_deref().unwrap_or_default();
+                map.get(key).is_some_and(|value| pattern.matches(&value, bindings))
+            }),
+            (Pattern::Array { .. } | Pattern::Map { .. }, _) => false,
+        }
+    }
+
+    // The names the pattern binds, in source order
+    pub fn bindings(&self) -> Vec<&Token> {
+        match self {
+            Pattern::Wildcard(_) | Pattern::Literal(_) => Vec::new(),
+            Pattern::Binding(name) => vec![name],
+            Pattern::Array { elements, .. } => elements.iter().flat_map(Pattern::bindings).collect(),
+            Pattern::Map { entries, .. } => entries.iter().flat_map(|(_, pattern)| pattern.bindings()).collect(),
+        }
+    }
+
+}
+
+impl Expr {
+    // Source line of the expression's first token
+    pub fn line(&self) -> usize {
+        match self {
+            Expr::Assign { name, .. } => name.line,
+            Expr::Binary { left, .. } => left.line(),
+            Expr::Call { callee, .. } => callee.line(),
+            Expr::Array { bracket, .. } => bracket.line,
+            Expr::Index { object, .. } | Expr::Slice { object, .. } => object.line(),
+            Expr::SetIndex { object, .. } => object.line(),
+            Expr::Get { object, .. } | Expr::Set { object, .. } => object.line(),
+            Expr::This(keyword, _) => keyword.line,
+            Expr::Grouping(expr) => expr.line(),
+            Expr::Literal { token, .. } => token.line,
+            Expr::Unary { operator, .. } => operator.line,
+            Expr::Variable(name, _) => name.line,
+            Expr::Match { keyword, .. } => keyword.line,
+        }
+    }
+
+    // Byte range from the expression's first token to its last. Parentheses around a
+    // grouping and an array's closing bracket are not in the tree, so not in the span.
+    pub fn span(&self) -> (usize, usize) {
+        let to = |first: (usize, usize), last: (usize, usize)| (first.0, last.1.max(first.1));
+        match self {
+            Expr::Assign { name, value, .. } => to(name.span(), value.span()),
+            Expr::Binary { left, right, .. } => to(left.span(), right.span()),
+            Expr::Call { callee, paren, .. } => to(callee.span(), paren.span()),
+            Expr::Array { bracket, elements } => to(bracket.span(), elements.last().map_or(bracket.span(), Expr::span)),
+            Expr::Index { object, bracket, .. } | Expr::Slice { object, bracket, .. } => {
+                to(object.span(), bracket.span())
+            }
+            Expr::SetIndex { object, value, .. } | Expr::Set { object, value, .. } => to(object.span(), value.span()),
+            Expr::Get { object, name } => to(object.span(), name.span()),
+            Expr::Grouping(expr) => expr.span(),
+            Expr::Unary { operator, right } => to(operator.span(), right.span()),
+            Expr::This(token, _) | Expr::Literal { token, .. } | Expr::Variable(token, _) => token.span(),
+            Expr::Match { keyword, subject, arms } => to(keyword.span(), arms.last().map_or(subject.span(), |arm| arm.body.span())),
+        }
+    }
+}
+
+// A child node printed in the same mode as its parent, so `{:#}` reaches the leaves
+pub(crate) struct Node<'a, T>(pub &'a T, pub bool);
+
+impl<T: fmt::Display> fmt::Display for Node<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.1 {
+            write!(f, "{:#}", self.0)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+// The AST printer. `{}` is canonical, as in the book: numbers as floats and strings
+// unquoted. `{:#}` prints literals as they were written in the source.
 impl fmt::Display for Expr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let source = f.alternate();
         match self {
             Expr::Binary { left, operator, right } => {
-                write!(f, "({} {} {})", operator.lexeme, left, right)
+                write!(f, "({} {} {})", operator.lexeme, Node(left, source), Node(right, source))
+            }
+            Expr::Call { callee, arguments, .. } => {
+                write!(f, "(call {}", Node(callee, source))?;
+                for argument in arguments {
+                    write!(f, " {}", Node(argument, source))?;
+                }
+                write!(f, ")")
+            }
+            Expr::Array { elements, .. } => {
+                write!(f, "(array")?;
+                for element in elements {
+                    write!(f, " {}", Node(element, source))?;
+                }
+                write!(f, ")")
             }
+            Expr::Index { object, index, .. } => {
+                write!(f, "(index {} {})", Node(object, source), Node(index, source))
+            }
+            Expr::Slice { object, start, end, .. } => {
+                let bound = |bound: &Option<Box<Expr>>| bound.as_ref().map_or("nil".to_string(), |b| Node(b, source).to_string());
+                write!(f, "(slice {} {} {})", Node(object, source), bound(start), bound(end))
+            }
+            Expr::SetIndex { object, index, value, .. } => {
+                write!(f, "(assign (index {} {}) = {})", Node(object, source), Node(index, source), Node(value, source))
+            }
+            Expr::Get { object, name } => {
+                write!(f, "(get {} {})", Node(object, source), name.lexeme)
+            }
+            Expr::Set { object, name, value } => {
+                write!(f, "(assign (get {} {}) = {})", Node(object, source), name.lexeme, Node(value, source))
+            }
+            Expr::This(..) => write!(f, "this"),
             Expr::Grouping(expr) => {
-                write!(f, "(group {})", expr)
+                write!(f, "(group {})", Node(expr, source))
             }
-            Expr::Literal(literal) => match literal {
+            Expr::Literal { token, .. } if source => write!(f, "{}", token.lexeme),
+            Expr::Literal { value, .. } => match value {
                 LiteralValue::StringLiteral(s) => write!(f, "{}", s),
-                LiteralValue::NumberLiteral(n) => {
-                    // Print number literals as floats
-                    if n.fract() == 0.0 {
-                        // Whole number, format with `.0`
-                        write!(f, "{:.1}", n)
-                    } else {
-                        // Floating-point number, print with full precision
-                        write!(f, "{}", n)
-                    }
-                }
+                LiteralValue::NumberLiteral(n) => write!(f, "{}", number::canonical(*n)),
                 LiteralValue::BooleanLiteral(b) => write!(f, "{}", b),
                 LiteralValue::Nil => write!(f, "nil"),
+                LiteralValue::Function(function) => write!(f, "{:?}", function),
+                LiteralValue::NativeFunction(native) => write!(f, "{:?}", native),
+                LiteralValue::HostMethod(method) => write!(f, "{:?}", method),
+                LiteralValue::Array(array) => write!(f, "{:?}", array),
+                LiteralValue::Map(map) => write!(f, "{:?}", map),
+                LiteralValue::Class(class) => write!(f, "{:?}", class),
+                LiteralValue::Instance(instance) => write!(f, "{:?}", instance),
             },
             Expr::Unary { operator, right } => {
-                write!(f, "({} {})", operator.lexeme, right)
+                write!(f, "({} {})", operator.lexeme, Node(right, source))
             }
             // Handle variable expressions like `print baz;`
-            Expr::Variable(token) => {
+            Expr::Variable(token, _) => {
                 write!(f, "{}", token.lexeme)
             }
             // Handle assignment expressions
-            Expr::Assign { name, value } => {
-                write!(f, "(assign {} = {})", name.lexeme, value)
+            Expr::Assign { name, value, .. } => {
+                write!(f, "(assign {} = {})", name.lexeme, Node(value, source))
+            }
+            Expr::Match { subject, arms, .. } => {
+                write!(f, "(match {}", Node(subject.as_ref(), source))?;
+                for arm in arms {
+                    write!(f, " ({} {})", Node(&arm.pattern, source), Node(&arm.body, source))?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+// Patterns print like the expressions they resemble: `(array ...)`, and `(map ...)`
+// of key and pattern pairs
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let source = f.alternate();
+        match self {
+            Pattern::Wildcard(token) | Pattern::Binding(token) => write!(f, "{}", token.lexeme),
+            Pattern::Literal(literal) => write!(f, "{}", Node(literal, source)),
+            Pattern::Array { elements, .. } => {
+                write!(f, "(array")?;
+                for element in elements {
+                    write!(f, " {}", Node(element, source))?;
+                }
+                write!(f, ")")
+            }
+            Pattern::Map { entries, .. } => {
+                write!(f, "(map")?;
+                for (key, pattern) in entries {
+                    write!(f, " ({} {})", key.lexeme, Node(pattern, source))?;
+                }
+                write!(f, ")")
             }
         }
     }
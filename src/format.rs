@@ -0,0 +1,158 @@
+use crate::expr::LiteralValue;
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::number;
+
+// Why formatting failed: a bad template, or an error while converting an argument
+// (e.g. in a toString() method)
+#[derive(Debug)]
+pub enum FormatError {
+    Template(String),
+    Runtime(RuntimeError),
+}
+
+impl From<String> for FormatError {
+    fn from(message: String) -> Self {
+        FormatError::Template(message)
+    }
+}
+
+// Placeholder syntax, a small subset of Rust's: {[index][:[[fill]align][0][width][.precision]]}
+// e.g. "{}", "{1}", "{:>8}", "{:*^10}", "{:08.3}". "{{" and "}}" are literal braces.
+pub fn format(interpreter: &mut Interpreter, template: &str, arguments: &[LiteralValue], line: usize) -> Result<String, FormatError> {
+    let mut out = String::new();
+    let mut next_argument = 0;
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '}' => return Err("found an unmatched '}' in the format string".to_string().into()),
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => return Err("found an unterminated '{' in the format string".to_string().into()),
+                    }
+                }
+
+                let (index, spec) = match placeholder.split_once(':') {
+                    Some((index, spec)) => (index, Some(spec)),
+                    None => (placeholder.as_str(), None),
+                };
+                let index = if index.is_empty() {
+                    next_argument += 1;
+                    next_argument - 1
+                } else {
+                    index.parse::<usize>().map_err(|_| format!("has an invalid placeholder '{{{}}}'", placeholder))?
+                };
+                let argument = arguments
+                    .get(index)
+                    .ok_or_else(|| format!("has no argument for placeholder {} ({} given)", index, arguments.len()))?;
+
+                let spec = match spec {
+                    Some(spec) => Spec::parse(spec).ok_or_else(|| format!("has an invalid format spec '{}'", spec))?,
+                    None => Spec::default(),
+                };
+                let text = spec.apply(interpreter, argument, line).map_err(FormatError::Runtime)?;
+                out.push_str(&text);
+            }
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+#[derive(Default)]
+struct Spec {
+    fill: Option<char>,
+    align: Option<char>, // '<', '>' or '^'
+    zero_pad: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl Spec {
+    fn parse(spec: &str) -> Option<Spec> {
+        let mut result = Spec::default();
+        let chars: Vec<char> = spec.chars().collect();
+        let mut i = 0;
+
+        let is_align = |c: char| matches!(c, '<' | '>' | '^');
+        if chars.len() >= 2 && is_align(chars[1]) {
+            result.fill = Some(chars[0]);
+            result.align = Some(chars[1]);
+            i = 2;
+        } else if !chars.is_empty() && is_align(chars[0]) {
+            result.align = Some(chars[0]);
+            i = 1;
+        }
+
+        if chars.get(i) == Some(&'0') {
+            result.zero_pad = true;
+            i += 1;
+        }
+
+        let digits = |from: usize| chars[from..].iter().take_while(|c| c.is_ascii_digit()).count();
+        let width_len = digits(i);
+        if width_len > 0 {
+            result.width = chars[i..i + width_len].iter().collect::<String>().parse().ok()?;
+            i += width_len;
+        }
+
+        if chars.get(i) == Some(&'.') {
+            let precision_len = digits(i + 1);
+            if precision_len == 0 {
+                return None;
+            }
+            result.precision = chars[i + 1..i + 1 + precision_len].iter().collect::<String>().parse().ok();
+            i += 1 + precision_len;
+        }
+
+        if i == chars.len() {
+            Some(result)
+        } else {
+            None
+        }
+    }
+
+    fn apply(&self, interpreter: &mut Interpreter, value: &LiteralValue, line: usize) -> Result<String, RuntimeError> {
+        // Precision means decimal places for numbers and maximum length for other values
+        let text = match (value, self.precision) {
+            (LiteralValue::NumberLiteral(n), Some(precision)) => number::fixed(*n, precision),
+            (_, Some(precision)) => interpreter.literal_to_string(value.clone(), line)?.chars().take(precision).collect(),
+            (_, None) => interpreter.literal_to_string(value.clone(), line)?,
+        };
+
+        let len = text.chars().count();
+        if len >= self.width {
+            return Ok(text);
+        }
+        let padding = self.width - len;
+
+        // Zero padding goes between the sign and the digits
+        if self.zero_pad && self.align.is_none() && matches!(value, LiteralValue::NumberLiteral(_)) {
+            return Ok(match text.strip_prefix('-') {
+                Some(digits) => format!("-{}{}", "0".repeat(padding), digits),
+                None => format!("{}{}", "0".repeat(padding), text),
+            });
+        }
+
+        let fill = self.fill.unwrap_or(' ').to_string();
+        // Numbers align right and everything else left by default, as in Rust
+        let align = self.align.unwrap_or(if matches!(value, LiteralValue::NumberLiteral(_)) { '>' } else { '<' });
+        Ok(match align {
+            '>' => format!("{}{}", fill.repeat(padding), text),
+            '^' => format!("{}{}{}", fill.repeat(padding / 2), text, fill.repeat(padding - padding / 2)),
+            _ => format!("{}{}", text, fill.repeat(padding)),
+        })
+    }
+}
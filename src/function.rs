@@ -0,0 +1,265 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::class::LoxInstance;
+use crate::expr::LiteralValue;
+use crate::interpreter::{Environment, Interpreter, RuntimeError, Signal};
+use crate::options::Capability;
+use crate::stmt::FunctionDecl;
+
+// Signature shared by every native function. The context gives natives access to the
+// running interpreter (globals, calling back into Lox functions) and the call-site line.
+pub type NativeFn = fn(&mut NativeContext, &[LiteralValue]) -> Result<LiteralValue, RuntimeError>;
+
+// Signature of the methods of host classes (see class::HostClass): natives that also
+// receive the instance they were called on
+pub type HostMethodFn = Arc<dyn Fn(&mut NativeContext, &LoxInstance, &[LiteralValue]) -> Result<LiteralValue, RuntimeError> + Send + Sync>;
+
+// Calling convention shared by every callable value. The interpreter checks the arity
+// and pushes a Frame before `call`, so implementations can assume the argument count
+// is valid; a `Signal::Return` escaping `call` is treated as the call's result.
+pub trait Callable {
+    fn name(&self) -> &str;
+    fn arity(&self) -> Arity;
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<LiteralValue>) -> Result<LiteralValue, Signal>;
+}
+
+// One active call, innermost last on the interpreter's call stack
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub name: String,
+    pub line: usize, // Line of the call site, in the caller
+}
+
+// Backtrace in the book's format, innermost call first:
+//   [line 3] in inner()
+//   [line 7] in outer()
+//   [line 9] in script
+pub fn format_backtrace(frames: &[Frame], error_line: usize) -> String {
+    let mut out = String::new();
+    let mut line = error_line;
+    for frame in frames.iter().rev() {
+        out.push_str(&format!("[line {}] in {}()\n", line, frame.name));
+        line = frame.line;
+    }
+    out.push_str(&format!("[line {}] in script\n", line));
+    out
+}
+
+// Number of arguments a callable accepts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arity {
+    Fixed(usize),
+    AtLeast(usize), // Variadic: the extra arguments are passed through as-is
+}
+
+impl Arity {
+    pub fn accepts(self, count: usize) -> bool {
+        match self {
+            Arity::Fixed(n) => count == n,
+            Arity::AtLeast(n) => count >= n,
+        }
+    }
+}
+
+// A function implemented in Rust and exposed to Lox scripts as a global
+#[derive(Clone)]
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: Arity,
+    pub function: NativeFn,
+}
+
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl Callable for NativeFunction {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn arity(&self) -> Arity {
+        self.arity
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<LiteralValue>) -> Result<LiteralValue, Signal> {
+        log::debug!("calling native {}", self.name);
+        let line = interpreter.call_line();
+        Ok((self.function)(&mut NativeContext::new(interpreter, line), &arguments)?)
+    }
+}
+
+// A method of a host class bound to the instance it was read from
+#[derive(Clone)]
+pub struct HostMethod {
+    pub name: String,
+    pub arity: Arity,
+    pub getter: bool, // Runs on property access instead of producing the method
+    pub receiver: LoxInstance,
+    pub function: HostMethodFn,
+}
+
+impl PartialEq for HostMethod {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.function, &other.function) && self.receiver == other.receiver
+    }
+}
+
+impl fmt::Debug for HostMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl Callable for HostMethod {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn arity(&self) -> Arity {
+        self.arity
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<LiteralValue>) -> Result<LiteralValue, Signal> {
+        log::debug!("calling host method {}", self.name);
+        let line = interpreter.call_line();
+        Ok((self.function)(&mut NativeContext::new(interpreter, line), &self.receiver, &arguments)?)
+    }
+}
+
+// A user-defined function together with the scope it was declared in
+#[derive(Clone)]
+pub struct LoxFunction {
+    pub declaration: Arc<FunctionDecl>,
+    pub closure: Arc<Mutex<Environment>>,
+    pub is_initializer: bool, // A class's `init` method, which always returns `this`
+}
+
+impl LoxFunction {
+    pub fn new(declaration: Arc<FunctionDecl>, closure: Arc<Mutex<Environment>>) -> Self {
+        LoxFunction {
+            declaration,
+            closure,
+            is_initializer: false,
+        }
+    }
+
+    // A method bound to a receiver (an instance, or the class for class methods): a
+    // copy of the function whose closure defines `this`
+    pub fn bind(&self, receiver: LiteralValue) -> LoxFunction {
+        let mut environment = Environment::from_enclosing(Arc::clone(&self.closure));
+        environment.define("this".to_string(), receiver);
+        LoxFunction {
+            closure: Arc::new(Mutex::new(environment)),
+            ..self.clone()
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.declaration.name.lexeme
+    }
+
+    pub fn arity(&self) -> Arity {
+        Arity::Fixed(self.declaration.params.len())
+    }
+}
+
+impl Callable for LoxFunction {
+    fn name(&self) -> &str {
+        LoxFunction::name(self)
+    }
+
+    fn arity(&self) -> Arity {
+        LoxFunction::arity(self)
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<LiteralValue>) -> Result<LiteralValue, Signal> {
+        log::debug!("calling function {}", self.name());
+        let mut environment = Environment::from_enclosing(Arc::clone(&self.closure));
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment.define(param.lexeme.clone(), argument);
+        }
+        match interpreter.execute_block(&self.declaration.body, environment) {
+            Ok(()) | Err(Signal::Return(_)) if self.is_initializer => {
+                Ok(self.closure.lock().unwrap().get("this", interpreter.call_line())?)
+            }
+            Ok(()) => Ok(LiteralValue::Nil),
+            Err(signal) => Err(signal),
+        }
+    }
+}
+
+// Functions are equal only if they are the same declaration closed over the same scope
+impl PartialEq for LoxFunction {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.declaration, &other.declaration) && Arc::ptr_eq(&self.closure, &other.closure)
+    }
+}
+
+// The closure may contain the function itself, so never print it
+impl fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.name())
+    }
+}
+
+// Handle passed to native functions for the duration of a single call
+pub struct NativeContext<'a> {
+    interpreter: &'a mut Interpreter,
+    line: usize,
+}
+
+impl<'a> NativeContext<'a> {
+    pub fn new(interpreter: &'a mut Interpreter, line: usize) -> Self {
+        NativeContext { interpreter, line }
+    }
+
+    pub fn interpreter(&mut self) -> &mut Interpreter {
+        self.interpreter
+    }
+
+    // Line of the call expression that invoked the native
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    // Call a Lox (or native) function value, e.g. a comparator passed by the script
+    pub fn call(&mut self, callee: &LiteralValue, arguments: Vec<LiteralValue>) -> Result<LiteralValue, RuntimeError> {
+        self.interpreter.call_value(callee, arguments, self.line)
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<LiteralValue> {
+        self.interpreter.get_global(name)
+    }
+
+    // Write to the interpreter's output sink
+    pub fn write(&mut self, text: &str) -> Result<(), RuntimeError> {
+        self.interpreter.write_output(text, self.line)
+    }
+
+    // Fail unless the embedder granted the capability this native needs
+    pub fn require(&self, capability: Capability, native: &str) -> Result<(), RuntimeError> {
+        if self.interpreter.options().capabilities.allows(capability) {
+            Ok(())
+        } else {
+            Err(self.error(format!("{}() requires the '{}' capability.", native, capability.name())))
+        }
+    }
+
+    // Build a runtime error reported at the call site
+    pub fn error(&self, message: impl Into<String>) -> RuntimeError {
+        RuntimeError {
+            message: message.into(),
+            line: self.line,
+        }
+    }
+}
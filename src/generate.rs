@@ -0,0 +1,270 @@
+use std::fmt::Write;
+
+use crate::rng::Rng;
+
+// Random Lox programs for stress-testing the parser and interpreter
+// (`generate --seed=N --depth=D`). The same seed and options always give the same
+// program. Expressions are generated by type, so a valid program also runs without
+// runtime errors: it only prints numbers, strings and booleans.
+#[derive(Debug, Clone)]
+pub struct GenerateOptions {
+    pub depth: usize,      // Deepest nesting of expressions and of blocks
+    pub statements: usize, // Top-level statements
+    // Break the program with one small edit (a dropped or stray token), for testing
+    // syntax error reporting
+    pub invalid: bool,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        GenerateOptions { depth: 3, statements: 12, invalid: false }
+    }
+}
+
+pub fn generate(seed: u64, options: &GenerateOptions) -> String {
+    let mut generator = Generator {
+        rng: Rng::new(seed),
+        depth: options.depth,
+        out: String::new(),
+        scopes: vec![Vec::new()],
+        functions: Vec::new(),
+        instances: Vec::new(),
+        in_function: false,
+        next_name: 0,
+        indent: 0,
+    };
+    for _ in 0..options.statements {
+        generator.statement(options.depth, false);
+    }
+    let mut program = generator.out;
+    if options.invalid {
+        corrupt(&mut generator.rng, &mut program);
+    }
+    program
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Type {
+    Number,
+    String,
+    Boolean,
+}
+
+const TYPES: [Type; 3] = [Type::Number, Type::String, Type::Boolean];
+
+struct Generator {
+    rng: Rng,
+    depth: usize,
+    out: String,
+    scopes: Vec<Vec<(String, Type)>>, // Variables in scope, innermost scope last
+    functions: Vec<(String, usize)>,  // Global functions: name and arity; all take and return numbers
+    instances: Vec<String>,           // Global instances of a generated class
+    in_function: bool,
+    next_name: usize,
+    indent: usize,
+}
+
+impl Generator {
+    fn chance(&mut self, percent: u64) -> bool {
+        self.rng.next_u64() % 100 < percent
+    }
+
+    fn name(&mut self, prefix: &str) -> String {
+        self.next_name += 1;
+        format!("{}{}", prefix, self.next_name)
+    }
+
+    fn line(&mut self, text: &str) {
+        writeln!(self.out, "{}{}", "  ".repeat(self.indent), text).unwrap();
+    }
+
+    fn variables(&self, of: Type) -> Vec<String> {
+        self.scopes.iter().flatten().filter(|(_, ty)| *ty == of).map(|(name, _)| name.clone()).collect()
+    }
+
+    fn declare(&mut self, name: String, ty: Type) {
+        self.scopes.last_mut().unwrap().push((name, ty));
+    }
+
+    fn statement(&mut self, depth: usize, in_function: bool) {
+        let top_level = self.scopes.len() == 1;
+        match self.rng.next_in_range(0, 9) {
+            0..=2 => {
+                let ty = pick(&mut self.rng, &TYPES).unwrap();
+                let initializer = self.expression(ty, self.depth);
+                let name = self.name("v");
+                self.line(&format!("var {} = {};", name, initializer));
+                self.declare(name, ty);
+            }
+            3..=4 => {
+                let ty = pick(&mut self.rng, &TYPES).unwrap();
+                let value = self.expression(ty, self.depth);
+                self.line(&format!("print {};", value));
+            }
+            5 => {
+                let ty = pick(&mut self.rng, &TYPES).unwrap();
+                let value = self.expression(ty, self.depth);
+                let variables = self.variables(ty);
+                match pick(&mut self.rng, &variables) {
+                    Some(name) => self.line(&format!("{} = {};", name, value)),
+                    None => self.line(&format!("print {};", value)),
+                }
+            }
+            6 if depth > 0 => {
+                self.line("{");
+                self.indent += 1;
+                self.scopes.push(Vec::new());
+                for _ in 0..self.rng.next_in_range(1, 3) {
+                    self.statement(depth - 1, in_function);
+                }
+                self.scopes.pop();
+                self.indent -= 1;
+                self.line("}");
+            }
+            7 if top_level && depth > 0 => self.function(depth - 1),
+            8 if top_level => self.class(),
+            9 if !self.instances.is_empty() => {
+                let instance = pick(&mut self.rng, &self.instances).unwrap();
+                let value = self.expression(Type::Number, self.depth);
+                self.line(&format!("{}.value = {};", instance, value));
+            }
+            _ => {
+                let value = self.expression(Type::Number, self.depth);
+                self.line(&format!("print {};", value));
+            }
+        }
+        if in_function && self.chance(5) {
+            let value = self.expression(Type::Number, self.depth);
+            self.line(&format!("return {};", value));
+        }
+    }
+
+    // `fun fN(p1, p2) { ...; return <number>; }`
+    fn function(&mut self, depth: usize) {
+        let name = self.name("f");
+        let params: Vec<String> = (0..self.rng.next_in_range(0, 3)).map(|_| self.name("p")).collect();
+        self.line(&format!("fun {}({}) {{", name, params.join(", ")));
+        self.indent += 1;
+        self.scopes.push(params.iter().map(|param| (param.clone(), Type::Number)).collect());
+        self.in_function = true;
+        for _ in 0..self.rng.next_in_range(0, 3) {
+            self.statement(depth, true);
+        }
+        let value = self.expression(Type::Number, self.depth);
+        self.line(&format!("return {};", value));
+        self.in_function = false;
+        self.scopes.pop();
+        self.indent -= 1;
+        self.line("}");
+        self.functions.push((name, params.len()));
+    }
+
+    // A class with a number field and a method reading it, plus an instance of it
+    fn class(&mut self) {
+        let name = self.name("C");
+        let value = self.expression(Type::Number, 1);
+        self.line(&format!("class {} {{", name));
+        self.line(&format!("  var value = {};", value));
+        self.line("  scaled(k) { return this.value * k; }");
+        self.line("}");
+        let instance = self.name("o");
+        self.line(&format!("var {} = {}();", instance, name));
+        self.instances.push(instance);
+    }
+
+    fn expression(&mut self, ty: Type, depth: usize) -> String {
+        if depth == 0 || self.chance(30) {
+            return self.leaf(ty);
+        }
+        // Operations are parenthesized, so they nest whatever the operators' precedence
+        let depth = depth - 1;
+        match ty {
+            Type::Number => match self.rng.next_in_range(0, 5) {
+                0 => format!("-{}", self.expression(Type::Number, depth)),
+                1 => format!("({})", self.expression(Type::Number, depth)),
+                // Function bodies call no functions, which keeps the run time linear
+                2 if !self.functions.is_empty() && !self.in_function => {
+                    let (name, arity) = pick(&mut self.rng, &self.functions).unwrap();
+                    let arguments: Vec<String> = (0..arity).map(|_| self.expression(Type::Number, depth)).collect();
+                    format!("{}({})", name, arguments.join(", "))
+                }
+                3 if !self.instances.is_empty() => {
+                    let instance = pick(&mut self.rng, &self.instances).unwrap();
+                    if self.chance(50) {
+                        format!("{}.value", instance)
+                    } else {
+                        format!("{}.scaled({})", instance, self.expression(Type::Number, depth))
+                    }
+                }
+                4 => {
+                    let elements: Vec<String> = (0..3).map(|_| self.expression(Type::Number, depth)).collect();
+                    format!("[{}][{}]", elements.join(", "), self.rng.next_in_range(0, 2))
+                }
+                // Dividing by zero is a runtime error, so divisors are non-zero literals
+                5 if self.chance(20) => format!("({} / {})", self.expression(Type::Number, depth), self.rng.next_in_range(1, 9)),
+                _ => {
+                    let operator = pick(&mut self.rng, &["+", "-", "*"]).unwrap();
+                    format!("({} {} {})", self.expression(Type::Number, depth), operator, self.expression(Type::Number, depth))
+                }
+            },
+            Type::String => match self.rng.next_in_range(0, 1) {
+                0 => format!("({})", self.expression(Type::String, depth)),
+                _ => format!("({} + {})", self.expression(Type::String, depth), self.expression(Type::String, depth)),
+            },
+            Type::Boolean => match self.rng.next_in_range(0, 3) {
+                0 => format!("!{}", self.expression(Type::Boolean, depth)),
+                1 => {
+                    let operator = pick(&mut self.rng, &["<", "<=", ">", ">="]).unwrap();
+                    format!("({} {} {})", self.expression(Type::Number, depth), operator, self.expression(Type::Number, depth))
+                }
+                _ => {
+                    let operand = pick(&mut self.rng, &TYPES).unwrap();
+                    let operator = pick(&mut self.rng, &["==", "!="]).unwrap();
+                    format!("({} {} {})", self.expression(operand, depth), operator, self.expression(operand, depth))
+                }
+            },
+        }
+    }
+
+    // A literal or a variable of the type
+    fn leaf(&mut self, ty: Type) -> String {
+        let variables = self.variables(ty);
+        if !variables.is_empty() && self.chance(50) {
+            return pick(&mut self.rng, &variables).unwrap();
+        }
+        match ty {
+            Type::Number => match self.rng.next_in_range(0, 2) {
+                0 => self.rng.next_in_range(0, 100).to_string(),
+                1 => format!("{}.5", self.rng.next_in_range(0, 10)),
+                _ => self.rng.next_in_range(0, 9).to_string(),
+            },
+            Type::String => {
+                let word = pick(&mut self.rng, &["a", "lox", "hello", "", "x y"]).unwrap();
+                format!("\"{}\"", word)
+            }
+            Type::Boolean => pick(&mut self.rng, &["true", "false"]).unwrap().to_string(),
+        }
+    }
+}
+
+fn pick<T: Clone>(rng: &mut Rng, items: &[T]) -> Option<T> {
+    if items.is_empty() {
+        return None;
+    }
+    Some(items[rng.next_u64() as usize % items.len()].clone())
+}
+
+// One small edit that makes the program a syntax error: drop a closing token or
+// insert a stray one
+fn corrupt(rng: &mut Rng, program: &mut String) {
+    let closers: Vec<usize> = program.match_indices([';', ')', '}', ']']).map(|(i, _)| i).collect();
+    if !closers.is_empty() && rng.next_u64() % 2 == 0 {
+        let position = closers[rng.next_u64() as usize % closers.len()];
+        program.remove(position);
+    } else {
+        let spaces: Vec<usize> = program.match_indices(' ').map(|(i, _)| i).collect();
+        let position = if spaces.is_empty() { 0 } else { spaces[rng.next_u64() as usize % spaces.len()] };
+        let stray = pick(rng, &["(", ")", "=", "var", "+", ","]).unwrap();
+        program.insert_str(position, &format!(" {}", stray));
+    }
+}
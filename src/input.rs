@@ -0,0 +1,131 @@
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use std::sync::{Arc, Mutex};
+
+// Source of script input. Send + Sync for the same reason as output sinks.
+pub type Source = Box<dyn Read + Send + Sync>;
+
+// Where an interpreter built from ExecOptions reads its input from
+#[derive(Clone, Default)]
+pub enum StdinSource {
+    #[default]
+    Inherit,      // The process's stdin
+    Text(String), // Fixed input, e.g. scripted by a test or the playground
+    // A host reader. Options stay Clone, so the reader is shared and handed to the
+    // first interpreter built from them; later ones see empty input.
+    Reader(Arc<Mutex<Option<Source>>>),
+}
+
+impl StdinSource {
+    pub fn reader(reader: impl Read + Send + Sync + 'static) -> Self {
+        StdinSource::Reader(Arc::new(Mutex::new(Some(Box::new(reader)))))
+    }
+
+    pub fn into_input(self) -> Input {
+        match self {
+            StdinSource::Inherit => Input::stdin(),
+            StdinSource::Text(text) => Input::new(Box::new(Cursor::new(text.into_bytes()))),
+            StdinSource::Reader(shared) => match shared.lock().unwrap().take() {
+                Some(reader) => Input::new(reader),
+                None => Input::new(Box::new(io::empty())),
+            },
+        }
+    }
+}
+
+impl From<String> for StdinSource {
+    fn from(text: String) -> Self {
+        StdinSource::Text(text)
+    }
+}
+
+impl From<&str> for StdinSource {
+    fn from(text: &str) -> Self {
+        StdinSource::Text(text.to_string())
+    }
+}
+
+impl fmt::Debug for StdinSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StdinSource::Inherit => write!(f, "Inherit"),
+            StdinSource::Text(text) => f.debug_tuple("Text").field(text).finish(),
+            StdinSource::Reader(_) => write!(f, "Reader"),
+        }
+    }
+}
+
+// Character-level reader over an input source with one character of lookahead,
+// backing readChar()/peekChar()/atEof()
+pub struct Input {
+    reader: BufReader<Source>,
+    peeked: Option<Option<char>>, // Some(None) once end of input has been seen
+}
+
+impl Input {
+    pub fn new(source: Source) -> Self {
+        Input {
+            reader: BufReader::new(source),
+            peeked: None,
+        }
+    }
+
+    pub fn stdin() -> Self {
+        Input::new(Box::new(io::stdin()))
+    }
+
+    pub fn read_char(&mut self) -> io::Result<Option<char>> {
+        let c = self.peek_char()?;
+        self.peeked = None;
+        Ok(c)
+    }
+
+    pub fn peek_char(&mut self) -> io::Result<Option<char>> {
+        if let Some(c) = self.peeked {
+            return Ok(c);
+        }
+        let c = self.decode_char()?;
+        self.peeked = Some(c);
+        Ok(c)
+    }
+
+    pub fn at_eof(&mut self) -> io::Result<bool> {
+        Ok(self.peek_char()?.is_none())
+    }
+
+    // Decode one UTF-8 character; malformed bytes become U+FFFD
+    fn decode_char(&mut self) -> io::Result<Option<char>> {
+        let first = match self.next_byte()? {
+            Some(byte) => byte,
+            None => return Ok(None),
+        };
+        let len = match first {
+            0x00..=0x7F => return Ok(Some(first as char)),
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => return Ok(Some(char::REPLACEMENT_CHARACTER)),
+        };
+
+        let mut bytes = vec![first];
+        while bytes.len() < len {
+            // Only consume continuation bytes, so a truncated sequence doesn't eat the next character
+            match self.reader.fill_buf()?.first() {
+                Some(&byte) if byte & 0xC0 == 0x80 => {
+                    bytes.push(byte);
+                    self.reader.consume(1);
+                }
+                _ => return Ok(Some(char::REPLACEMENT_CHARACTER)),
+            }
+        }
+        Ok(Some(std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()).unwrap_or(char::REPLACEMENT_CHARACTER)))
+    }
+
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        let byte = self.reader.fill_buf()?.first().copied();
+        if byte.is_some() {
+            self.reader.consume(1);
+        }
+        Ok(byte)
+    }
+}
@@ -1,9 +1,32 @@
-use crate::expr::{Expr, LiteralValue};
+use crate::array::LoxArray;
+use crate::ast::Node;
+use crate::class::{HostClass, LoxClass};
+use crate::diagnostic::{Diagnostic, Stage};
+use crate::expr::{Expr, LiteralValue, LocalSlot, MatchArm};
+use crate::function::{Arity, Callable, Frame, LoxFunction, NativeContext};
+use crate::input::{Input, Source};
+use crate::natives;
+use crate::options::{ExecOptions, LanguageLevel};
+use crate::output::Sink;
+use crate::parser::Parser;
+use crate::profiler::Profiler;
+use crate::reload::{self, ReloadDiff};
+use crate::coverage::Coverage;
+use crate::crash;
+use crate::rng::Rng;
+use crate::scanner::Scanner;
+use crate::stats;
 use crate::stmt::Stmt;
+use crate::suggest;
 use crate::token::Token;
-use std::collections::HashMap;
+use crate::trace::Trace;
+use crate::watch::{self, Access, WatchEvent, Watchpoints};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
 use std::error::Error;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 // Define a RuntimeError type for handling errors during expression evaluation
 #[derive(Debug)]
@@ -19,114 +42,690 @@ impl fmt::Display for RuntimeError {
 }
 impl Error for RuntimeError {}
 
-// Environment for storing variables
-#[derive(Clone)]
+impl RuntimeError {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic::error(Stage::Runtime, self.message.clone(), self.line)
+    }
+}
+
+// Reasons for unwinding out of statement execution
+#[derive(Debug)]
+pub enum Signal {
+    Error(RuntimeError),
+    Return(LiteralValue), // `return` unwinding to the enclosing function call
+}
+
+impl From<RuntimeError> for Signal {
+    fn from(error: RuntimeError) -> Self {
+        Signal::Error(error)
+    }
+}
+
+// Environment for storing variables. Scopes are shared (closures keep their declaring
+// scope alive), so they are reference counted behind a Mutex to stay Send + Sync.
+//
+// Variables are kept in declaration order, so code the resolver has seen reads and
+// writes locals by slot (see LocalSlot) without hashing their names. Names are kept
+// for everything else: globals, unresolved code, the debugger and diagnostics.
 pub struct Environment {
-    values: HashMap<String, LiteralValue>,
-    enclosing: Option<Box<Environment>>,
+    values: Vec<LiteralValue>,
+    names: Vec<String>, // Parallel to `values`
+    // Slots by name, in the global scope only: it holds every native and top-level
+    // declaration. Local scopes are small enough to search.
+    index: Option<HashMap<String, usize>>,
+    // Slots of variables declared without an initializer under `strict_init`; reading
+    // one before its first assignment is an error
+    unassigned: HashSet<usize>,
+    enclosing: Option<Arc<Mutex<Environment>>>,
 }
 
 impl Environment {
+    // A global scope
     pub fn new() -> Self {
+        stats::environment_created();
         Environment {
-            values: HashMap::new(),
+            values: Vec::new(),
+            names: Vec::new(),
+            index: Some(HashMap::new()),
+            unassigned: HashSet::new(),
             enclosing: None,
         }
     }
 
      // Create a new environment that has a parent (enclosing scope)
-     pub fn from_enclosing(enclosing: Environment) -> Self {
+     pub fn from_enclosing(enclosing: Arc<Mutex<Environment>>) -> Self {
+        stats::environment_created();
         Environment {
-            values: HashMap::new(),
-            enclosing: Some(Box::new(enclosing)),
+            values: Vec::new(),
+            names: Vec::new(),
+            index: None,
+            unassigned: HashSet::new(),
+            enclosing: Some(enclosing),
         }
     }
 
+    // Declare a variable in this scope, in the next slot, or give an existing one of
+    // the same name a new value
     pub fn define(&mut self, name: String, value: LiteralValue) {
-        self.values.insert(name, value);
+        match self.slot_of(&name) {
+            Some(slot) => {
+                self.values[slot] = value;
+                self.unassigned.remove(&slot);
+            }
+            None => {
+                if let Some(index) = &mut self.index {
+                    index.insert(name.clone(), self.values.len());
+                }
+                self.values.push(value);
+                self.names.push(name);
+            }
+        }
+    }
+
+    // Declare a variable that holds no value until it is first assigned
+    pub fn declare_unassigned(&mut self, name: String) {
+        self.define(name.clone(), LiteralValue::Nil);
+        self.unassigned.extend(self.slot_of(&name));
     }
 
     pub fn get(&self, name: &str, line: usize) -> Result<LiteralValue, RuntimeError> {
+        match self.lookup(name, line)? {
+            Some(value) => Ok(value),
+            None => Err(self.undefined_variable(name, line)),
+        }
+    }
+
+    pub fn assign(&mut self, name: &str, value: LiteralValue, line: usize) -> Result<(), RuntimeError> {
+        if self.assign_existing(name, value) {
+            Ok(())
+        } else {
+            Err(self.undefined_variable(name, line))
+        }
+    }
+
+    // Read a variable the resolver placed at `slot` of the scope `depth` levels out.
+    // Code evaluated in a scope it wasn't resolved for finds something else there, and
+    // falls back to looking the name up.
+    pub fn get_at(&mut self, depth: usize, slot: usize, name: &str, line: usize) -> Result<LiteralValue, RuntimeError> {
+        match self.at_slot(depth, slot, name, |scope| scope.read(slot, name, line)) {
+            Some(result) => result,
+            None => self.get(name, line),
+        }
+    }
+
+    pub fn assign_at(&mut self, depth: usize, slot: usize, name: &str, value: LiteralValue, line: usize) -> Result<(), RuntimeError> {
+        let assigned = self.at_slot(depth, slot, name, |scope| {
+            scope.values[slot] = value.clone();
+            scope.unassigned.remove(&slot);
+        });
+        match assigned {
+            Some(()) => Ok(()),
+            None => self.assign(name, value, line),
+        }
+    }
+
+    // Every variable visible from this scope, innermost scope first
+    pub fn names(&self) -> Vec<String> {
+        let mut names = self.names.clone();
+        if let Some(enclosing) = &self.enclosing {
+            names.extend(enclosing.lock().unwrap().names());
+        }
+        names
+    }
+
+    // A variable of this scope only, not of enclosing ones
+    pub fn get_own(&self, name: &str) -> Option<LiteralValue> {
+        self.slot_of(name).map(|slot| self.values[slot].clone())
+    }
+
+    // Every variable of this scope only, in declaration order
+    pub fn own_variables(&self) -> impl Iterator<Item = (&str, &LiteralValue)> {
+        self.names.iter().map(String::as_str).zip(&self.values)
+    }
+
+    // Undeclare a variable of this scope. Later variables move down a slot, so this is
+    // only for the global scope, whose variables are never accessed by slot.
+    pub fn remove(&mut self, name: &str) {
+        let Some(slot) = self.slot_of(name) else {
+            return;
+        };
+        self.values.remove(slot);
+        self.names.remove(slot);
+        self.unassigned = self.unassigned.iter().filter(|&&other| other != slot).map(|&other| other - usize::from(other > slot)).collect();
+        if let Some(index) = &mut self.index {
+            *index = self.names.iter().enumerate().map(|(slot, name)| (name.clone(), slot)).collect();
+        }
+    }
+
+    fn slot_of(&self, name: &str) -> Option<usize> {
+        match &self.index {
+            Some(index) => index.get(name).copied(),
+            None => self.names.iter().position(|own| own == name),
+        }
+    }
+
+    // Run `f` on the scope `depth` levels out if `name` is at `slot` there
+    fn at_slot<R>(&mut self, depth: usize, slot: usize, name: &str, f: impl FnOnce(&mut Environment) -> R) -> Option<R> {
+        if depth == 0 {
+            return (self.names.get(slot).map(String::as_str) == Some(name)).then(|| f(self));
+        }
+        self.enclosing.as_ref()?.lock().unwrap().at_slot(depth - 1, slot, name, f)
+    }
+
+    fn read(&self, slot: usize, name: &str, line: usize) -> Result<LiteralValue, RuntimeError> {
+        if self.unassigned.contains(&slot) {
+            return Err(RuntimeError {
+                message: format!("Variable '{}' used before assignment.", name),
+                line,
+            });
+        }
+        Ok(self.values[slot].clone())
+    }
+
+    // None if no scope up the chain declares the variable
+    fn lookup(&self, name: &str, line: usize) -> Result<Option<LiteralValue>, RuntimeError> {
         log::debug!("getting var: {}", name);
-        if let Some(value) = self.values.get(name) {
-            log::debug!("got {:?}", value.clone());
-            Ok(value.clone())
+        if let Some(slot) = self.slot_of(name) {
+            self.read(slot, name, line).map(Some)
         } else if let Some(enclosing) = &self.enclosing {
             // If not found in the current environment, check the enclosing one
             log::debug!("not found in current, checking enclosing");
-            enclosing.get(name, line)
+            enclosing.lock().unwrap().lookup(name, line)
         } else {
-            Err(RuntimeError {
-                message: format!("Undefined variable '{}'.", name),
-                line,
-            })
+            Ok(None)
         }
     }
 
-    pub fn assign(&mut self, name: &str, value: LiteralValue, line: usize) -> Result<(), RuntimeError> {
-        if self.values.contains_key(name) {
+    // False if no scope up the chain declares the variable
+    fn assign_existing(&mut self, name: &str, value: LiteralValue) -> bool {
+        if let Some(slot) = self.slot_of(name) {
             log::debug!("assigning {:?} to {}", value, name);
-            self.values.insert(name.to_string(), value);
-            Ok(())
-        } else if let Some(enclosing) = &mut self.enclosing {
+            self.unassigned.remove(&slot);
+            self.values[slot] = value;
+            true
+        } else if let Some(enclosing) = &self.enclosing {
             log::debug!("var {} not declared in current scope, trying to assign in enclosing", name);
             // If not found in the current environment, try to assign in the enclosing one
-            enclosing.assign(name, value, line)
+            enclosing.lock().unwrap().assign_existing(name, value)
         } else {
-            Err(RuntimeError {
-                message: format!("Undefined variable '{}'.", name),
-                line,
-            })
+            false
         }
     }
+
+    // Whether any scope up the chain declares the variable
+    pub fn declares(&self, name: &str) -> bool {
+        self.slot_of(name).is_some() || self.enclosing.as_ref().is_some_and(|enclosing| enclosing.lock().unwrap().declares(name))
+    }
+
+    fn undefined_variable(&self, name: &str, line: usize) -> RuntimeError {
+        RuntimeError {
+            message: format!("Undefined variable '{}'.", name),
+            line,
+        }
+    }
+
+    // A scope holding the same variables, in the same enclosing scope
+    fn copy(&self) -> Environment {
+        stats::environment_created();
+        Environment {
+            values: self.values.clone(),
+            names: self.names.clone(),
+            index: self.index.clone(),
+            unassigned: self.unassigned.clone(),
+            enclosing: self.enclosing.clone(),
+        }
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Environment {
+    fn drop(&mut self) {
+        stats::object_freed();
+    }
+}
+
+// Global variables to start interpreters from: the natives of a language level and
+// whatever an embedder adds. Build them once and share them, e.g. across the programs
+// of a test runner or benchmark loop: Interpreter::with_globals and reset_globals copy
+// the table instead of registering every native again.
+pub struct Globals {
+    scope: Environment,
+}
+
+impl Globals {
+    pub fn new(language: LanguageLevel) -> Self {
+        let mut scope = Environment::new();
+        natives::define_natives(&mut scope, language);
+        Globals { scope }
+    }
+
+    pub fn define(&mut self, name: &str, value: LiteralValue) {
+        self.scope.define(name.to_string(), value);
+    }
+
+    // See Interpreter::register_class
+    pub fn register_class(&mut self, class: HostClass) {
+        let name = class.name().to_string();
+        self.define(&name, LiteralValue::Class(LoxClass::host(class)));
+    }
+
+    // A global scope holding a copy of the table
+    fn environment(&self) -> Environment {
+        self.scope.copy()
+    }
+}
+
+impl Clone for Globals {
+    fn clone(&self) -> Self {
+        Globals { scope: self.scope.copy() }
+    }
 }
 
-// Interpreter struct to evaluate expressions and statements
+impl fmt::Debug for Globals {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.scope.own_variables()).finish()
+    }
+}
+
+// Interpreter struct to evaluate expressions and statements.
+// Embedders hand one interpreter to each worker thread, so it must stay Send + Sync:
+// shared scopes use Arc<Mutex<_>> rather than Rc/RefCell.
 pub struct Interpreter {
-    environment: Environment,
+    initial_globals: Arc<Globals>, // What reset_globals starts over from
+    globals: Arc<Mutex<Environment>>,
+    environment: Arc<Mutex<Environment>>,
+    options: ExecOptions,
+    rng: Rng,
+    output: Sink,       // `print` and the output natives
+    error_output: Sink, // eprint()
+    input: Input,       // readChar() and friends
+    profiler: Option<Profiler>,
+    coverage: Option<Coverage>,
+    trace: Option<Trace>,
+    watchpoints: Option<Watchpoints>,
+    virtual_clock: u64, // Milliseconds, see tick_virtual_clock
+    output_bytes: usize,
+    error_output_bytes: usize,
+    peak_call_depth: usize,
+    frames: Vec<Frame>,    // Active calls, innermost last
+    // Scope each active call was made from, parallel to `frames`, for evaluate_in_frame
+    frame_environments: Vec<Arc<Mutex<Environment>>>,
+    backtrace: Vec<Frame>, // Call stack at the last runtime error
+}
+
+// Compile-time check that keeps the Send + Sync guarantee from silently regressing
+const fn assert_send_sync<T: Send + Sync>() {}
+const _: () = {
+    assert_send_sync::<Interpreter>();
+    assert_send_sync::<Environment>();
+    assert_send_sync::<RuntimeError>();
+};
+
+// Outcome of a `run_with_yield` slice
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunStatus {
+    Yielded,  // Budget exhausted, call again with the same cursor to continue
+    Finished, // Every statement has been executed
+}
+
+// What a for-in loop goes over, and how far it has got
+enum Iteration {
+    // Array elements, map keys or string characters, taken up front
+    Values(std::vec::IntoIter<LiteralValue>),
+    // The bound next() method of the iterator an iter() method returned
+    Protocol(LiteralValue),
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
     pub fn new() -> Self {
+        Self::with_options(ExecOptions::default())
+    }
+
+    pub fn with_options(options: ExecOptions) -> Self {
+        let globals = Arc::new(Globals::new(options.language));
+        Self::with_globals(globals, options)
+    }
+
+    // Start from prebuilt globals. They decide which natives exist, whatever
+    // options.language says.
+    pub fn with_globals(initial_globals: Arc<Globals>, options: ExecOptions) -> Self {
+        let globals = Arc::new(Mutex::new(initial_globals.environment()));
+        let seed = options.seed.or(options.deterministic.then_some(0));
+        let rng = seed.map_or_else(Rng::from_time, Rng::new);
+        let input = options.stdin.clone().into_input();
+        let profiler = options.profile.then(Profiler::new);
+        let coverage = options.coverage.then(Coverage::new);
+        let trace = options.record.then(Trace::new);
         Interpreter {
-            environment: Environment::new(),
+            environment: Arc::clone(&globals),
+            initial_globals,
+            globals,
+            options,
+            rng,
+            output: Box::new(io::stdout()),
+            error_output: Box::new(io::stderr()),
+            input,
+            profiler,
+            coverage,
+            trace,
+            watchpoints: None,
+            virtual_clock: 0,
+            output_bytes: 0,
+            error_output_bytes: 0,
+            peak_call_depth: 0,
+            frames: Vec::new(),
+            frame_environments: Vec::new(),
+            backtrace: Vec::new(),
         }
     }
 
+    pub fn options(&self) -> &ExecOptions {
+        &self.options
+    }
+
+    // Redirect script output, e.g. into an OutputBuffer to capture it
+    pub fn set_output(&mut self, output: Sink) {
+        self.output = output;
+    }
+
+    pub fn set_error_output(&mut self, error_output: Sink) {
+        self.error_output = error_output;
+    }
+
+    // Replace the input source, e.g. with scripted input in tests
+    pub fn set_input(&mut self, source: Source) {
+        self.input = Input::new(source);
+    }
+
+    pub fn input(&mut self) -> &mut Input {
+        &mut self.input
+    }
+
+    pub fn write_output(&mut self, text: &str, line: usize) -> Result<(), RuntimeError> {
+        self.output_bytes += text.len();
+        self.output.write_all(text.as_bytes()).map_err(|error| RuntimeError {
+            message: format!("Failed to write output: {}.", error),
+            line,
+        })
+    }
+
+    pub fn write_error_output(&mut self, text: &str, line: usize) -> Result<(), RuntimeError> {
+        self.error_output_bytes += text.len();
+        self.error_output.write_all(text.as_bytes()).map_err(|error| RuntimeError {
+            message: format!("Failed to write error output: {}.", error),
+            line,
+        })
+    }
+
+    // Call back on every read or write of the named variables, e.g. for a debugger
+    pub fn set_watchpoints(&mut self, watchpoints: Watchpoints) {
+        self.watchpoints = Some(watchpoints);
+    }
+
+    // Bytes the script has written to its output and error output so far
+    pub fn output_bytes(&self) -> (usize, usize) {
+        (self.output_bytes, self.error_output_bytes)
+    }
+
+    // Most calls that have been active at once
+    pub fn peak_call_depth(&self) -> usize {
+        self.peak_call_depth
+    }
+
+    // Timings collected so far when profiling is enabled
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    // Line hit counts collected so far when coverage is enabled
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    // Statements and variable writes recorded so far when recording is enabled
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    // What clock() returns under ExecOptions::deterministic: seconds on a virtual clock
+    // that starts at 0 and advances a millisecond per call
+    pub fn tick_virtual_clock(&mut self) -> f64 {
+        self.virtual_clock += 1;
+        self.virtual_clock as f64 / 1000.0
+    }
+
+    // What sleep() does under ExecOptions::deterministic, to the nearest millisecond
+    pub fn advance_virtual_clock(&mut self, seconds: f64) {
+        self.virtual_clock += (seconds * 1000.0).round() as u64;
+    }
+
+    // Per-interpreter random source used by the random natives
+    pub fn rng(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+
     pub fn interpret(&mut self, statements: Vec<Stmt>) -> Result<(), RuntimeError> {
+        self.backtrace.clear();
         for stmt in statements {
-            self.execute(&stmt)?;
+            match self.execute(&stmt) {
+                Ok(()) => {}
+                // A top-level `return` simply ends the program
+                Err(Signal::Return(_)) => break,
+                Err(Signal::Error(error)) => return Err(error),
+            }
         }
         Ok(())
     }
 
+    // Look up a global variable (natives included), for natives and embedders
+    pub fn get_global(&self, name: &str) -> Option<LiteralValue> {
+        self.globals.lock().unwrap().get_own(name)
+    }
+
+    pub fn define_global(&mut self, name: &str, value: LiteralValue) {
+        self.globals.lock().unwrap().define(name.to_string(), value);
+    }
+
+    // Call a function (or class) value from the host, e.g. a hook the loaded script
+    // defined, as often as needed:
+    //     let on_event = interpreter.get_global("onEvent").unwrap();
+    //     interpreter.call(&on_event, vec![to_value(event)])?;
+    // The call has no call site, so it is made from line 0. As after `interpret`, the
+    // backtrace is that of this call's error, if any.
+    pub fn call(&mut self, callee: &LiteralValue, arguments: Vec<LiteralValue>) -> Result<LiteralValue, RuntimeError> {
+        self.backtrace.clear();
+        self.call_value(callee, arguments, 0)
+    }
+
+    // Evaluate an expression, given as source, in the scope of an active call: for a
+    // debugger that pauses the script in a native or host function (a `breakpoint()`
+    // native, say). Depth 0 is the code that made the innermost call, i.e. called the
+    // native; 1 its caller, and so on up to the script. With no call active, depth 0 is
+    // the global scope. With `read_only`, an expression that assigns or calls anything
+    // is refused before it runs (getters still run on property access); otherwise it
+    // may change the paused program's state.
+    pub fn evaluate_in_frame(&mut self, source: &str, depth: usize, read_only: bool) -> Result<LiteralValue, Vec<Diagnostic>> {
+        let environment = match self.frame_environments.iter().rev().nth(depth) {
+            Some(environment) => Arc::clone(environment),
+            None if depth == 0 => Arc::clone(&self.environment),
+            None => return Err(vec![Diagnostic::error(Stage::Runtime, format!("No frame at depth {}.", depth), 0)]),
+        };
+
+        let mut scanner = Scanner::new(source.to_string());
+        scanner.scan_tokens();
+        if scanner.has_error() {
+            return Err(scanner.diagnostics().to_vec());
+        }
+        let mut parser = Parser::new(scanner.get_tokens().to_vec());
+        let expression = parser.parse_expression();
+        let expression = match expression {
+            Some(expression) if !parser.has_error() => expression,
+            _ => return Err(parser.diagnostics().to_vec()),
+        };
+        if read_only {
+            if let Some(node) = side_effect(Node::Expr(&expression)) {
+                let message = format!("Can't {} in a read-only evaluation.", node);
+                return Err(vec![Diagnostic::error(Stage::Resolve, message, expression.line())]);
+            }
+        }
+
+        // The evaluation must not show up in the backtrace of the paused program
+        let backtrace = std::mem::take(&mut self.backtrace);
+        let result = self.evaluate_in(&expression, environment);
+        self.backtrace = backtrace;
+        result.map_err(|error| vec![error.to_diagnostic()])
+    }
+
+    // Active calls, innermost last; the frames evaluate_in_frame counts from the end
+    pub fn call_stack(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    // Hot reload: run an updated version of the script in this interpreter, then put back
+    // the values the globals named in `keep` had before (a game's state, say), so the new
+    // version's top-level code still sees its own initial values. Functions and
+    // classes the new version no longer declares are removed; other globals keep their
+    // values unless the new version assigns them. On a runtime error the reload stops
+    // part way, with the kept globals not restored.
+    pub fn reload(&mut self, statements: Vec<Stmt>, keep: &[&str]) -> Result<ReloadDiff, RuntimeError> {
+        let before = self.definitions();
+        let kept: Vec<(&str, LiteralValue)> = keep.iter().filter_map(|name| Some((*name, self.get_global(name)?))).collect();
+        {
+            let mut globals = self.globals.lock().unwrap();
+            for name in before.keys() {
+                globals.remove(name);
+            }
+        }
+        self.interpret(statements)?;
+        for (name, value) in kept {
+            self.define_global(name, value);
+        }
+        Ok(ReloadDiff::new(&before, &self.definitions()))
+    }
+
+    // The script's global functions and classes, with their code
+    fn definitions(&self) -> BTreeMap<String, String> {
+        let globals = self.globals.lock().unwrap();
+        globals
+            .own_variables()
+            .filter_map(|(name, value)| Some((name.to_string(), reload::definition(value)?)))
+            .collect()
+    }
+
+    // Forget every global the programs run so far defined or changed, to run an
+    // unrelated one next. Output, input, options and the profiler are kept.
+    pub fn reset_globals(&mut self) {
+        self.globals = Arc::new(Mutex::new(self.initial_globals.environment()));
+        self.environment = Arc::clone(&self.globals);
+        self.backtrace.clear();
+    }
+
+    // Expose a class implemented in Rust to scripts as a global, see HostClass
+    pub fn register_class(&mut self, class: HostClass) {
+        let name = class.name().to_string();
+        self.define_global(&name, LiteralValue::Class(LoxClass::host(class)));
+    }
+
+    // Cooperative driver for hosts without threads (GUI event loops, WASM):
+    // executes at most `budget` top-level statements starting at `*cursor`, then
+    // hands control back. Blocks count as a single statement.
+    pub fn run_with_yield(&mut self, statements: &[Stmt], cursor: &mut usize, budget: usize) -> Result<RunStatus, RuntimeError> {
+        let end = statements.len().min(cursor.saturating_add(budget.max(1)));
+        while *cursor < end {
+            let stmt = &statements[*cursor];
+            *cursor += 1;
+            match self.execute(stmt) {
+                Ok(()) => {}
+                Err(Signal::Return(_)) => {
+                    *cursor = statements.len();
+                    break;
+                }
+                Err(Signal::Error(error)) => return Err(error),
+            }
+        }
+
+        if *cursor < statements.len() {
+            log::debug!("yielding to host after statement {}", cursor);
+            Ok(RunStatus::Yielded)
+        } else {
+            Ok(RunStatus::Finished)
+        }
+    }
+
      // Execute a block of statements in a new environment
-    fn execute_block(&mut self, statements: &[Stmt], environment: Environment) -> Result<(), RuntimeError> {
+    pub(crate) fn execute_block(&mut self, statements: &[Stmt], environment: Environment) -> Result<(), Signal> {
         log::debug!("--- ENTERING BLOCK ---");
-        log::debug!("Environment before block: {:?}", self.environment.values);
+        log::debug!("Environment before block: {:?}", self.environment.lock().unwrap().values);
 
-        // Set new environment as the current one, saving the current as "enclosing"
-        self.environment = environment;
+        // Execute the block in the new environment, then go back to the enclosing one
+        let result = self.with_environment(Arc::new(Mutex::new(environment)), |interpreter| {
+            let mut deferred = Vec::new();
+            let result = statements.iter().try_for_each(|stmt| {
+                if let Stmt::Defer { expression, .. } = stmt {
+                    deferred.push(expression);
+                }
+                interpreter.execute(stmt)
+            });
+            interpreter.run_deferred(deferred, result)
+        });
 
-        log::debug!("New environment inside block: {:?}", self.environment.values);
+        log::debug!("Restored environment after block: {:?}", self.environment.lock().unwrap().values);
+        log::debug!("--- EXITING BLOCK ---");
+        result
+    }
 
-        // Execute the block
-        let result = self.interpret(statements.to_vec());
+    // On leaving a block normally or by `return`, evaluate the expressions its `defer`
+    // statements reached, last first. A runtime error ends the program without them.
+    fn run_deferred(&mut self, deferred: Vec<&Expr>, result: Result<(), Signal>) -> Result<(), Signal> {
+        if matches!(result, Err(Signal::Error(_))) {
+            return result;
+        }
+        for expression in deferred.into_iter().rev() {
+            self.evaluate(expression)?;
+        }
+        result
+    }
 
-        // Revert back to the enclosing environment (the one before the block)
-        self.environment = *self.environment.enclosing.take().unwrap();
+    // Execute statements, timing them per line when profiling. Blocks aren't timed
+    // or counted themselves since their statements already are.
+    fn execute(&mut self, stmt: &Stmt) -> Result<(), Signal> {
+        stats::statement_executed();
+        crash::set_line(stmt.line());
+        if let Some(coverage) = self.coverage.as_mut().filter(|_| !matches!(stmt, Stmt::Block(_))) {
+            coverage.record_line(stmt.line());
+        }
+        if let Some(trace) = self.trace.as_mut().filter(|_| !matches!(stmt, Stmt::Block(_))) {
+            trace.record_statement(stmt.line(), self.frames.len());
+        }
+        if self.profiler.is_none() || matches!(stmt, Stmt::Block(_)) {
+            return self.execute_statement(stmt);
+        }
 
-        log::debug!("Restored environment after block: {:?}", self.environment.values);
-        log::debug!("--- EXITING BLOCK ---");
+        let start = Instant::now();
+        let result = self.execute_statement(stmt);
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record_line(stmt.line(), start.elapsed());
+        }
         result
     }
 
-    // Execute statements
-    fn execute(&mut self, stmt: &Stmt) -> Result<(), RuntimeError> {
+    fn execute_statement(&mut self, stmt: &Stmt) -> Result<(), Signal> {
         match stmt {
             Stmt::Print(expr) => {
                 let value = self.evaluate(expr)?;
-                println!("{}", self.literal_to_string(value));
+                let text = self.literal_to_string(value, expr.line())? + "\n";
+                self.write_output(&text, expr.line())?;
                 Ok(())
             }
             Stmt::Expression(expr) => {
@@ -136,52 +735,409 @@ impl Interpreter {
             Stmt::Var { name, initializer } => {
                 let value = if let Some(expr) = initializer {
                     self.evaluate(expr)?
+                } else if self.options.strict_init {
+                    self.environment.lock().unwrap().declare_unassigned(name.lexeme.clone());
+                    return Ok(());
                 } else {
                     LiteralValue::Nil
                 };
-                self.environment.define(name.lexeme.clone(), value.clone());
+                self.environment.lock().unwrap().define(name.lexeme.clone(), value.clone());
                 log::debug!("defined variable {} with value: {:?}", name.lexeme.clone(), value);
+                self.notify_access(name, Access::Write, &value, None);
                 Ok(())
             }
             Stmt::Block(statements) => {
                 // Create a new environment and execute the block
-                self.execute_block(statements, Environment::from_enclosing(self.environment.clone()))
+                self.execute_block(statements, Environment::from_enclosing(Arc::clone(&self.environment)))
+            }
+            Stmt::Function(declaration) => {
+                let function = LoxFunction::new(Arc::clone(declaration), Arc::clone(&self.environment));
+                log::debug!("defined function {}", function.name());
+                self.environment.lock().unwrap().define(declaration.name.lexeme.clone(), LiteralValue::Function(function));
+                Ok(())
+            }
+            Stmt::Class(declaration) => {
+                let class = LoxClass::new(Arc::clone(declaration), Arc::clone(&self.environment));
+                self.environment.lock().unwrap().define(declaration.name.lexeme.clone(), LiteralValue::Class(class));
+                Ok(())
+            }
+            Stmt::ForIn { name, iterable, body, .. } => {
+                let collection = self.evaluate(iterable)?;
+                let mut iteration = self.iteration(collection, name.line)?;
+                while let Some(value) = self.next_value(&mut iteration, name.line)? {
+                    let mut environment = Environment::from_enclosing(Arc::clone(&self.environment));
+                    environment.define(name.lexeme.clone(), value.clone());
+                    self.notify_access(name, Access::Write, &value, None);
+                    self.execute_block(std::slice::from_ref(body), environment)?;
+                }
+                Ok(())
+            }
+            Stmt::Return { value, .. } => {
+                let value = match value {
+                    Some(expr) => self.evaluate(expr)?,
+                    None => LiteralValue::Nil,
+                };
+                Err(Signal::Return(value))
             }
+            // Only recorded here: execute_block runs the expression when the block exits
+            Stmt::Defer { .. } => Ok(()),
         }
     }
 
-    pub fn literal_to_string(&self, value: LiteralValue) -> String {
-        match value {
-            LiteralValue::StringLiteral(s) => s,
-            LiteralValue::NumberLiteral(n) => {
-                if n.fract() == 0.0 {
-                    format!("{}", n as i64) // If it's an integer, format without decimal point
-                } else {
-                    n.to_string() // Otherwise, format as a float
-                }
-            }
+    // With `strict_properties`, the error names the closest existing property
+    fn undefined_property(&self, name: &Token, candidates: Vec<String>) -> RuntimeError {
+        let mut message = format!("Undefined property '{}'.", name.lexeme);
+        if self.options.strict_properties {
+            message += &suggest::did_you_mean(&name.lexeme, candidates.iter().map(String::as_str));
+        }
+        RuntimeError { message, line: name.line }
+    }
+
+    // Evaluate an expression in the given scope instead of the current one: a field
+    // initializer in a scope binding `this`, a debugger expression in a paused call's
+    // scope. Assignments and declarations made by the expression land in that scope.
+    pub fn evaluate_in(&mut self, expr: &Expr, environment: Arc<Mutex<Environment>>) -> Result<LiteralValue, RuntimeError> {
+        self.with_environment(environment, |interpreter| interpreter.evaluate(expr))
+    }
+
+    // The scope code runs in right now, e.g. to evaluate in a child of it
+    pub fn current_environment(&self) -> Arc<Mutex<Environment>> {
+        Arc::clone(&self.environment)
+    }
+
+    // Run `f` with `environment` as the current scope, restoring the previous one after,
+    // whether or not it succeeds
+    fn with_environment<T>(&mut self, environment: Arc<Mutex<Environment>>, f: impl FnOnce(&mut Self) -> T) -> T {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let result = f(self);
+        self.environment = previous;
+        result
+    }
+
+    // Text `print` shows for a value. With the extensions, instances whose class
+    // defines toString() are shown by calling it, which may run arbitrary Lox code.
+    pub fn literal_to_string(&mut self, value: LiteralValue, line: usize) -> Result<String, RuntimeError> {
+        self.display(value, line, &mut Vec::new())
+    }
+
+    // `open` holds the ids of the arrays and maps being printed around this value: one
+    // that contains itself prints as `[...]` / `{...}` instead of recursing forever
+    fn display(&mut self, value: LiteralValue, line: usize, open: &mut Vec<usize>) -> Result<String, RuntimeError> {
+        let too_deep = self.options.display_depth.is_some_and(|depth| open.len() >= depth);
+        let text = match value {
+            LiteralValue::StringLiteral(s) => Arc::unwrap_or_clone(s),
+            LiteralValue::NumberLiteral(n) => self.options.number_format.format(n),
             LiteralValue::BooleanLiteral(b) => b.to_string(),
             LiteralValue::Nil => "nil".to_string(),
+            LiteralValue::Function(function) => format!("<fn {}>", function.name()),
+            LiteralValue::NativeFunction(_) | LiteralValue::HostMethod(_) => "<native fn>".to_string(),
+            LiteralValue::Class(class) => class.name().to_string(),
+            LiteralValue::Instance(instance) => {
+                let to_string = instance.class().find_method("toString").cloned();
+                match to_string {
+                    Some(method) if self.extensions() => {
+                        let method = LiteralValue::Function(method.bind(LiteralValue::Instance(instance)));
+                        match self.call_value(&method, Vec::new(), line)? {
+                            LiteralValue::StringLiteral(s) => Arc::unwrap_or_clone(s),
+                            _ => {
+                                return Err(RuntimeError {
+                                    message: "toString() must return a string.".to_string(),
+                                    line,
+                                })
+                            }
+                        }
+                    }
+                    _ => format!("{} instance", instance.class().name()),
+                }
+            }
+            LiteralValue::Array(array) if too_deep || open.contains(&array.id()) => "[...]".to_string(),
+            LiteralValue::Array(array) => {
+                open.push(array.id());
+                let mut elements = Vec::with_capacity(array.len());
+                for element in self.truncate_display(array.to_vec()) {
+                    elements.push(self.display(element, line, open)?);
+                }
+                if elements.len() < array.len() {
+                    elements.push("...".to_string());
+                }
+                open.pop();
+                format!("[{}]", elements.join(", "))
+            }
+            LiteralValue::Map(map) if too_deep || open.contains(&map.id()) => "{...}".to_string(),
+            LiteralValue::Map(map) => {
+                open.push(map.id());
+                let mut entries = Vec::with_capacity(map.len());
+                for (key, value) in self.truncate_display(map.entries()) {
+                    entries.push(format!("{}: {}", key, self.display(value, line, open)?));
+                }
+                if entries.len() < map.len() {
+                    entries.push("...".to_string());
+                }
+                open.pop();
+                format!("{{{}}}", entries.join(", "))
+            }
+        };
+        Ok(text)
+    }
+
+    // The entries of a container that fit in the display width
+    fn truncate_display<T>(&self, mut entries: Vec<T>) -> Vec<T> {
+        if let Some(width) = self.options.display_width {
+            entries.truncate(width);
+        }
+        entries
+    }
+
+    // Call a function value with already-evaluated arguments. Also the entry point
+    // natives use to call back into Lox code.
+    pub fn call_value(&mut self, callee: &LiteralValue, arguments: Vec<LiteralValue>, line: usize) -> Result<LiteralValue, RuntimeError> {
+        let callable: &dyn Callable = match callee {
+            LiteralValue::NativeFunction(native) => native,
+            LiteralValue::HostMethod(method) => method,
+            LiteralValue::Function(function) => function,
+            LiteralValue::Class(class) => class,
+            _ => {
+                return Err(RuntimeError {
+                    message: "Can only call functions and classes.".to_string(),
+                    line,
+                })
+            }
+        };
+        self.check_arity(callable.arity(), arguments.len(), line)?;
+
+        self.frames.push(Frame {
+            name: callable.name().to_string(),
+            line,
+        });
+        self.frame_environments.push(Arc::clone(&self.environment));
+        self.peak_call_depth = self.peak_call_depth.max(self.frames.len());
+        if let Some(profiler) = &mut self.profiler {
+            profiler.enter(callable.name());
+        }
+        let result = callable.call(self, arguments);
+        if let Some(profiler) = &mut self.profiler {
+            profiler.exit();
+        }
+        // The innermost failing call sees the error first and records the full stack
+        if matches!(result, Err(Signal::Error(_))) && self.backtrace.is_empty() {
+            self.backtrace = self.frames.clone();
+        }
+        self.frames.pop();
+        self.frame_environments.pop();
+
+        match result {
+            Ok(value) | Err(Signal::Return(value)) => Ok(value),
+            Err(Signal::Error(error)) => Err(error),
+        }
+    }
+
+    // Line of the innermost active call site
+    pub fn call_line(&self) -> usize {
+        self.frames.last().map_or(0, |frame| frame.line)
+    }
+
+    // Call stack at the most recent runtime error of `interpret`, innermost last;
+    // empty if the error happened outside any call
+    pub fn backtrace(&self) -> &[Frame] {
+        &self.backtrace
+    }
+
+    fn check_arity(&self, arity: Arity, count: usize, line: usize) -> Result<(), RuntimeError> {
+        if arity.accepts(count) {
+            return Ok(());
+        }
+        let message = match arity {
+            Arity::Fixed(n) => format!("Expected {} arguments but got {}.", n, count),
+            Arity::AtLeast(n) => format!("Expected at least {} arguments but got {}.", n, count),
+        };
+        Err(RuntimeError { message, line })
+    }
+
+    // By slot where the resolver found a local, in the global scope where it found none,
+    // and by name in code it never saw
+    fn read_variable(&self, name: &Token, slot: &LocalSlot) -> Result<LiteralValue, RuntimeError> {
+        let value = match slot.get() {
+            Some((depth, slot)) => self.environment.lock().unwrap().get_at(depth, slot, &name.lexeme, name.line),
+            None if slot.is_global() => self.globals.lock().unwrap().get(&name.lexeme, name.line),
+            None => self.environment.lock().unwrap().get(&name.lexeme, name.line),
+        };
+        value.map_err(|error| self.suggest_variable(name, error))
+    }
+
+    // At LanguageLevel::Extended, an "Undefined variable" error suggests the closest
+    // visible name. The book's message is graded exactly, so it is left alone there.
+    fn suggest_variable(&self, name: &Token, error: RuntimeError) -> RuntimeError {
+        let environment = self.environment.lock().unwrap();
+        if self.options.language == LanguageLevel::Book || environment.declares(&name.lexeme) {
+            return error;
+        }
+        let names = environment.names();
+        let suggestion = suggest::did_you_mean(&name.lexeme, names.iter().map(String::as_str));
+        RuntimeError {
+            message: error.message + &suggestion,
+            ..error
         }
     }
 
     // Evaluate the given expression and return a result as a String or error
     pub fn evaluate(&mut self, expr: &Expr) -> Result<LiteralValue, RuntimeError> {
         match expr {
-            Expr::Literal(value) => self.visit_literal(value),
-            Expr::Assign { name, value } => {
+            Expr::Literal { value, .. } => self.visit_literal(value),
+            Expr::Assign { name, value, slot } => {
                 let new_value = self.evaluate(value)?;
-                self.environment.assign(&name.lexeme, new_value.clone(), name.line)?;
+                let previous = if self.watching(&name.lexeme) {
+                    self.read_variable(name, slot).ok()
+                } else {
+                    None
+                };
+                let assigned = match slot.get() {
+                    Some((depth, slot)) => self.environment.lock().unwrap().assign_at(depth, slot, &name.lexeme, new_value.clone(), name.line),
+                    None if slot.is_global() => self.globals.lock().unwrap().assign(&name.lexeme, new_value.clone(), name.line),
+                    None => self.environment.lock().unwrap().assign(&name.lexeme, new_value.clone(), name.line),
+                };
+                assigned.map_err(|error| self.suggest_variable(name, error))?;
+                self.notify_access(name, Access::Write, &new_value, previous);
                 Ok(new_value)
             },
-            Expr::Variable(name) => self.environment.get(&name.lexeme, name.line),
+            Expr::Variable(name, slot) => {
+                let value = self.read_variable(name, slot)?;
+                self.notify_access(name, Access::Read, &value, None);
+                Ok(value)
+            }
+            Expr::Array { elements, .. } => {
+                let mut values = Vec::with_capacity(elements.len());
+                for element in elements {
+                    values.push(self.evaluate(element)?);
+                }
+                Ok(LiteralValue::Array(LoxArray::new(values)))
+            }
+            Expr::Index { object, bracket, index } => {
+                let object_value = self.evaluate(object)?;
+                let index_value = self.evaluate(index)?;
+                self.visit_index(&object_value, &index_value, bracket.line)
+            }
+            Expr::Slice { object, bracket, start, end } => {
+                let object_value = self.evaluate(object)?;
+                let start = start.as_ref().map(|start| self.evaluate(start)).transpose()?;
+                let end = end.as_ref().map(|end| self.evaluate(end)).transpose()?;
+                self.visit_slice(&object_value, start.as_ref(), end.as_ref(), bracket.line)
+            }
+            Expr::SetIndex { object, bracket, index, value } => {
+                let object_value = self.evaluate(object)?;
+                let index_value = self.evaluate(index)?;
+                let new_value = self.evaluate(value)?;
+                match object_value {
+                    LiteralValue::Array(array) => {
+                        let position = self.expect_index(&index_value, array.len(), bracket.line)?;
+                        array.set(position, new_value.clone());
+                        Ok(new_value)
+                    }
+                    LiteralValue::Map(map) => {
+                        let key = self.expect_key(&index_value, bracket.line)?;
+                        map.set(key, new_value.clone());
+                        Ok(new_value)
+                    }
+                    _ => Err(RuntimeError {
+                        message: "Only array and map elements can be assigned.".to_string(),
+                        line: bracket.line,
+                    }),
+                }
+            }
+            Expr::Get { object, name } => match self.evaluate(object)? {
+                LiteralValue::Instance(instance) => {
+                    let Some(value) = instance.get(&name.lexeme) else {
+                        return Err(self.undefined_property(name, instance.property_names()));
+                    };
+                    // Getters run on access instead of producing a bound method
+                    match &value {
+                        LiteralValue::Function(method) if method.declaration.getter => {
+                            self.call_value(&value, Vec::new(), name.line)
+                        }
+                        LiteralValue::HostMethod(method) if method.getter => self.call_value(&value, Vec::new(), name.line),
+                        _ => Ok(value),
+                    }
+                }
+                LiteralValue::Class(class) => class
+                    .get(&name.lexeme)
+                    .ok_or_else(|| self.undefined_property(name, class.class_method_names())),
+                _ => Err(RuntimeError {
+                    message: "Only instances have properties.".to_string(),
+                    line: name.line,
+                }),
+            },
+            Expr::Set { object, name, value } => {
+                let LiteralValue::Instance(instance) = self.evaluate(object)? else {
+                    return Err(RuntimeError {
+                        message: "Only instances have fields.".to_string(),
+                        line: name.line,
+                    });
+                };
+                let new_value = self.evaluate(value)?;
+                match instance.class().host_setter(&name.lexeme) {
+                    Some(setter) => setter(&mut NativeContext::new(self, name.line), &instance, new_value.clone())?,
+                    None => instance.set(&name.lexeme, new_value.clone()),
+                }
+                Ok(new_value)
+            }
+            Expr::This(keyword, slot) => self.read_variable(keyword, slot),
+            Expr::Call { callee, paren, arguments } => {
+                let callee_value = self.evaluate(callee)?;
+                let mut argument_values = Vec::with_capacity(arguments.len());
+                for argument in arguments {
+                    argument_values.push(self.evaluate(argument)?);
+                }
+                self.call_value(&callee_value, argument_values, paren.line)
+            }
             Expr::Unary { operator, right } => self.visit_unary(operator, right),
             Expr::Binary { left, operator, right } => self.visit_binary(left, operator, right),
             Expr::Grouping(expr) => self.visit_grouping(expr),
+            Expr::Match { keyword, subject, arms } => self.visit_match(keyword, subject, arms),
+        }
+    }
+
+    // The first arm whose pattern matches is evaluated in a scope of its own, holding
+    // what the pattern bound
+    fn visit_match(&mut self, keyword: &Token, subject: &Expr, arms: &[MatchArm]) -> Result<LiteralValue, RuntimeError> {
+        let value = self.evaluate(subject)?;
+        for arm in arms {
+            let mut bindings = Vec::new();
+            if !arm.pattern.matches(&value, &mut bindings) {
+                continue;
+            }
+            let mut environment = Environment::from_enclosing(Arc::clone(&self.environment));
+            for (name, value) in bindings {
+                environment.define(name.lexeme.clone(), value.clone());
+                self.notify_access(name, Access::Write, &value, None);
+            }
+            return self.evaluate_in(&arm.body, Arc::new(Mutex::new(environment)));
         }
+        Err(RuntimeError {
+            message: format!("No pattern matches the {} value.", value.type_name()),
+            line: keyword.line,
+        })
     }
     
 
+    fn watching(&self, name: &str) -> bool {
+        self.watchpoints.as_ref().is_some_and(|watchpoints| watchpoints.watches(name))
+    }
+
+    // Report a variable access to the watchpoints, and a write to the trace
+    fn notify_access(&mut self, name: &Token, access: Access, value: &LiteralValue, previous: Option<LiteralValue>) {
+        if let Some(trace) = self.trace.as_mut().filter(|_| access == Access::Write) {
+            trace.record_write(&name.lexeme, watch::describe(value));
+        }
+        if let Some(watchpoints) = self.watchpoints.as_mut().filter(|watchpoints| watchpoints.watches(&name.lexeme)) {
+            watchpoints.notify(&WatchEvent {
+                name: name.lexeme.clone(),
+                access,
+                value: value.clone(),
+                previous,
+                line: name.line,
+            });
+        }
+    }
+
     fn visit_literal(&self, value: &LiteralValue) -> Result<LiteralValue, RuntimeError> {
         Ok(value.clone()) // Return the literal value as-is
     }
@@ -217,15 +1173,26 @@ impl Interpreter {
     
 
     fn visit_binary(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Result<LiteralValue, RuntimeError> {
-        let left_value = self.evaluate(left)?;
+        let mut left_value = self.evaluate(left)?;
         let right_value = self.evaluate(right)?;
+        if self.extensions() {
+            if let Some(result) = self.overloaded_binary(&left_value, operator, &right_value)? {
+                return Ok(result);
+            }
+        }
     
         match operator.token_type {
             crate::token::TokenType::PLUS => {
                 // Handle string concatenation
-                if let (LiteralValue::StringLiteral(left_str), LiteralValue::StringLiteral(right_str)) = (&left_value, &right_value) {
+                if let (LiteralValue::StringLiteral(left_str), LiteralValue::StringLiteral(right_str)) = (&mut left_value, &right_value) {
                     log::debug!("concatenating strings {} and {}", left_str, right_str);
-                    return Ok(LiteralValue::StringLiteral(left_str.clone() + right_str));
+                    // Nothing else holds a left side that is itself a concatenation, as in
+                    // the middle of `a + b + c`: append to it, amortized O(1) per character
+                    match Arc::get_mut(left_str) {
+                        Some(owned) => owned.push_str(right_str),
+                        None => *left_str = Arc::new([left_str.as_str(), right_str.as_str()].concat()),
+                    }
+                    return Ok(left_value);
                 }
     
                 // Handle numeric addition
@@ -291,19 +1258,209 @@ impl Interpreter {
         }
     }
     
+    // toString() and operator overloading; the book's Lox has neither
+    fn extensions(&self) -> bool {
+        self.options.extensions && self.options.language == LanguageLevel::Extended
+    }
+
+    // With the extensions, binary operators on instances call well-known methods:
+    // `+` → plus, `==`/`!=` → eq, and the comparisons → lt (`a > b` is `b.lt(a)`).
+    // Returns None when the receiver doesn't define the method, so the standard
+    // semantics (and type errors) apply.
+    fn overloaded_binary(&mut self, left: &LiteralValue, operator: &Token, right: &LiteralValue) -> Result<Option<LiteralValue>, RuntimeError> {
+        use crate::token::TokenType;
+        let (receiver, name, argument, negate) = match operator.token_type {
+            TokenType::PLUS => (left, "plus", right, false),
+            TokenType::EQUAL_EQUAL => (left, "eq", right, false),
+            TokenType::BANG_EQUAL => (left, "eq", right, true),
+            TokenType::LESS => (left, "lt", right, false),
+            TokenType::GREATER => (right, "lt", left, false),
+            TokenType::LESS_EQUAL => (right, "lt", left, true),
+            TokenType::GREATER_EQUAL => (left, "lt", right, true),
+            _ => return Ok(None),
+        };
+        let LiteralValue::Instance(instance) = receiver else {
+            return Ok(None);
+        };
+        let Some(method) = instance.class().find_method(name).map(|method| method.bind(receiver.clone())) else {
+            return Ok(None);
+        };
+
+        let result = self.call_value(&LiteralValue::Function(method), vec![argument.clone()], operator.line)?;
+        if name == "plus" {
+            return Ok(Some(result));
+        }
+        let truthy = self.is_truthy(&result);
+        Ok(Some(LiteralValue::BooleanLiteral(truthy != negate)))
+    }
+
+    fn visit_index(&self, object: &LiteralValue, index: &LiteralValue, line: usize) -> Result<LiteralValue, RuntimeError> {
+        match object {
+            LiteralValue::Array(array) => {
+                let position = self.expect_index(index, array.len(), line)?;
+                Ok(array.get(position).unwrap_or(LiteralValue::Nil))
+            }
+            LiteralValue::StringLiteral(s) => {
+                let position = self.expect_index(index, s.chars().count(), line)?;
+                Ok(LiteralValue::string(s.chars().nth(position).unwrap()))
+            }
+            // Missing keys read as nil, like undefined JSON fields
+            LiteralValue::Map(map) => {
+                let key = self.expect_key(index, line)?;
+                Ok(map.get(&key).unwrap_or(LiteralValue::Nil))
+            }
+            _ => Err(RuntimeError {
+                message: "Only arrays, maps and strings can be indexed.".to_string(),
+                line,
+            }),
+        }
+    }
+
+    // A new array or string of the elements from `start` up to but not including
+    // `end`. Negative bounds count from the end; a missing one is that end.
+    fn visit_slice(
+        &self,
+        object: &LiteralValue,
+        start: Option<&LiteralValue>,
+        end: Option<&LiteralValue>,
+        line: usize,
+    ) -> Result<LiteralValue, RuntimeError> {
+        let len = match object {
+            LiteralValue::Array(array) => array.len(),
+            LiteralValue::StringLiteral(s) => s.chars().count(),
+            _ => {
+                return Err(RuntimeError {
+                    message: "Only arrays and strings can be sliced.".to_string(),
+                    line,
+                })
+            }
+        };
+        let start = start.map_or(Ok(0), |start| self.expect_slice_bound(start, len, line))?;
+        let end = end.map_or(Ok(len), |end| self.expect_slice_bound(end, len, line))?;
+        if start > end {
+            return Err(RuntimeError {
+                message: format!("Slice start {} is after end {}.", start, end),
+                line,
+            });
+        }
+
+        Ok(match object {
+            LiteralValue::Array(array) => LiteralValue::Array(LoxArray::new(array.to_vec()[start..end].to_vec())),
+            LiteralValue::StringLiteral(s) => LiteralValue::string(s.chars().skip(start).take(end - start).collect::<String>()),
+            _ => unreachable!(),
+        })
+    }
+
+    // Validate a slice bound, which may be negative or equal to the length, and
+    // turn it into a position
+    fn expect_slice_bound(&self, bound: &LiteralValue, len: usize, line: usize) -> Result<usize, RuntimeError> {
+        let number = match bound {
+            LiteralValue::NumberLiteral(n) if n.fract() == 0.0 => *n,
+            _ => {
+                return Err(RuntimeError {
+                    message: "Index must be an integer.".to_string(),
+                    line,
+                })
+            }
+        };
+
+        let position = if number < 0.0 { number + len as f64 } else { number };
+        if position < 0.0 || position > len as f64 {
+            return Err(RuntimeError {
+                message: format!("Slice index {} out of bounds for length {}.", number as i64, len),
+                line,
+            });
+        }
+        Ok(position as usize)
+    }
+
+    // What a for-in loop goes over: array elements, map keys in order and string
+    // characters, taken up front so changing the collection in the body doesn't
+    // change the loop. An instance is iterable if it has an iter() method, which
+    // returns an iterator: an instance whose next() gives each value, then nil.
+    fn iteration(&mut self, collection: LiteralValue, line: usize) -> Result<Iteration, RuntimeError> {
+        let values = match collection {
+            LiteralValue::Array(array) => array.to_vec(),
+            LiteralValue::Map(map) => map.keys().into_iter().map(LiteralValue::string).collect(),
+            LiteralValue::StringLiteral(s) => s.chars().map(LiteralValue::string).collect(),
+            LiteralValue::Instance(instance) if instance.get("iter").is_some() => {
+                let iterator = self.call_value(&instance.get("iter").unwrap(), Vec::new(), line)?;
+                let next = match iterator {
+                    LiteralValue::Instance(iterator) => iterator.get("next"),
+                    _ => None,
+                };
+                return next.map(Iteration::Protocol).ok_or_else(|| RuntimeError {
+                    message: "iter() must return an instance with a next() method.".to_string(),
+                    line,
+                });
+            }
+            _ => {
+                return Err(RuntimeError {
+                    message: "Only arrays, maps, strings and instances with an iter() method can be looped over."
+                        .to_string(),
+                    line,
+                })
+            }
+        };
+        Ok(Iteration::Values(values.into_iter()))
+    }
+
+    // The loop's next value, or None when it is done
+    fn next_value(&mut self, iteration: &mut Iteration, line: usize) -> Result<Option<LiteralValue>, RuntimeError> {
+        match iteration {
+            Iteration::Values(values) => Ok(values.next()),
+            Iteration::Protocol(next) => match self.call_value(next, Vec::new(), line)? {
+                LiteralValue::Nil => Ok(None),
+                value => Ok(Some(value)),
+            },
+        }
+    }
+
+    fn expect_key(&self, key: &LiteralValue, line: usize) -> Result<String, RuntimeError> {
+        match key {
+            LiteralValue::StringLiteral(s) => Ok(s.to_string()),
+            _ => Err(RuntimeError {
+                message: "Map keys must be strings.".to_string(),
+                line,
+            }),
+        }
+    }
+
+    // Validate an index value against a container length
+    fn expect_index(&self, index: &LiteralValue, len: usize, line: usize) -> Result<usize, RuntimeError> {
+        let number = match index {
+            LiteralValue::NumberLiteral(n) if n.fract() == 0.0 => *n,
+            _ => {
+                return Err(RuntimeError {
+                    message: "Index must be an integer.".to_string(),
+                    line,
+                })
+            }
+        };
+
+        if number < 0.0 || number >= len as f64 {
+            return Err(RuntimeError {
+                message: format!("Index {} out of bounds for length {}.", number as i64, len),
+                line,
+            });
+        }
+        Ok(number as usize)
+    }
+
+    // An operand of a binary arithmetic or comparison operator
     fn expect_number_literal(&self, value: &LiteralValue, line: usize) -> Result<f64, RuntimeError> {
         if let LiteralValue::NumberLiteral(n) = value {
             Ok(*n)
         } else {
             Err(RuntimeError {
-                message: "Operand must be a number.".to_string(),
+                message: "Operands must be numbers.".to_string(),
                 line,
             })
         }
     }
 
     // Helper method to determine if a value is "truthy"
-    fn is_truthy(&self, value: &LiteralValue) -> bool {
+    pub fn is_truthy(&self, value: &LiteralValue) -> bool {
         match value {
             LiteralValue::Nil => false,
             LiteralValue::BooleanLiteral(b) => *b,
@@ -312,3 +1469,12 @@ impl Interpreter {
     }
     
 }
+
+// What an expression does that a read-only evaluation refuses, if anything
+fn side_effect(node: Node) -> Option<&'static str> {
+    match node {
+        Node::Expr(Expr::Assign { .. } | Expr::Set { .. } | Expr::SetIndex { .. }) => Some("assign"),
+        Node::Expr(Expr::Call { .. }) => Some("call"),
+        _ => node.children().into_iter().find_map(side_effect),
+    }
+}
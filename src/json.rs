@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+
+use crate::array::LoxArray;
+use crate::expr::LiteralValue;
+use crate::map::LoxMap;
+
+// Containers nested deeper than this are rejected (this also stops self-referencing
+// arrays/maps from recursing forever while stringifying)
+const MAX_DEPTH: usize = 512;
+
+// Parse JSON text into Lox values: objects become maps, arrays become arrays and
+// null becomes nil. Errors carry the byte position where parsing failed.
+pub fn parse(text: &str) -> Result<LiteralValue, String> {
+    let mut parser = JsonParser { text, position: 0 };
+    let value = parser.value(0)?;
+    parser.skip_whitespace();
+    if parser.position < text.len() {
+        return Err(parser.error("unexpected trailing characters"));
+    }
+    Ok(value)
+}
+
+// Serialize a Lox value as compact JSON. Functions have no JSON representation.
+pub fn stringify(value: &LiteralValue) -> Result<String, String> {
+    let mut out = String::new();
+    write_value(value, &mut out, 0)?;
+    Ok(out)
+}
+
+fn write_value(value: &LiteralValue, out: &mut String, depth: usize) -> Result<(), String> {
+    if depth > MAX_DEPTH {
+        return Err("value is nested too deeply (or contains itself)".to_string());
+    }
+
+    match value {
+        LiteralValue::Nil => out.push_str("null"),
+        LiteralValue::BooleanLiteral(b) => out.push_str(&b.to_string()),
+        LiteralValue::NumberLiteral(n) => {
+            if !n.is_finite() {
+                return Err(format!("cannot represent {} in JSON", n));
+            }
+            out.push_str(&n.to_string());
+        }
+        LiteralValue::StringLiteral(s) => write_string(s, out),
+        LiteralValue::Array(array) => {
+            out.push('[');
+            for (i, element) in array.to_vec().iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(element, out, depth + 1)?;
+            }
+            out.push(']');
+        }
+        LiteralValue::Map(map) => {
+            out.push('{');
+            for (i, (key, element)) in map.entries().iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(key, out);
+                out.push(':');
+                write_value(element, out, depth + 1)?;
+            }
+            out.push('}');
+        }
+        LiteralValue::Function(_) | LiteralValue::NativeFunction(_) | LiteralValue::HostMethod(_) => {
+            return Err("cannot convert a function to JSON".to_string());
+        }
+        LiteralValue::Class(_) | LiteralValue::Instance(_) => {
+            return Err("cannot convert a class or instance to JSON".to_string());
+        }
+    }
+    Ok(())
+}
+
+// s as a JSON string literal, quotes included
+pub fn quote(s: &str) -> String {
+    let mut out = String::new();
+    write_string(s, &mut out);
+    out
+}
+
+fn write_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+struct JsonParser<'a> {
+    text: &'a str,
+    position: usize, // Byte offset into text
+}
+
+impl JsonParser<'_> {
+    fn value(&mut self, depth: usize) -> Result<LiteralValue, String> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("value is nested too deeply"));
+        }
+
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.object(depth),
+            Some('[') => self.array(depth),
+            Some('"') => Ok(LiteralValue::string(self.string()?)),
+            Some('t') => self.keyword("true", LiteralValue::BooleanLiteral(true)),
+            Some('f') => self.keyword("false", LiteralValue::BooleanLiteral(false)),
+            Some('n') => self.keyword("null", LiteralValue::Nil),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c) => Err(self.error(&format!("unexpected character '{}'", c))),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<LiteralValue, String> {
+        self.expect('{')?;
+        let mut entries = BTreeMap::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.position += 1;
+            return Ok(LiteralValue::Map(LoxMap::new(entries)));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("expected string key"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            let value = self.value(depth + 1)?;
+            entries.insert(key, value);
+
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => continue,
+                Some('}') => break,
+                _ => return Err(self.error("expected ',' or '}' in object")),
+            }
+        }
+        Ok(LiteralValue::Map(LoxMap::new(entries)))
+    }
+
+    fn array(&mut self, depth: usize) -> Result<LiteralValue, String> {
+        self.expect('[')?;
+        let mut elements = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.position += 1;
+            return Ok(LiteralValue::Array(LoxArray::new(elements)));
+        }
+
+        loop {
+            elements.push(self.value(depth + 1)?);
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => continue,
+                Some(']') => break,
+                _ => return Err(self.error("expected ',' or ']' in array")),
+            }
+        }
+        Ok(LiteralValue::Array(LoxArray::new(elements)))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut value = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(value),
+                Some('\\') => {
+                    let escaped = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.unicode_escape()?,
+                        _ => return Err(self.error("invalid escape sequence")),
+                    };
+                    value.push(escaped);
+                }
+                Some(c) if (c as u32) < 0x20 => return Err(self.error("control character in string")),
+                Some(c) => value.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    // The part of a \uXXXX escape after the 'u', including surrogate pairs
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).ok_or_else(|| self.error("invalid unicode escape"));
+        }
+
+        if self.next() != Some('\\') || self.next() != Some('u') {
+            return Err(self.error("unpaired surrogate in unicode escape"));
+        }
+        let low = self.hex4()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err(self.error("invalid low surrogate in unicode escape"));
+        }
+        let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+        char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits = self.text.get(self.position..self.position + 4).ok_or_else(|| self.error("truncated unicode escape"))?;
+        let code = u32::from_str_radix(digits, 16).map_err(|_| self.error("invalid unicode escape"))?;
+        self.position += 4;
+        Ok(code)
+    }
+
+    fn number(&mut self) -> Result<LiteralValue, String> {
+        let start = self.position;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E') {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+        self.text[start..self.position]
+            .parse::<f64>()
+            .map(LiteralValue::NumberLiteral)
+            .map_err(|_| format!("invalid number at position {}", start))
+    }
+
+    fn keyword(&mut self, word: &str, value: LiteralValue) -> Result<LiteralValue, String> {
+        if self.text[self.position..].starts_with(word) {
+            self.position += word.len();
+            Ok(value)
+        } else {
+            Err(self.error("invalid literal"))
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.next() == Some(expected) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if matches!(c, ' ' | '\t' | '\n' | '\r') {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{} at position {}", message, self.position)
+    }
+}
@@ -0,0 +1,46 @@
+pub mod scanner;
+pub mod token;
+pub mod parser;
+pub mod resolver;
+pub mod expr;
+pub mod interpreter;
+pub mod stmt;
+pub mod ast;
+pub mod printer;
+pub mod function;
+pub mod class;
+pub mod natives;
+pub mod array;
+pub mod map;
+pub mod range;
+pub mod string_builder;
+pub mod json;
+pub mod format;
+pub mod output;
+pub mod input;
+pub mod profiler;
+pub mod diagnostic;
+pub mod codes;
+pub mod options;
+pub mod rng;
+pub mod suggest;
+pub mod stats;
+pub mod document;
+pub mod cache;
+pub mod loader;
+pub mod semantic;
+pub mod refactor;
+pub mod analysis;
+pub mod coverage;
+pub mod number;
+pub mod watch;
+pub mod trace;
+pub mod report;
+pub mod driver;
+pub mod manifest;
+pub mod value;
+pub mod reload;
+pub mod generate;
+pub mod crash;
+pub mod conformance;
+pub mod selftest;
@@ -0,0 +1,94 @@
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+
+use crate::diagnostic::{Diagnostic, Reporter, Stage, DEFAULT_MAX_ERRORS};
+use crate::options::LanguageLevel;
+use crate::parser::{Parser, DEFAULT_MAX_DEPTH};
+use crate::scanner::{ScanOptions, Scanner};
+use crate::stmt::Stmt;
+
+// Front-end settings shared by every file of a batch
+#[derive(Debug, Clone)]
+pub struct LoadOptions {
+    pub scan: ScanOptions,
+    pub language: LanguageLevel,
+    pub extensions: bool,
+    pub max_depth: usize,
+    pub max_errors: usize,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions {
+            scan: ScanOptions::default(),
+            language: LanguageLevel::default(),
+            extensions: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_errors: DEFAULT_MAX_ERRORS,
+        }
+    }
+}
+
+// One file's scan and parse. `statements` is None when the file could not be read or
+// has syntax errors, which are then in `diagnostics`.
+#[derive(Debug)]
+pub struct LoadedFile {
+    pub path: PathBuf,
+    pub statements: Option<Vec<Stmt>>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl LoadedFile {
+    // Reporter that attributes this file's diagnostics to it
+    pub fn reporter(&self, reporter: &Reporter) -> Reporter {
+        Reporter {
+            color: reporter.color,
+            errors_only: reporter.errors_only,
+            ..Reporter::new(reporter.format, &self.path.display().to_string())
+        }
+    }
+}
+
+// Scan and parse many files at once, for callers that load a batch of scripts before
+// interpreting them one by one. Files are split across one thread per core; results
+// come back in the order of `paths`.
+pub fn load_files(paths: &[PathBuf], options: &LoadOptions) -> Vec<LoadedFile> {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get()).min(paths.len()).max(1);
+    let chunk_size = paths.len().div_ceil(threads).max(1);
+    thread::scope(|scope| {
+        let workers: Vec<_> = paths
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(|path| load_file(path.clone(), options)).collect::<Vec<_>>()))
+            .collect();
+        workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+    })
+}
+
+pub fn load_file(path: PathBuf, options: &LoadOptions) -> LoadedFile {
+    let source = match fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(error) => {
+            let message = format!("Failed to read file {}: {}", path.display(), error);
+            return LoadedFile { path, statements: None, diagnostics: vec![Diagnostic::error(Stage::Scan, message, 0)] };
+        }
+    };
+
+    let mut scanner = Scanner::new(source);
+    scanner.set_options(options.scan.clone());
+    scanner.set_language(options.language);
+    scanner.scan_tokens();
+    if scanner.has_error() {
+        return LoadedFile { path, statements: None, diagnostics: scanner.diagnostics().to_vec() };
+    }
+
+    let mut parser = Parser::new(scanner.get_tokens().to_vec());
+    parser.set_language(options.language);
+    parser.set_extensions(options.extensions);
+    parser.set_max_depth(options.max_depth);
+    parser.set_max_errors(options.max_errors);
+    match parser.parse_statements() {
+        Ok(statements) => LoadedFile { path, statements: Some(statements), diagnostics: Vec::new() },
+        Err(diagnostics) => LoadedFile { path, statements: None, diagnostics },
+    }
+}
@@ -1,158 +1,753 @@
-mod scanner;
-mod token;
-mod parser;
-mod expr;
-mod interpreter;
-mod stmt;
-
 use std::env;
+use std::fmt;
 use std::fs;
-use std::io::{self, Write};
+use std::panic;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::time::Instant;
 use env_logger::Env;
-use scanner::Scanner;
-use parser::Parser;
-use interpreter::Interpreter;
+use codecrafters_interpreter::scanner::ScanOptions;
+use codecrafters_interpreter::parser::DEFAULT_MAX_DEPTH;
+use codecrafters_interpreter::resolver::{Lint, Lints, Resolver};
+use codecrafters_interpreter::interpreter::Interpreter;
+use codecrafters_interpreter::function::format_backtrace;
+use codecrafters_interpreter::options::{Capabilities, Capability, ExecOptions, LanguageLevel};
+use codecrafters_interpreter::profiler::Profiler;
+use codecrafters_interpreter::stats::Stats;
+use codecrafters_interpreter::cache::ParseCache;
+use codecrafters_interpreter::driver::{Failure, Pipeline, EXIT_CHECK_FAILED, EXIT_CONFIG, EXIT_INTERNAL, EXIT_IO, EXIT_NO_INPUT, EXIT_RUNTIME, EXIT_SYNTAX, EXIT_USAGE};
+use codecrafters_interpreter::semantic;
+use codecrafters_interpreter::refactor;
+use codecrafters_interpreter::printer;
+use codecrafters_interpreter::analysis;
+use codecrafters_interpreter::coverage;
+use codecrafters_interpreter::trace;
+use codecrafters_interpreter::manifest::{self, Manifest};
+use codecrafters_interpreter::generate::{self, GenerateOptions};
+use codecrafters_interpreter::crash;
+use codecrafters_interpreter::codes;
+use codecrafters_interpreter::conformance;
+use codecrafters_interpreter::selftest;
+use codecrafters_interpreter::number::NumberFormat;
+use codecrafters_interpreter::watch::{self, WatchEvent, Watchpoints};
+use codecrafters_interpreter::diagnostic::{ColorChoice, Diagnostic, DiagnosticFormat, Reporter, DEFAULT_MAX_ERRORS};
 
 fn main() {
     let env = Env::default().filter_or("RUST_LOG", "error");
     env_logger::init_from_env(env);
 
+    // A panic is a bug in the interpreter: report it as an internal error, not a raw panic
+    panic::set_hook(Box::new(|info| eprint!("{}", crash::report(info.payload(), info.location()))));
+    if panic::catch_unwind(run_command).is_err() {
+        process::exit(EXIT_INTERNAL);
+    }
+}
+
+fn run_command() {
     let args: Vec<String> = env::args().collect();
-    if args.len() < 3 {
-        writeln!(io::stderr(), "Usage: {} <command> <filename>", args[0]).unwrap();
+    // Everything after `--` is passed through to the script
+    let (cli_args, script_args) = match args.iter().position(|arg| arg == "--") {
+        Some(separator) => (&args[..separator], args[separator + 1..].to_vec()),
+        None => (&args[..], Vec::new()),
+    };
+    let (flags, mut positional): (Vec<&String>, Vec<&String>) =
+        cli_args.iter().skip(1).partition(|arg| arg.starts_with("--"));
+    // `run` without a file runs the project described by lox.toml in the current directory
+    let manifest = (positional.len() == 1 && positional[0] == "run").then(load_manifest);
+    let entry = manifest.as_ref().map(|manifest| manifest.entry.display().to_string());
+    positional.extend(entry.as_ref());
+    if positional.first().is_some_and(|command| *command == "generate") {
+        generate_program(&flags);
         return;
     }
+    if positional.first().is_some_and(|command| *command == "conformance") {
+        run_conformance_checklist();
+        return;
+    }
+    if positional.first().is_some_and(|command| *command == "selftest") {
+        run_selftest();
+        return;
+    }
+    if positional.len() < 2 {
+        eprintln!("Usage: {} <command> [flags] <filename> [-- <args>]", args[0]);
+        eprintln!("       {} run [flags] [-- <args>]   (the project in ./{})", args[0], manifest::FILE_NAME);
+        eprintln!("       {} generate [--seed=N] [--depth=D] [--statements=N] [--invalid] [--round-trip=N]", args[0]);
+        eprintln!("       {} explain <code>", args[0]);
+        eprintln!("       {} conformance   (check the output the CodeCrafters tester grades)", args[0]);
+        eprintln!("       {} selftest      (run the bundled programs of each stage)", args[0]);
+        process::exit(EXIT_USAGE);
+    }
 
-    let command = &args[1];
-    let filename = &args[2];
+    let command = positional[0];
+    let filename = positional[1];
+    // `--conformance`: exactly the book's language and output, as graded
+    let conformance = flags.iter().any(|flag| *flag == "--conformance");
+    if let Some(flag) = conformance.then(|| conformance::output_flag(&flags)).flatten() {
+        eprintln!("--conformance can't be combined with {}, which changes the output", flag);
+        process::exit(EXIT_USAGE);
+    }
+    // `tokenize` output is graded as is, so it defaults to the book's Lox
+    let default_language = if command == "tokenize" { LanguageLevel::Book } else { LanguageLevel::default() };
+    let language = if conformance { LanguageLevel::Book } else { language_from_flags(&flags, manifest.as_ref(), default_language) };
+    let options = ExecOptions {
+        script_args,
+        language,
+        strict_init: flags.iter().any(|flag| *flag == "--strict-init"),
+        deterministic: flags.iter().any(|flag| *flag == "--deterministic"),
+        strict_properties: flags.iter().any(|flag| *flag == "--strict-properties"),
+        extensions: flags.iter().any(|flag| *flag == "--extensions") || manifest.as_ref().is_some_and(|manifest| manifest.extensions),
+        capabilities: capabilities_from_flags(&flags, manifest.as_ref()),
+        profile: flags.iter().any(|flag| *flag == "--profile" || flag.starts_with("--profile-folded=")),
+        coverage: flags.iter().any(|flag| flag.starts_with("--coverage=") || *flag == "--coverage-report"),
+        record: flags.iter().any(|flag| flag.starts_with("--record=")),
+        display_depth: limit_from_flags(&flags, "--display-depth="),
+        display_width: limit_from_flags(&flags, "--display-width="),
+        number_format: NumberFormat { precision: limit_from_flags(&flags, "--precision=") },
+        ..Default::default()
+    };
+    let folded_path = flags.iter().find_map(|flag| flag.strip_prefix("--profile-folded="));
+
+    let format = match flags.iter().find_map(|flag| flag.strip_prefix("--diagnostics=")) {
+        Some("json") => DiagnosticFormat::Json,
+        Some("text") | None => DiagnosticFormat::Text,
+        Some(other) => {
+            eprintln!("Unknown diagnostics format: {}", other);
+            DiagnosticFormat::Text
+        }
+    };
+
+    let color = match flags.iter().find_map(|flag| flag.strip_prefix("--color=")) {
+        Some(name) => ColorChoice::from_name(name).unwrap_or_else(|| {
+            eprintln!("Unknown color choice: {}", name);
+            ColorChoice::Auto
+        }),
+        None => ColorChoice::Auto,
+    };
+
+    let has_flag = |name: &str| flags.iter().any(|flag| *flag == name);
+    let color = if conformance { ColorChoice::Never } else { color };
+    let mut pipeline = Pipeline::new(Reporter { color, errors_only: conformance, ..Reporter::new(format, filename) });
+    pipeline.scan_options = ScanOptions {
+        recover_strings: has_flag("--recover-strings"),
+        max_tokens: limit_from_flags(&flags, "--max-tokens="),
+        max_string_length: limit_from_flags(&flags, "--max-string="),
+        comments: has_flag("--comments"),
+    };
+    pipeline.language = options.language;
+    pipeline.extensions = options.extensions;
+    pipeline.max_depth = limit_from_flags(&flags, "--max-depth=").unwrap_or(DEFAULT_MAX_DEPTH);
+    pipeline.max_errors = limit_from_flags(&flags, "--max-errors=").unwrap_or(DEFAULT_MAX_ERRORS);
+    pipeline.lints = lints_from_flags(&flags);
+    pipeline.cache = flags.iter().find_map(|flag| flag.strip_prefix("--cache-dir=")).map(ParseCache::new);
+    let mut session = Session {
+        pipeline,
+        timings: has_flag("--timings"),
+        report_path: flags.iter().find_map(|flag| flag.strip_prefix("--report=")),
+    };
 
     match command.as_str() {
-        "tokenize" => tokenize_file(filename),
-        "parse" => parse_file(filename),
-        "evaluate" => evaluate_file(filename), 
-        "run" => run_file(filename), 
+        "tokenize" => tokenize_file(&mut session, filename),
+        "parse" => parse_file(&mut session, filename, has_flag("--program"), has_flag("--source-literals")),
+        "evaluate" => evaluate_file(&mut session, filename, options),
+        "fmt" => format_file(&mut session, filename, has_flag("--check")),
+        "analyze" => {
+            let (calls, symbols) = (has_flag("--calls"), has_flag("--symbols"));
+            let format = flags.iter().find_map(|flag| flag.strip_prefix("--format=")).unwrap_or("json");
+            // Without either flag, report both
+            let sections = (calls || !symbols, symbols || !calls);
+            analyze_file(&mut session, filename, sections, format)
+        }
+        "refs" | "def" => navigate(command, filename),
+        "replay" => replay_file(filename),
+        "explain" => explain_code(filename),
+        "rename" => rename_file(filename, &positional[2..], has_flag("--write")),
+        "highlight" => highlight_file(&mut session, filename, has_flag("--semantic")),
+        "run" => {
+            let flags = RunFlags {
+                folded_path,
+                check_only: has_flag("--check-only"),
+                backtrace: has_flag("--backtrace"),
+                stats: has_flag("--stats"),
+                coverage_path: flags.iter().find_map(|flag| flag.strip_prefix("--coverage=")),
+                coverage_report: has_flag("--coverage-report"),
+                watch: flags.iter().find_map(|flag| flag.strip_prefix("--watch=")),
+                record_path: flags.iter().find_map(|flag| flag.strip_prefix("--record=")),
+            };
+            run_files(&mut session, &positional[1..], options, &flags)
+        }
         _ => {
-            writeln!(io::stderr(), "Unknown command: {}", command).unwrap();
+            eprintln!("Unknown command: {}", command);
+            process::exit(EXIT_USAGE);
         }
     }
 }
 
-fn tokenize_file(filename: &str) {
-    let file_contents = fs::read_to_string(filename).unwrap_or_else(|_| {
-        writeln!(io::stderr(), "Failed to read file {}", filename).unwrap();
-        String::new()
-    });
+// `generate` prints a random program; the same flags always print the same program
+fn generate_program(flags: &[&String]) {
+    let defaults = GenerateOptions::default();
+    let options = GenerateOptions {
+        depth: limit_from_flags(flags, "--depth=").unwrap_or(defaults.depth),
+        statements: limit_from_flags(flags, "--statements=").unwrap_or(defaults.statements),
+        invalid: flags.iter().any(|flag| *flag == "--invalid"),
+    };
+    let seed = limit_from_flags(flags, "--seed=").unwrap_or(0) as u64;
+    match limit_from_flags(flags, "--round-trip=") {
+        Some(count) => check_round_trips(seed, count, &options),
+        None => print!("{}", generate::generate(seed, &options)),
+    }
+}
 
-    if !file_contents.is_empty() {
-        log::info!("Starting to scan tokens in file: {}", filename);
-        let mut scanner = Scanner::new(file_contents);
-        scanner.scan_tokens();
+// `conformance`: run this executable in `--conformance` mode on every check of the
+// checklist, reporting each failure
+fn run_conformance_checklist() {
+    let executable = current_executable();
+    let checks = conformance::CHECKLIST.iter().map(|check| (check, true));
+    let checks: Vec<_> = checks.chain(conformance::UNFLAGGED.iter().map(|check| (check, false))).collect();
+    let mut failures = 0;
+    for &(check, flagged) in &checks {
+        if let Err(message) = conformance::run(&executable, check, flagged) {
+            eprintln!("{} ({}): {}", check.stage, check.command, message);
+            failures += 1;
+        }
+    }
+    println!("{} checks, {} failed", checks.len(), failures);
+    if failures > 0 {
+        process::exit(EXIT_CHECK_FAILED);
+    }
+}
 
-        for token in scanner.get_tokens() {
-            println!("{}", token);
+// `selftest`: the fixture programs of each stage, in order, up to the first stage that
+// fails, as a local stand-in for the remote grader
+fn run_selftest() {
+    let executable = current_executable();
+    for (number, stage) in selftest::STAGES.iter().enumerate() {
+        let failure = stage.fixtures.iter().find_map(|fixture| conformance::run(&executable, fixture, true).err().map(|message| (fixture, message)));
+        if let Some((fixture, message)) = failure {
+            println!("FAIL {}", stage.name);
+            eprintln!("{}.lox ({}): {}", fixture.stage, fixture.command, message);
+            println!("First failing stage: {} of {}, {}", number + 1, selftest::STAGES.len(), stage.name);
+            process::exit(EXIT_CHECK_FAILED);
         }
+        println!("ok   {}", stage.name);
+    }
+    println!("All {} stages pass", selftest::STAGES.len());
+}
+
+// This interpreter, for commands that run it on test programs
+fn current_executable() -> PathBuf {
+    env::current_exe().unwrap_or_else(|error| {
+        eprintln!("Can't find the interpreter's executable: {}", error);
+        process::exit(EXIT_IO);
+    })
+}
 
-        if scanner.has_error() {
-            log::error!("Lexical error detected during scanning.");
-            process::exit(65);
-        }else {
-            process::exit(0); // No errors, exit with code 0
+// `generate --round-trip=N`: check that the N programs from the seed on survive being
+// printed as source and parsed again, see printer::check_round_trip
+fn check_round_trips(seed: u64, count: usize, options: &GenerateOptions) {
+    let mut pipeline = Pipeline::new(Reporter::new(DiagnosticFormat::Text, "<generated>"));
+    pipeline.extensions = true;
+    let mut failures = 0;
+    for seed in seed..seed + count as u64 {
+        // Programs broken on purpose (--invalid) have nothing to check
+        let Ok(statements) = pipeline.scan(generate::generate(seed, options)).and_then(|tokens| pipeline.parse(tokens)) else {
+            continue;
+        };
+        if let Err(message) = printer::check_round_trip(&statements) {
+            eprintln!("seed {}: {}", seed, message);
+            failures += 1;
         }
+    }
+    println!("{} programs checked, {} failed", count, failures);
+    if failures > 0 {
+        process::exit(EXIT_CHECK_FAILED);
+    }
+}
+
+// The project to run, exiting if lox.toml is missing or invalid
+fn load_manifest() -> Manifest {
+    let text = read_source(manifest::FILE_NAME);
+    Manifest::parse(&text, Path::new("")).unwrap_or_else(|message| {
+        eprintln!("{}:{}", manifest::FILE_NAME, message);
+        process::exit(EXIT_CONFIG);
+    })
+}
+
+// `--lang=book|extended`, or lox.toml's `lang`, or else `default`. Unlike other
+// unknown flag values, an unknown level is fatal: silently running extended Lox could
+// pass a book-only check.
+fn language_from_flags(flags: &[&String], manifest: Option<&Manifest>, default: LanguageLevel) -> LanguageLevel {
+    let language = match flags.iter().find_map(|flag| flag.strip_prefix("--lang=")) {
+        Some(name) => LanguageLevel::from_name(name).unwrap_or_else(|| {
+            eprintln!("Unknown language level: {} (expected book or extended)", name);
+            process::exit(EXIT_USAGE);
+        }),
+        None => manifest.map_or(default, |manifest| manifest.language),
+    };
+    if language == LanguageLevel::Book && flags.iter().any(|flag| *flag == "--extensions") {
+        eprintln!("--extensions needs --lang=extended");
+        process::exit(EXIT_USAGE);
+    }
+    language
+}
+
+// The CLI runs trusted scripts, so every capability is granted unless `--sandbox`
+// is given or the manifest sandboxes the project; the manifest's `allow` list and
+// `--allow-<name>` then re-enable individual capabilities.
+fn capabilities_from_flags(flags: &[&String], manifest: Option<&Manifest>) -> Capabilities {
+    let mut capabilities = if flags.iter().any(|flag| *flag == "--sandbox") || manifest.is_some_and(|manifest| manifest.sandbox) {
+        Capabilities::none()
     } else {
-        println!("EOF  null");
+        Capabilities::all()
+    };
+    for capability in manifest.iter().flat_map(|manifest| &manifest.allow) {
+        capabilities.set(*capability, true);
+    }
+
+    for flag in flags {
+        if let Some(name) = flag.strip_prefix("--allow-") {
+            match Capability::from_name(name) {
+                Some(capability) => capabilities.set(capability, true),
+                None => eprintln!("Unknown capability: {}", name),
+            }
+        }
     }
+    capabilities
 }
 
-fn parse_file(filename: &str) {
-    let file_contents = fs::read_to_string(filename).unwrap_or_else(|_| {
-        writeln!(io::stderr(), "Failed to read file {}", filename).unwrap();
-        String::new()
-    });
+// Numeric limit such as `--max-tokens=10000`; invalid values are reported and ignored
+fn limit_from_flags(flags: &[&String], prefix: &str) -> Option<usize> {
+    let value = flags.iter().find_map(|flag| flag.strip_prefix(prefix))?;
+    match value.parse() {
+        Ok(limit) => Some(limit),
+        Err(_) => {
+            eprintln!("Invalid limit: {}{}", prefix, value);
+            None
+        }
+    }
+}
 
-    if !file_contents.is_empty() {
-        let mut scanner = Scanner::new(file_contents);
-        let _tokens = scanner.scan_tokens();
+// `--warn=<name>` enables a lint (`--warn=all` enables every one) and
+// `--deny-warnings` turns the enabled lints into errors
+fn lints_from_flags(flags: &[&String]) -> Lints {
+    let mut lints = Lints {
+        deny_warnings: flags.iter().any(|flag| *flag == "--deny-warnings"),
+        ..Default::default()
+    };
+
+    for flag in flags {
+        match flag.strip_prefix("--warn=") {
+            Some("all") => Lint::ALL.into_iter().for_each(|lint| lints.set(lint, true)),
+            Some(name) => match Lint::from_name(name) {
+                Some(lint) => lints.set(lint, true),
+                None => eprintln!("Unknown lint: {}", name),
+            },
+            None => {}
+        }
+    }
+    lints
+}
+
+// What every command shares: the pipeline, and what to report when the command ends
+struct Session<'a> {
+    pipeline: Pipeline,
+    timings: bool,                // `--timings`: time per phase on stderr
+    report_path: Option<&'a str>, // `--report=<path>`, JSON summary of the command
+}
+
+impl Session<'_> {
+    // Run a phase of the pipeline, ending the command if it fails
+    fn phase<T>(&mut self, phase: impl FnOnce(&mut Pipeline) -> Result<T, Failure>) -> T {
+        match phase(&mut self.pipeline) {
+            Ok(value) => value,
+            Err(failure) => self.exit(&failure.diagnostics, failure.code),
+        }
+    }
 
-        if scanner.has_error() {
-            process::exit(65);
+    // Print the timings, write the report and emit the diagnostics of a command that
+    // ends with `code`
+    fn finish(&mut self, diagnostics: &[Diagnostic], code: i32) {
+        if self.timings {
+            eprint!("{}", self.pipeline.report.timings());
+        }
+        if let Some(path) = self.report_path {
+            let report = &mut self.pipeline.report;
+            report.exit_code = code;
+            report.diagnostics = diagnostics.to_vec();
+            if let Err(error) = fs::write(path, report.to_json(&self.pipeline.reporter) + "\n") {
+                eprintln!("Failed to write report {}: {}", path, error);
+            }
         }
+        self.pipeline.reporter.emit(diagnostics);
+    }
 
-        let mut parser = Parser::new(scanner.get_tokens().to_vec());
-        let expression = parser.parse_expression();
+    fn exit(&mut self, diagnostics: &[Diagnostic], code: i32) -> ! {
+        self.finish(diagnostics, code);
+        process::exit(code);
+    }
+}
+
+fn read_source(filename: &str) -> String {
+    fs::read_to_string(filename).unwrap_or_else(|error| {
+        eprintln!("Failed to read file {}: {}", filename, error);
+        process::exit(EXIT_NO_INPUT);
+    })
+}
 
-        if let Some(expr) = expression {
-            println!("{}", expr);  // Print the AST
+// An empty file prints the lone EOF token, as tokenize does, whatever the command
+fn print_empty_file() {
+    println!("EOF  null");
+}
+
+// Prints every token, even after a lexical error
+fn tokenize_file(session: &mut Session, filename: &str) {
+    let source = read_source(filename);
+    let (tokens, diagnostics) = session.pipeline.tokenize(source);
+    for token in &tokens {
+        println!("{}", token);
+    }
+    let code = if diagnostics.is_empty() { 0 } else { EXIT_SYNTAX };
+    session.exit(&diagnostics, code);
+}
+
+// Prints the AST of a single expression, or of every statement when the file is a
+// program (detected, or forced with `--program`). `--source-literals` prints literals
+// as written instead of in the book's canonical form.
+fn parse_file(session: &mut Session, filename: &str, program: bool, source_literals: bool) {
+    let source = read_source(filename);
+    if source.is_empty() {
+        return print_empty_file();
+    }
+
+    let print = |node: &dyn fmt::Display| {
+        if source_literals {
+            println!("{:#}", node);
         } else {
-            eprintln!("Parse error");
-            process::exit(65);
+            println!("{}", node);
+        }
+    };
+    let tokens = session.phase(|pipeline| pipeline.scan(source));
+    if program || session.pipeline.parser(tokens.clone()).looks_like_program() {
+        for stmt in session.phase(|pipeline| pipeline.parse(tokens)) {
+            print(&stmt);
         }
     } else {
-        println!("EOF  null");
+        print(&session.phase(|pipeline| pipeline.parse_expression(tokens)));
     }
+    session.finish(&[], 0);
 }
 
-fn evaluate_file(filename: &str) {
-    let file_contents = fs::read_to_string(filename).unwrap_or_else(|_| {
-        writeln!(io::stderr(), "Failed to read file {}", filename).unwrap();
-        String::new()
-    });
+// Print the program back as Lox source, laid out uniformly. Comments are dropped.
+// With `--check`, print nothing but fail if the printed source wouldn't parse back
+// to the same program.
+fn format_file(session: &mut Session, filename: &str, check: bool) {
+    let source = read_source(filename);
+    let tokens = session.phase(|pipeline| pipeline.scan(source));
+    let statements = session.phase(|pipeline| pipeline.parse(tokens));
+    if !check {
+        print!("{}", printer::to_source(&statements));
+    } else if let Err(message) = printer::check_round_trip(&statements) {
+        eprintln!("{}: {}", filename, message);
+        process::exit(EXIT_CHECK_FAILED);
+    }
+    session.finish(&[], 0);
+}
 
-    if !file_contents.is_empty() {
-        let mut scanner = Scanner::new(file_contents);
-        let _tokens = scanner.scan_tokens();
+fn evaluate_file(session: &mut Session, filename: &str, options: ExecOptions) {
+    let source = read_source(filename);
+    if source.is_empty() {
+        return print_empty_file();
+    }
+
+    let tokens = session.phase(|pipeline| pipeline.scan(source));
+    let expression = session.phase(|pipeline| pipeline.parse_expression(tokens));
+    let mut interpreter = Interpreter::with_options(options);
+    let text = session.phase(|pipeline| pipeline.evaluate(&mut interpreter, &expression));
+    println!("{}", text);
+    session.finish(&[], 0);
+}
+
+// Settings of the `run` command beyond the interpreter's ExecOptions and the pipeline
+struct RunFlags<'a> {
+    coverage_path: Option<&'a str>, // `--coverage=<path>`, LCOV output
+    coverage_report: bool, // `--coverage-report`: annotated source on stderr
+    folded_path: Option<&'a str>, // `--profile-folded=<path>`
+    // Stop after scanning, parsing and resolving: the exit code reports whether the
+    // program is well-formed (0) or not (65), for editor save hooks
+    check_only: bool,
+    backtrace: bool, // Follow a runtime error with the call stack it happened in
+    stats: bool,     // Print allocation counters and the run time to stderr
+    watch: Option<&'a str>, // `--watch=a,b`: report every read and write of these variables
+    record_path: Option<&'a str>, // `--record=<path>`, trace for `replay`
+}
+
+// `run a.lox b.lox ...` runs the files in order in one interpreter, so later files see
+// the globals of earlier ones. Each file's lint warnings are reported with the file
+// name once it has run; the first file that fails ends the command with its exit code.
+fn run_files(session: &mut Session, filenames: &[&String], options: ExecOptions, flags: &RunFlags) {
+    if filenames.len() > 1 && (flags.coverage_path.is_some() || flags.coverage_report || flags.record_path.is_some()) {
+        eprintln!("Coverage and trace recording take a single file.");
+        process::exit(EXIT_USAGE);
+    }
 
-        if scanner.has_error() {
-            process::exit(65); // Exit with 65 for syntax errors
+    session.pipeline.reporter.show_file = filenames.len() > 1;
+    let start = Instant::now();
+    let mut interpreter = Interpreter::with_options(options);
+    if let Some(names) = flags.watch {
+        interpreter.set_watchpoints(Watchpoints::new(names.split(',').map(str::to_string), Box::new(report_watch)));
+    }
+    let mut outcome = Ok(Vec::new());
+    for (i, filename) in filenames.iter().enumerate() {
+        session.pipeline.reporter.file = filename.to_string();
+        outcome = run_file(session, &mut interpreter, filename, flags);
+        match &outcome {
+            // The last file's warnings are reported with the outcome of the run
+            Ok(diagnostics) if i + 1 < filenames.len() => session.pipeline.reporter.emit(diagnostics),
+            Ok(_) => {}
+            Err(_) => break,
         }
+    }
 
-        let mut parser = Parser::new(scanner.get_tokens().to_vec());
-        let expression = parser.parse_expression();
+    if flags.stats {
+        eprint!("{}", Stats::snapshot().report(start.elapsed()));
+    }
+    if let Some(profiler) = interpreter.profiler() {
+        report_profile(profiler, flags.folded_path);
+    }
+    match outcome {
+        Ok(diagnostics) => session.finish(&diagnostics, 0),
+        Err(diagnostics) => session.exit(&diagnostics, EXIT_RUNTIME),
+    }
+}
 
-        if let Some(expr) = expression {
-            let mut interpreter = Interpreter::new();
-            match interpreter.evaluate(&expr) {
-                Ok(literal_value) => {
-                    // Convert LiteralValue to string for output
-                    println!("{}", interpreter.literal_to_string(literal_value));
-                }
-                Err(err) => {
-                    eprintln!("{}", err);
-                    process::exit(70); // Exit with 70 for runtime errors
-                }
+// Run one file: its lint warnings, or them and the runtime error it stopped with.
+// Syntax errors end the command right away.
+fn run_file(session: &mut Session, interpreter: &mut Interpreter, filename: &str, flags: &RunFlags) -> Result<Vec<Diagnostic>, Vec<Diagnostic>> {
+    let source = read_source(filename);
+    if source.is_empty() {
+        print_empty_file();
+        return Ok(Vec::new());
+    }
+
+    let statements = session.phase(|pipeline| pipeline.load(&source));
+    let mut diagnostics = session.phase(|pipeline| pipeline.resolve(&statements));
+    if flags.check_only {
+        return Ok(diagnostics);
+    }
+
+    let executable_lines = coverage::executable_lines(&statements);
+    let result = session.pipeline.execute(interpreter, statements);
+    if let Some(coverage) = interpreter.coverage() {
+        if flags.coverage_report {
+            eprint!("{}", coverage.annotate(&source, &executable_lines));
+        }
+        if let Some(path) = flags.coverage_path {
+            if let Err(error) = fs::write(path, coverage.lcov(filename, &executable_lines)) {
+                eprintln!("Failed to write coverage {}: {}", path, error);
             }
-        } else {
-            eprintln!("Parse error");
-            process::exit(65); // Exit with 65 for syntax errors
         }
+    }
+    if let (Some(trace), Some(path)) = (interpreter.trace(), flags.record_path) {
+        if let Err(error) = fs::write(path, trace::encode(trace, &source)) {
+            eprintln!("Failed to write trace {}: {}", path, error);
+        }
+    }
+    match result {
+        Ok(()) => Ok(diagnostics),
+        Err(error) => {
+            if flags.backtrace {
+                eprint!("{}", format_backtrace(interpreter.backtrace(), error.line));
+            }
+            diagnostics.push(error.to_diagnostic());
+            Err(diagnostics)
+        }
+    }
+}
+
+// Print every token with its highlighting class, one per line as `line:column class
+// lexeme`. With `--semantic` names are classified by what they resolve to, and
+// declarations get a `.declaration` suffix.
+fn highlight_file(session: &mut Session, filename: &str, semantic: bool) {
+    let source = read_source(filename);
+    let tokens = session.phase(|pipeline| pipeline.scan(source));
+    let references = if semantic {
+        let statements = session.phase(|pipeline| pipeline.parse(tokens.clone()));
+        let mut resolver = Resolver::new();
+        resolver.resolve(&statements);
+        resolver.references()
     } else {
-        println!("EOF  null");
+        Vec::new()
+    };
+    for highlight in semantic::highlight(&tokens, &references) {
+        let suffix = if highlight.declaration { ".declaration" } else { "" };
+        let token = &highlight.token;
+        println!("{}:{} {}{} {}", token.line, token.column, highlight.class, suffix, token.lexeme);
+    }
+    session.finish(&[], 0);
+}
+
+// Print the declaration outline and/or the static call graph as JSON or Graphviz dot
+fn analyze_file(session: &mut Session, filename: &str, (calls, symbols): (bool, bool), format: &str) {
+    let source = read_source(filename);
+    let statements = session.phase(|pipeline| pipeline.load(&source));
+    let outline = symbols.then(|| analysis::outline(&statements));
+    let graph = calls.then(|| analysis::call_graph(&statements));
+    match format {
+        "json" => println!("{}", analysis::to_json(outline.as_deref(), graph.as_deref())),
+        "dot" => print!("{}", analysis::to_dot(outline.as_deref(), graph.as_deref())),
+        other => {
+            eprintln!("Unknown analysis format: {}", other);
+            process::exit(EXIT_USAGE);
+        }
+    }
+    session.finish(&[], 0);
+}
+
+// `explain E0012` describes a diagnostic code, as shown in JSON diagnostics
+fn explain_code(code: &str) {
+    match codes::lookup(code) {
+        Some(entry) => print!("{}", codes::explain(entry)),
+        None => {
+            eprintln!("Unknown diagnostic code: {}", code);
+            process::exit(EXIT_USAGE);
+        }
     }
 }
 
-fn run_file(filename: &str) {
-    let file_contents = fs::read_to_string(filename).unwrap_or_else(|_| {
-        writeln!(io::stderr(), "Failed to read file {}", filename).unwrap();
-        String::new()
+// `refs <file>:<line>:<column>` prints every use of the name at that position and
+// `def <file>:<line>:<column>` where it is declared, one `file:line:column` per line
+fn navigate(command: &str, location: &str) {
+    let mut parts = location.rsplitn(3, ':');
+    let (column, line, filename) = (parts.next(), parts.next(), parts.next());
+    let (filename, line, column) = match (filename, line.map(str::parse::<usize>), column.map(str::parse::<usize>)) {
+        (Some(filename), Some(Ok(line)), Some(Ok(column))) => (filename, line, column),
+        _ => {
+            eprintln!("Usage: {} <filename>:<line>:<column>", command);
+            process::exit(EXIT_USAGE);
+        }
+    };
+    let file_contents = read_source(filename);
+
+    let references = refactor::resolve_source(&file_contents).unwrap_or_else(|diagnostics| {
+        Reporter::new(DiagnosticFormat::Text, filename).emit(&diagnostics);
+        process::exit(EXIT_SYNTAX);
     });
+    let Some(target) = semantic::reference_at(&references, line, column) else {
+        eprintln!("No name at {}:{}.", line, column);
+        process::exit(EXIT_SYNTAX);
+    };
+    let found = if command == "refs" {
+        semantic::find_references(&references, target)
+    } else {
+        semantic::find_definitions(&references, target)
+    };
+    if found.is_empty() {
+        eprintln!("No declaration of '{}' in this file.", target.name.lexeme);
+        process::exit(EXIT_SYNTAX);
+    }
+    for reference in found {
+        println!("{}:{}:{}", filename, reference.name.line, reference.name.column);
+    }
+}
+
+// `rename <file> <line> <column> <new name>`: print the file with the name at that
+// position renamed everywhere it is used, or rewrite the file with `--write`
+fn rename_file(filename: &str, arguments: &[&String], write: bool) {
+    let (line, column, new_name) = match arguments {
+        [line, column, new_name] => match (line.parse(), column.parse()) {
+            (Ok(line), Ok(column)) => (line, column, new_name.as_str()),
+            _ => {
+                eprintln!("Invalid position: {}:{}", line, column);
+                process::exit(EXIT_USAGE);
+            }
+        },
+        _ => {
+            eprintln!("Usage: rename [--write] <filename> <line> <column> <new name>");
+            process::exit(EXIT_USAGE);
+        }
+    };
+    let file_contents = read_source(filename);
+
+    match refactor::rename(&file_contents, line, column, new_name) {
+        Ok(edits) => {
+            let renamed = refactor::apply(&file_contents, &edits);
+            if !write {
+                print!("{}", renamed);
+            } else if let Err(error) = fs::write(filename, renamed) {
+                eprintln!("Failed to write file {}: {}", filename, error);
+                process::exit(EXIT_IO);
+            }
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(EXIT_SYNTAX);
+        }
+    }
+}
 
-    if !file_contents.is_empty() {
-        let mut scanner = Scanner::new(file_contents);
-        let _tokens = scanner.scan_tokens();
+// Step through a trace from `run --record=<path>`, one command per line on stdin:
+// `n [count]` (or an empty line) forward, `b [count]` back, `g <step>` to jump, `vars`
+// for every variable written so far, `q` to quit
+fn replay_file(filename: &str) {
+    let Some((source, trace)) = fs::read(filename).ok().and_then(|bytes| trace::decode(&bytes)) else {
+        eprintln!("Failed to read trace {}", filename);
+        process::exit(EXIT_NO_INPUT);
+    };
+    if trace.steps.is_empty() {
+        println!("Empty trace.");
+        return;
+    }
 
-        if scanner.has_error() {
-            process::exit(65);
+    let lines: Vec<&str> = source.lines().collect();
+    let last = trace.steps.len() - 1;
+    let mut position = 0;
+    let show = |position: usize| {
+        let step = &trace.steps[position];
+        println!("step {}/{}  line {}  depth {}", position + 1, trace.steps.len(), step.line, step.depth);
+        if let Some(text) = step.line.checked_sub(1).and_then(|index| lines.get(index)) {
+            println!("{:>6} | {}", step.line, text.trim());
         }
+        for (name, value) in &step.writes {
+            println!("    {} = {}", name, value);
+        }
+    };
+    show(position);
 
-        let mut parser = Parser::new(scanner.get_tokens().to_vec());
-        let statements = parser.parse_statements().unwrap();
+    for command in std::io::stdin().lines().map_while(Result::ok) {
+        let mut words = command.split_whitespace();
+        let command = words.next().unwrap_or("n");
+        let count: Option<usize> = words.next().and_then(|word| word.parse().ok());
+        match command {
+            "n" | "next" => position = (position + count.unwrap_or(1)).min(last),
+            "b" | "back" => position = position.saturating_sub(count.unwrap_or(1)),
+            "g" | "goto" => position = count.unwrap_or(1).clamp(1, last + 1) - 1,
+            "vars" => {
+                for (name, value) in trace.state_at(position) {
+                    println!("    {} = {}", name, value);
+                }
+                continue;
+            }
+            "q" | "quit" => return,
+            _ => {
+                println!("Commands: n [count], b [count], g <step>, vars, q");
+                continue;
+            }
+        }
+        show(position);
+    }
+}
+
+// `[line 4] write count = 2 (was 1)` on stderr
+fn report_watch(event: &WatchEvent) {
+    let previous = event.previous.as_ref().map_or(String::new(), |previous| format!(" (was {})", watch::describe(previous)));
+    eprintln!("[line {}] {} {} = {}{}", event.line, event.access.name(), event.name, watch::describe(&event.value), previous);
+}
 
-        let mut interpreter = Interpreter::new();
-        if let Err(error) = interpreter.interpret(statements) {
-            eprintln!("{}", error);
-            process::exit(70); // Runtime error
+// Print the profile to stderr, and the folded stacks to a file when requested
+fn report_profile(profiler: &Profiler, folded_path: Option<&str>) {
+    eprint!("{}", profiler.report());
+    if let Some(path) = folded_path {
+        if let Err(error) = fs::write(path, profiler.folded_stacks()) {
+            eprintln!("Failed to write profile {}: {}", path, error);
         }
-    } else {
-        println!("EOF null");
     }
 }
\ No newline at end of file
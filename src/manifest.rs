@@ -0,0 +1,211 @@
+use std::path::{Path, PathBuf};
+
+use crate::options::{Capability, LanguageLevel};
+
+pub const FILE_NAME: &str = "lox.toml";
+
+// A project's settings, read from lox.toml so that `run` needs no arguments:
+//
+//     entry = "src/main.lox"
+//     include = ["lib"]   # Where imports will be looked up
+//     lang = "extended"  # Or "book", see LanguageLevel
+//     extensions = true
+//     sandbox = true      # Grant no capabilities...
+//     allow = ["args"]    # ...but these
+//
+// Paths are relative to the manifest. Only this subset of TOML is read: top-level keys
+// with string, boolean or string-array values, and `#` comments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    pub entry: PathBuf,
+    // Kept for imports; the language has no import statement yet
+    pub include: Vec<PathBuf>,
+    pub language: LanguageLevel,
+    pub extensions: bool,
+    pub sandbox: bool,
+    pub allow: Vec<Capability>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    String(String),
+    Boolean(bool),
+    Array(Vec<String>),
+}
+
+impl Manifest {
+    // `dir` is the manifest's directory. Errors start with the line they are on:
+    // "3: unknown key 'entri'"
+    pub fn parse(text: &str, dir: &Path) -> Result<Manifest, String> {
+        let mut entry = None;
+        let mut manifest = Manifest {
+            entry: PathBuf::new(),
+            include: Vec::new(),
+            language: LanguageLevel::default(),
+            extensions: false,
+            sandbox: false,
+            allow: Vec::new(),
+        };
+
+        let mut seen = Vec::new();
+        let mut parser = TomlParser { text, position: 0 };
+        while let Some((key, line)) = parser.key()? {
+            let error = |message: String| format!("{}: {}", line, message);
+            if seen.contains(&key) {
+                return Err(error(format!("duplicate key '{}'", key)));
+            }
+            let value = parser.value().map_err(error)?;
+            match (key.as_str(), value) {
+                ("entry", Value::String(path)) => entry = Some(dir.join(path)),
+                ("include", Value::Array(paths)) => manifest.include = paths.iter().map(|path| dir.join(path)).collect(),
+                ("lang", Value::String(name)) => {
+                    manifest.language = LanguageLevel::from_name(&name).ok_or_else(|| error(format!("unknown language level '{}'", name)))?;
+                }
+                ("extensions", Value::Boolean(extensions)) => manifest.extensions = extensions,
+                ("sandbox", Value::Boolean(sandbox)) => manifest.sandbox = sandbox,
+                ("allow", Value::Array(names)) => {
+                    for name in names {
+                        let capability = Capability::from_name(&name).ok_or_else(|| error(format!("unknown capability '{}'", name)))?;
+                        manifest.allow.push(capability);
+                    }
+                }
+                ("entry" | "include" | "lang" | "extensions" | "sandbox" | "allow", _) => {
+                    return Err(error(format!("wrong type of value for '{}'", key)));
+                }
+                _ => return Err(error(format!("unknown key '{}'", key))),
+            }
+            parser.end_of_line().map_err(error)?;
+            seen.push(key);
+        }
+
+        manifest.entry = entry.ok_or("missing 'entry', the file to run")?;
+        Ok(manifest)
+    }
+}
+
+struct TomlParser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl TomlParser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn line(&self) -> usize {
+        self.text[..self.position].matches('\n').count() + 1
+    }
+
+    // Spaces and tabs, plus newlines and comments when `newlines` is set
+    fn skip_blank(&mut self, newlines: bool) {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' => self.position += 1,
+                '\n' | '\r' if newlines => self.position += 1,
+                '#' if newlines => {
+                    let rest = &self.text[self.position..];
+                    self.position += rest.find('\n').unwrap_or(rest.len());
+                }
+                _ => break,
+            }
+        }
+    }
+
+    // The next `key =` and its line, or None at the end of the file
+    fn key(&mut self) -> Result<Option<(String, usize)>, String> {
+        self.skip_blank(true);
+        if self.peek().is_none() {
+            return Ok(None);
+        }
+        let line = self.line();
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            self.position += 1;
+        }
+        if self.peek() == Some('[') {
+            return Err(format!("{}: tables are not supported", line));
+        }
+        let key = self.text[start..self.position].to_string();
+        self.skip_blank(false);
+        if key.is_empty() || self.peek() != Some('=') {
+            return Err(format!("{}: expected 'key = value'", line));
+        }
+        self.position += 1;
+        self.skip_blank(false);
+        Ok(Some((key, line)))
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') => self.string().map(Value::String),
+            Some('[') => self.array().map(Value::Array),
+            _ => {
+                let rest = &self.text[self.position..];
+                for (word, value) in [("true", true), ("false", false)] {
+                    if rest.starts_with(word) {
+                        self.position += word.len();
+                        return Ok(Value::Boolean(value));
+                    }
+                }
+                Err("expected a string, a boolean or an array of strings".to_string())
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.position += 1; // Opening quote
+        let mut value = String::new();
+        loop {
+            let c = self.peek().ok_or("unterminated string")?;
+            self.position += c.len_utf8();
+            match c {
+                '"' => return Ok(value),
+                '\n' => return Err("unterminated string".to_string()),
+                '\\' => {
+                    let escaped = self.peek().ok_or("unterminated string")?;
+                    self.position += escaped.len_utf8();
+                    value.push(match escaped {
+                        '"' => '"',
+                        '\\' => '\\',
+                        'n' => '\n',
+                        't' => '\t',
+                        other => return Err(format!("unknown escape '\\{}'", other)),
+                    });
+                }
+                c => value.push(c),
+            }
+        }
+    }
+
+    // `["a", "b"]`, possibly over several lines and with a trailing comma
+    fn array(&mut self) -> Result<Vec<String>, String> {
+        self.position += 1; // [
+        let mut items = Vec::new();
+        loop {
+            self.skip_blank(true);
+            match self.peek() {
+                Some(']') => break,
+                Some('"') => items.push(self.string()?),
+                _ => return Err("expected a string in the array".to_string()),
+            }
+            self.skip_blank(true);
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(']') => break,
+                _ => return Err("expected ',' or ']' in the array".to_string()),
+            }
+        }
+        self.position += 1; // ]
+        Ok(items)
+    }
+
+    // Only a comment may follow a value on its line
+    fn end_of_line(&mut self) -> Result<(), String> {
+        self.skip_blank(false);
+        match self.peek() {
+            None | Some('\n' | '\r' | '#') => Ok(()),
+            Some(_) => Err("unexpected text after the value".to_string()),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::expr::LiteralValue;
+use crate::stats;
+
+// A string-keyed map shared by reference. Keys are kept sorted so printing and
+// serialization are deterministic.
+#[derive(Clone)]
+pub struct LoxMap(Arc<Mutex<BTreeMap<String, LiteralValue>>>);
+
+impl LoxMap {
+    pub fn new(entries: BTreeMap<String, LiteralValue>) -> Self {
+        stats::object_created();
+        LoxMap(Arc::new(Mutex::new(entries)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Identity of the shared storage, the same for every copy of this map
+    pub fn id(&self) -> usize {
+        Arc::as_ptr(&self.0) as *const () as usize
+    }
+
+    pub fn get(&self, key: &str) -> Option<LiteralValue> {
+        self.0.lock().unwrap().get(key).cloned()
+    }
+
+    pub fn set(&self, key: String, value: LiteralValue) {
+        self.0.lock().unwrap().insert(key, value);
+    }
+
+    pub fn keys(&self) -> Vec<String> {
+        self.0.lock().unwrap().keys().cloned().collect()
+    }
+
+    // Snapshot of the entries, in key order; see LoxArray::to_vec for why
+    pub fn entries(&self) -> Vec<(String, LiteralValue)> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+}
+
+// Maps are reference values: equal only when they are the same map
+impl PartialEq for LoxMap {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+// The last copy going away frees the shared storage
+impl Drop for LoxMap {
+    fn drop(&mut self) {
+        if Arc::strong_count(&self.0) == 1 {
+            stats::object_freed();
+        }
+    }
+}
+
+impl fmt::Debug for LoxMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<map len {}>", self.len())
+    }
+}
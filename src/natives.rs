@@ -0,0 +1,417 @@
+use std::fs;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::array::LoxArray;
+use crate::expr::LiteralValue;
+use crate::json;
+use crate::number;
+use crate::format::{self, FormatError};
+use crate::function::{Arity, Callable, NativeContext, NativeFunction};
+use crate::interpreter::{Environment, RuntimeError};
+use crate::options::{Capability, LanguageLevel};
+use crate::range;
+use crate::string_builder;
+
+// Every native function available to Lox scripts
+const NATIVES: &[NativeFunction] = &[
+    NativeFunction { name: "clock", arity: Arity::Fixed(0), function: clock },
+    NativeFunction { name: "len", arity: Arity::Fixed(1), function: len },
+    NativeFunction { name: "push", arity: Arity::Fixed(2), function: push },
+    NativeFunction { name: "sort", arity: Arity::Fixed(2), function: sort },
+    NativeFunction { name: "map", arity: Arity::Fixed(2), function: map },
+    NativeFunction { name: "filter", arity: Arity::Fixed(2), function: filter },
+    NativeFunction { name: "reduce", arity: Arity::Fixed(3), function: reduce },
+    NativeFunction { name: "number", arity: Arity::Fixed(1), function: number },
+    NativeFunction { name: "string", arity: Arity::Fixed(1), function: string },
+    NativeFunction { name: "type", arity: Arity::Fixed(1), function: type_of },
+    NativeFunction { name: "arity", arity: Arity::Fixed(1), function: arity },
+    NativeFunction { name: "random", arity: Arity::Fixed(0), function: random },
+    NativeFunction { name: "randomInt", arity: Arity::Fixed(2), function: random_int },
+    NativeFunction { name: "setSeed", arity: Arity::Fixed(1), function: set_seed },
+    NativeFunction { name: "args", arity: Arity::Fixed(0), function: args },
+    NativeFunction { name: "env", arity: Arity::Fixed(1), function: env },
+    NativeFunction { name: "readFile", arity: Arity::Fixed(1), function: read_file },
+    NativeFunction { name: "writeFile", arity: Arity::Fixed(2), function: write_file },
+    NativeFunction { name: "sleep", arity: Arity::Fixed(1), function: sleep },
+    NativeFunction { name: "range", arity: Arity::AtLeast(2), function: range::range },
+    NativeFunction { name: "keys", arity: Arity::Fixed(1), function: keys },
+    NativeFunction { name: "fields", arity: Arity::Fixed(1), function: fields },
+    NativeFunction { name: "stringBuilder", arity: Arity::Fixed(0), function: string_builder::string_builder },
+    NativeFunction { name: "jsonParse", arity: Arity::Fixed(1), function: json_parse },
+    NativeFunction { name: "jsonStringify", arity: Arity::Fixed(1), function: json_stringify },
+    NativeFunction { name: "format", arity: Arity::AtLeast(1), function: format },
+    NativeFunction { name: "printf", arity: Arity::AtLeast(1), function: printf },
+    NativeFunction { name: "write", arity: Arity::Fixed(1), function: write },
+    NativeFunction { name: "eprint", arity: Arity::Fixed(1), function: eprint },
+    NativeFunction { name: "readChar", arity: Arity::Fixed(0), function: read_char },
+    NativeFunction { name: "peekChar", arity: Arity::Fixed(0), function: peek_char },
+    NativeFunction { name: "atEof", arity: Arity::Fixed(0), function: at_eof },
+];
+
+// Define the natives of a language level in the given (global) environment. The
+// book's Lox has only clock().
+pub fn define_natives(environment: &mut Environment, language: LanguageLevel) {
+    let natives = NATIVES.iter().filter(|native| language == LanguageLevel::Extended || native.name == "clock");
+    for native in natives {
+        environment.define(native.name.to_string(), LiteralValue::NativeFunction(native.clone()));
+    }
+}
+
+// clock() -> seconds since the Unix epoch, or on the virtual clock in deterministic mode
+fn clock(context: &mut NativeContext, _arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    if context.interpreter().options().deterministic {
+        return Ok(LiteralValue::NumberLiteral(context.interpreter().tick_virtual_clock()));
+    }
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| context.error("System clock is before the Unix epoch."))?;
+    Ok(LiteralValue::NumberLiteral(elapsed.as_secs_f64()))
+}
+
+// len(array | map | string) -> number of elements/entries/characters
+fn len(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    match &arguments[0] {
+        LiteralValue::Array(array) => Ok(LiteralValue::NumberLiteral(array.len() as f64)),
+        LiteralValue::Map(map) => Ok(LiteralValue::NumberLiteral(map.len() as f64)),
+        LiteralValue::StringLiteral(s) => Ok(LiteralValue::NumberLiteral(s.chars().count() as f64)),
+        _ => Err(context.error("len() expects an array, a map or a string.")),
+    }
+}
+
+// push(array, value) -> the array, with value appended
+fn push(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let array = expect_array(context, &arguments[0], "push")?;
+    array.push(arguments[1].clone());
+    Ok(arguments[0].clone())
+}
+
+// sort(array, comparator) -> new array, stably sorted. comparator(a, b) returns a
+// number that is negative when a comes first, zero when equal, positive otherwise.
+fn sort(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let array = expect_array(context, &arguments[0], "sort")?;
+    let comparator = expect_callable(context, &arguments[1], "sort")?;
+    let sorted = merge_sort(context, &comparator, array.to_vec())?;
+    Ok(LiteralValue::Array(LoxArray::new(sorted)))
+}
+
+// map(array, function) -> new array of function(element)
+fn map(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let array = expect_array(context, &arguments[0], "map")?;
+    let function = expect_callable(context, &arguments[1], "map")?;
+    let mut mapped = Vec::with_capacity(array.len());
+    for element in array.to_vec() {
+        mapped.push(context.call(&function, vec![element])?);
+    }
+    Ok(LiteralValue::Array(LoxArray::new(mapped)))
+}
+
+// filter(array, predicate) -> new array of the elements for which predicate is truthy
+fn filter(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let array = expect_array(context, &arguments[0], "filter")?;
+    let predicate = expect_callable(context, &arguments[1], "filter")?;
+    let mut kept = Vec::new();
+    for element in array.to_vec() {
+        let keep = context.call(&predicate, vec![element.clone()])?;
+        if context.interpreter().is_truthy(&keep) {
+            kept.push(element);
+        }
+    }
+    Ok(LiteralValue::Array(LoxArray::new(kept)))
+}
+
+// reduce(array, function, initial) -> function(...function(initial, a[0])..., a[n-1])
+fn reduce(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let array = expect_array(context, &arguments[0], "reduce")?;
+    let function = expect_callable(context, &arguments[1], "reduce")?;
+    let mut accumulator = arguments[2].clone();
+    for element in array.to_vec() {
+        accumulator = context.call(&function, vec![accumulator, element])?;
+    }
+    Ok(accumulator)
+}
+
+// number(s) -> the number written in s; nil (or a runtime error under
+// ExecOptions::strict_number) if s is not a valid Lox number
+fn number(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let text = match &arguments[0] {
+        LiteralValue::NumberLiteral(n) => return Ok(LiteralValue::NumberLiteral(*n)),
+        LiteralValue::StringLiteral(s) => s.trim(),
+        _ => return Err(context.error("number() expects a string.")),
+    };
+
+    match number::parse(text) {
+        Some(n) => Ok(LiteralValue::NumberLiteral(n)),
+        None if context.interpreter().options().strict_number => {
+            Err(context.error(format!("Cannot convert '{}' to a number.", text)))
+        }
+        None => Ok(LiteralValue::Nil),
+    }
+}
+
+// string(v) -> v formatted exactly as print would show it
+fn string(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let line = context.line();
+    let text = context.interpreter().literal_to_string(arguments[0].clone(), line)?;
+    Ok(LiteralValue::string(text))
+}
+
+// type(v) -> name of the value's runtime type
+fn type_of(_context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    Ok(LiteralValue::string(arguments[0].type_name()))
+}
+
+// arity(f) -> number of arguments f expects. Variadic natives that need at least n
+// arguments report -(n + 1), so -1 means "any number".
+fn arity(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let arity = match &arguments[0] {
+        LiteralValue::Function(function) => function.arity(),
+        LiteralValue::NativeFunction(native) => native.arity,
+        LiteralValue::HostMethod(method) => method.arity,
+        LiteralValue::Class(class) => class.arity(),
+        _ => return Err(context.error("arity() expects a function or a class.")),
+    };
+    let count = match arity {
+        Arity::Fixed(n) => n as f64,
+        Arity::AtLeast(n) => -(n as f64) - 1.0,
+    };
+    Ok(LiteralValue::NumberLiteral(count))
+}
+
+// random() -> uniform number in [0, 1)
+fn random(context: &mut NativeContext, _arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    Ok(LiteralValue::NumberLiteral(context.interpreter().rng().next_f64()))
+}
+
+// randomInt(a, b) -> uniform integer in [a, b], both inclusive
+fn random_int(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let low = expect_integer(context, &arguments[0], "randomInt")?;
+    let high = expect_integer(context, &arguments[1], "randomInt")?;
+    if low > high {
+        return Err(context.error("randomInt() expects its first argument to be at most its second."));
+    }
+    let value = context.interpreter().rng().next_in_range(low, high);
+    Ok(LiteralValue::NumberLiteral(value as f64))
+}
+
+// setSeed(n) -> nil; restarts the random sequence so runs are reproducible
+fn set_seed(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let seed = expect_integer(context, &arguments[0], "setSeed")?;
+    context.interpreter().rng().set_seed(seed as u64);
+    Ok(LiteralValue::Nil)
+}
+
+// args() -> array of the strings passed to the script
+fn args(context: &mut NativeContext, _arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    context.require(Capability::Args, "args")?;
+    let values = context
+        .interpreter()
+        .options()
+        .script_args
+        .iter()
+        .map(|arg| LiteralValue::string(arg.as_str()))
+        .collect();
+    Ok(LiteralValue::Array(LoxArray::new(values)))
+}
+
+// env(name) -> value of the environment variable, or nil if it is not set
+fn env(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    context.require(Capability::Env, "env")?;
+    let name = match &arguments[0] {
+        LiteralValue::StringLiteral(name) => name,
+        _ => return Err(context.error("env() expects a string.")),
+    };
+    Ok(std::env::var(name.as_str()).map_or(LiteralValue::Nil, LiteralValue::string))
+}
+
+// readFile(path) -> the file's contents as a string
+fn read_file(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    context.require(Capability::Read, "readFile")?;
+    let path = match &arguments[0] {
+        LiteralValue::StringLiteral(path) => path,
+        _ => return Err(context.error("readFile() expects a string.")),
+    };
+    let text = fs::read_to_string(path.as_str()).map_err(|error| context.error(format!("Failed to read '{}': {}.", path, error)))?;
+    Ok(LiteralValue::string(text))
+}
+
+// writeFile(path, text) -> nil; creates the file, or replaces what it held
+fn write_file(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    context.require(Capability::Write, "writeFile")?;
+    let (path, text) = match (&arguments[0], &arguments[1]) {
+        (LiteralValue::StringLiteral(path), LiteralValue::StringLiteral(text)) => (path, text),
+        _ => return Err(context.error("writeFile() expects a path and a string.")),
+    };
+    fs::write(path.as_str(), text.as_bytes()).map_err(|error| context.error(format!("Failed to write '{}': {}.", path, error)))?;
+    Ok(LiteralValue::Nil)
+}
+
+// sleep(seconds) -> nil. Under ExecOptions::deterministic the virtual clock moves
+// on instead, so the run stays reproducible and doesn't wait.
+fn sleep(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    context.require(Capability::Sleep, "sleep")?;
+    let seconds = match &arguments[0] {
+        LiteralValue::NumberLiteral(seconds) if *seconds >= 0.0 && seconds.is_finite() => *seconds,
+        _ => return Err(context.error("sleep() expects a non-negative number of seconds.")),
+    };
+    if context.interpreter().options().deterministic {
+        context.interpreter().advance_virtual_clock(seconds);
+    } else {
+        thread::sleep(Duration::from_secs_f64(seconds));
+    }
+    Ok(LiteralValue::Nil)
+}
+
+// keys(map) -> array of the map's keys, in sorted order
+fn keys(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    match &arguments[0] {
+        LiteralValue::Map(map) => {
+            let keys = map.keys().into_iter().map(LiteralValue::string).collect();
+            Ok(LiteralValue::Array(LoxArray::new(keys)))
+        }
+        _ => Err(context.error("keys() expects a map.")),
+    }
+}
+
+// fields(instance) -> array of the instance's field names, in sorted order
+fn fields(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    match &arguments[0] {
+        LiteralValue::Instance(instance) => {
+            let names = instance.field_names().into_iter().map(LiteralValue::string).collect();
+            Ok(LiteralValue::Array(LoxArray::new(names)))
+        }
+        _ => Err(context.error("fields() expects an instance.")),
+    }
+}
+
+// jsonParse(s) -> the Lox value (maps, arrays, strings, numbers, booleans, nil) for s
+fn json_parse(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    match &arguments[0] {
+        LiteralValue::StringLiteral(text) => {
+            json::parse(text).map_err(|message| context.error(format!("Invalid JSON: {}.", message)))
+        }
+        _ => Err(context.error("jsonParse() expects a string.")),
+    }
+}
+
+// jsonStringify(v) -> compact JSON text for v
+fn json_stringify(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    json::stringify(&arguments[0])
+        .map(LiteralValue::string)
+        .map_err(|message| context.error(format!("Cannot convert to JSON: {}.", message)))
+}
+
+// format(template, ...) -> template with each {} placeholder replaced by an argument
+fn format(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    format_arguments(context, arguments, "format").map(LiteralValue::string)
+}
+
+// printf(template, ...) -> nil; prints the formatted text without a trailing newline
+fn printf(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let text = format_arguments(context, arguments, "printf")?;
+    context.write(&text)?;
+    Ok(LiteralValue::Nil)
+}
+
+// write(v) -> nil; prints v like print does, but without the trailing newline
+fn write(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let line = context.line();
+    let text = context.interpreter().literal_to_string(arguments[0].clone(), line)?;
+    context.write(&text)?;
+    Ok(LiteralValue::Nil)
+}
+
+// eprint(v) -> nil; prints v and a newline to the error output
+fn eprint(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let line = context.line();
+    let text = context.interpreter().literal_to_string(arguments[0].clone(), line)? + "\n";
+    context.interpreter().write_error_output(&text, line)?;
+    Ok(LiteralValue::Nil)
+}
+
+fn format_arguments(context: &mut NativeContext, arguments: &[LiteralValue], native: &str) -> Result<String, RuntimeError> {
+    let template = match &arguments[0] {
+        LiteralValue::StringLiteral(template) => template,
+        _ => return Err(context.error(format!("{}() expects a format string as its first argument.", native))),
+    };
+    let line = context.line();
+    format::format(context.interpreter(), template, &arguments[1..], line).map_err(|error| match error {
+        FormatError::Template(message) => context.error(format!("{}() {}.", native, message)),
+        FormatError::Runtime(error) => error,
+    })
+}
+
+// readChar() -> next input character as a one-character string, or nil at end of input
+fn read_char(context: &mut NativeContext, _arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let c = context.interpreter().input().read_char().map_err(|error| input_error(context, error))?;
+    Ok(c.map_or(LiteralValue::Nil, LiteralValue::string))
+}
+
+// peekChar() -> like readChar() but leaves the character to be read again
+fn peek_char(context: &mut NativeContext, _arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let c = context.interpreter().input().peek_char().map_err(|error| input_error(context, error))?;
+    Ok(c.map_or(LiteralValue::Nil, LiteralValue::string))
+}
+
+// atEof() -> true once every input character has been read
+fn at_eof(context: &mut NativeContext, _arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    let eof = context.interpreter().input().at_eof().map_err(|error| input_error(context, error))?;
+    Ok(LiteralValue::BooleanLiteral(eof))
+}
+
+fn input_error(context: &NativeContext, error: std::io::Error) -> RuntimeError {
+    context.error(format!("Failed to read input: {}.", error))
+}
+
+// Stable merge sort driven by a Lox comparator. The comparator may be inconsistent or
+// fail, so the standard library sorts (which may panic on a bad total order) are avoided.
+fn merge_sort(context: &mut NativeContext, comparator: &LiteralValue, mut items: Vec<LiteralValue>) -> Result<Vec<LiteralValue>, RuntimeError> {
+    if items.len() <= 1 {
+        return Ok(items);
+    }
+
+    let right = items.split_off(items.len() / 2);
+    let left = merge_sort(context, comparator, items)?;
+    let right = merge_sort(context, comparator, right)?;
+
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        let order = context.call(comparator, vec![a.clone(), b.clone()])?;
+        let take_right = match order {
+            LiteralValue::NumberLiteral(n) => n > 0.0,
+            _ => return Err(context.error("sort() comparator must return a number.")),
+        };
+        // Ties take from the left half, which keeps the sort stable
+        if take_right {
+            merged.extend(right.next());
+        } else {
+            merged.extend(left.next());
+        }
+    }
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
+
+fn expect_array(context: &NativeContext, value: &LiteralValue, native: &str) -> Result<LoxArray, RuntimeError> {
+    match value {
+        LiteralValue::Array(array) => Ok(array.clone()),
+        _ => Err(context.error(format!("{}() expects an array as its first argument.", native))),
+    }
+}
+
+fn expect_callable(context: &NativeContext, value: &LiteralValue, native: &str) -> Result<LiteralValue, RuntimeError> {
+    match value {
+        LiteralValue::Function(_) | LiteralValue::NativeFunction(_) | LiteralValue::HostMethod(_) => Ok(value.clone()),
+        _ => Err(context.error(format!("{}() expects a function as its second argument.", native))),
+    }
+}
+
+fn expect_integer(context: &NativeContext, value: &LiteralValue, native: &str) -> Result<i64, RuntimeError> {
+    match value {
+        LiteralValue::NumberLiteral(n) if n.fract() == 0.0 && n.abs() < (1u64 << 53) as f64 => Ok(*n as i64),
+        _ => Err(context.error(format!("{}() expects integer arguments.", native))),
+    }
+}
@@ -0,0 +1,59 @@
+// Number syntax and formatting in one place. Rust's float parsing and printing never
+// consult the system locale, so '.' is always the decimal point.
+
+// How the interpreter prints numbers: the shortest form that parses back to the same
+// value, or a fixed number of decimal places (`--precision=N`)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NumberFormat {
+    pub precision: Option<usize>,
+}
+
+impl NumberFormat {
+    pub fn format(&self, n: f64) -> String {
+        match self.precision {
+            Some(precision) => fixed(n, precision),
+            None => display(n),
+        }
+    }
+}
+
+// Runtime form: whole numbers without a decimal point ("3"), others with the fewest
+// digits that round-trip through parse(). -0 prints as "0".
+pub fn display(n: f64) -> String {
+    if n == 0.0 {
+        "0".to_string()
+    } else {
+        n.to_string()
+    }
+}
+
+// AST printer and token literal form, as in the book: whole numbers keep one decimal
+// place ("3.0")
+pub fn canonical(n: f64) -> String {
+    if n.fract() == 0.0 {
+        format!("{:.1}", n)
+    } else {
+        n.to_string()
+    }
+}
+
+// Exactly `precision` decimal places, rounded to the nearest. -0 prints as zero here too.
+pub fn fixed(n: f64, precision: usize) -> String {
+    let n = if n == 0.0 { 0.0 } else { n };
+    format!("{:.*}", precision, n)
+}
+
+// The scanner's number syntax with an optional leading minus: -?digits(.digits)?
+pub fn parse(text: &str) -> Option<f64> {
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    let (integer, fraction) = match digits.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (digits, None),
+    };
+
+    let all_digits = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit());
+    if !all_digits(integer) || !fraction.map_or(true, all_digits) {
+        return None;
+    }
+    text.parse::<f64>().ok()
+}
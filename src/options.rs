@@ -0,0 +1,153 @@
+use crate::input::StdinSource;
+use crate::number::NumberFormat;
+
+// Execution options for embedders and the CLI. Every field has a safe default, so
+// callers only set what they need: `ExecOptions { strict_number: true, ..Default::default() }`.
+#[derive(Debug, Clone, Default)]
+pub struct ExecOptions {
+    // number(s) raises a runtime error on unparsable input instead of returning nil
+    pub strict_number: bool,
+    // Reading a variable declared without an initializer, before it is assigned, is a
+    // runtime error instead of nil
+    pub strict_init: bool,
+    // Undefined property errors suggest the closest existing property name
+    pub strict_properties: bool,
+    // Which Lox the program is written in; Book also leaves out the natives beyond clock()
+    pub language: LanguageLevel,
+    // Language extensions beyond the book's Lox (`--extensions`): getters and
+    // operator overloading. Ignored at LanguageLevel::Book.
+    pub extensions: bool,
+    // Seed for random()/randomInt(); None seeds from the clock
+    pub seed: Option<u64>,
+    // Reproducible runs (`--deterministic`): random() is seeded with 0 unless `seed` is
+    // set, and clock() reads a virtual clock, see Interpreter::tick_virtual_clock
+    pub deterministic: bool,
+    // Arguments passed to the script (after `--` on the command line), returned by args()
+    pub script_args: Vec<String>,
+    // Powerful natives the script may use; nothing is granted by default
+    pub capabilities: Capabilities,
+    // Input for readChar()/peekChar()/atEof()
+    pub stdin: StdinSource,
+    // Record per-function and per-line timings, see Interpreter::profiler
+    pub profile: bool,
+    // Count statement executions per line, see Interpreter::coverage
+    pub coverage: bool,
+    // Record every statement and variable write for replay, see Interpreter::trace
+    pub record: bool,
+    // Printed arrays and maps nested deeper than this show as `[...]` / `{...}`
+    pub display_depth: Option<usize>,
+    // Printed arrays and maps show at most this many entries, then `...`
+    pub display_width: Option<usize>,
+    // How print and string conversion write numbers, see NumberFormat
+    pub number_format: NumberFormat,
+}
+
+impl ExecOptions {
+    // Script the interpreter's input: `ExecOptions::default().stdin("1 2 3")` or
+    // `.stdin(StdinSource::reader(file))`
+    pub fn stdin(mut self, source: impl Into<StdinSource>) -> Self {
+        self.stdin = source.into();
+        self
+    }
+}
+
+// Which Lox is accepted (`--lang=book|extended`). Book is exactly the language of
+// Crafting Interpreters, the one the CodeCrafters tests check: `[`, `]` and `:` are
+// unexpected characters, for-in loops, class fields and class methods are syntax
+// errors, and clock() is the only native. Extended, the default, adds all of those;
+// `--extensions` goes further still. The CLI's `tokenize` defaults to Book instead,
+// since its output is graded as is.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LanguageLevel {
+    Book,
+    #[default]
+    Extended,
+}
+
+impl LanguageLevel {
+    pub const ALL: [LanguageLevel; 2] = [LanguageLevel::Book, LanguageLevel::Extended];
+
+    // Name used in `--lang=<name>` and in lox.toml
+    pub fn name(self) -> &'static str {
+        match self {
+            LanguageLevel::Book => "book",
+            LanguageLevel::Extended => "extended",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<LanguageLevel> {
+        LanguageLevel::ALL.into_iter().find(|level| level.name() == name)
+    }
+}
+
+// Host resources a native can reach outside the interpreter. Every native that
+// touches the file system, the environment, the process or the wall clock's pace
+// checks one of these; there is no network access to gate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capability {
+    Read,  // readFile(path): read files
+    Write, // writeFile(path, text): create and overwrite files
+    Env,   // env(name): read environment variables
+    Args,  // args(): read the script's command-line arguments
+    Sleep, // sleep(seconds): pause the interpreter's thread
+}
+
+impl Capability {
+    pub const ALL: [Capability; 5] = [Capability::Read, Capability::Write, Capability::Env, Capability::Args, Capability::Sleep];
+
+    // Name used in diagnostics and in `--allow-<name>` CLI flags
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::Read => "read",
+            Capability::Write => "write",
+            Capability::Env => "env",
+            Capability::Args => "args",
+            Capability::Sleep => "sleep",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Capability> {
+        Capability::ALL.into_iter().find(|capability| capability.name() == name)
+    }
+}
+
+// Set of granted capabilities. Deny-by-default (`Capabilities::default()`) is what
+// library embedders get; the CLI starts from `Capabilities::all()`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Capabilities {
+    pub read: bool,
+    pub write: bool,
+    pub env: bool,
+    pub args: bool,
+    pub sleep: bool,
+}
+
+impl Capabilities {
+    pub fn all() -> Self {
+        Capabilities { read: true, write: true, env: true, args: true, sleep: true }
+    }
+
+    pub fn none() -> Self {
+        Capabilities::default()
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::Read => self.read,
+            Capability::Write => self.write,
+            Capability::Env => self.env,
+            Capability::Args => self.args,
+            Capability::Sleep => self.sleep,
+        }
+    }
+
+    pub fn set(&mut self, capability: Capability, allowed: bool) {
+        match capability {
+            Capability::Read => self.read = allowed,
+            Capability::Write => self.write = allowed,
+            Capability::Env => self.env = allowed,
+            Capability::Args => self.args = allowed,
+            Capability::Sleep => self.sleep = allowed,
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+// Destination for script output. Send + Sync so the interpreter can still move
+// between threads.
+pub type Sink = Box<dyn Write + Send + Sync>;
+
+// In-memory sink that can be read back after (or while) the script runs, for tests
+// and embedders that capture output. Clones share the same buffer.
+#[derive(Clone, Default)]
+pub struct OutputBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl OutputBuffer {
+    pub fn new() -> Self {
+        OutputBuffer::default()
+    }
+
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+
+    pub fn clear(&self) {
+        self.0.lock().unwrap().clear();
+    }
+}
+
+impl Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
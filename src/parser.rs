@@ -1,28 +1,113 @@
-use std::process;
+use std::sync::Arc;
 
+use crate::crash;
+use crate::document::TextEdit;
+use crate::diagnostic::{Diagnostic, Stage, DEFAULT_MAX_ERRORS};
 use crate::token::{Token, TokenType};
-use crate::expr::{Expr, LiteralValue};
-use crate::stmt::Stmt;
+use crate::expr::{Expr, LiteralValue, LocalSlot, MatchArm, Pattern};
+use crate::options::LanguageLevel;
+use crate::stmt::{ClassDecl, FieldDecl, FunctionDecl, Stmt};
+
+// Deepest nesting of blocks and expressions accepted by default. Parsing, resolving
+// and evaluating all recurse on the tree, so this keeps hostile input off the stack limit.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
 
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    had_error: bool,
+    diagnostics: Vec<Diagnostic>,
+    language: LanguageLevel,
+    extensions: bool,   // Accept syntax beyond the book's Lox (`--extensions`)
+    depth: usize,       // Blocks and expressions currently being parsed
+    max_depth: usize,
+    max_errors: usize,
+    suppressed: usize,  // Errors past max_errors, counted but not kept
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
+    pub fn new(mut tokens: Vec<Token>) -> Self {
+        tokens.retain(|token| token.token_type != TokenType::COMMENT);
+        Parser {
+            tokens,
+            current: 0,
+            had_error: false,
+            diagnostics: Vec::new(),
+            language: LanguageLevel::default(),
+            extensions: false,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_errors: DEFAULT_MAX_ERRORS,
+            suppressed: 0,
+        }
     }
 
-     // Parse a list of statements for the 'run' command
-     pub fn parse_statements(&mut self) -> Option<Vec<Stmt>> {
+    // At LanguageLevel::Book, for-in loops, class fields and class methods are syntax
+    // errors, and so are getters even with the extensions
+    pub fn set_language(&mut self, language: LanguageLevel) {
+        self.language = language;
+    }
+
+    // Enable the language extensions: getters (`area { ... }` in a class body),
+    // `defer` statements and `match` expressions
+    pub fn set_extensions(&mut self, extensions: bool) {
+        self.extensions = extensions;
+    }
+
+    // Nesting beyond this depth is a syntax error instead of a stack overflow
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    // Errors kept before the rest are only counted, in a closing note
+    pub fn set_max_errors(&mut self, max_errors: usize) {
+        self.max_errors = max_errors;
+    }
+
+    // Parse a whole program. After a syntax error the parser skips to the next
+    // statement and goes on, so the error lists every one it found, in order.
+    pub fn parse_statements(&mut self) -> Result<Vec<Stmt>, Vec<Diagnostic>> {
+        let statements = self.parse_program();
+        if self.had_error {
+            Err(self.diagnostics.clone())
+        } else {
+            Ok(statements)
+        }
+    }
+
+    // The statements that parsed, leaving out those with syntax errors (see
+    // diagnostics()), for tools that work on incomplete programs
+    pub fn parse_program(&mut self) -> Vec<Stmt> {
         let mut statements = Vec::new();
         while !self.is_at_end() {
             if let Some(stmt) = self.declaration() {
                 statements.push(stmt);
             }
         }
-        Some(statements)
+        if self.suppressed > 0 {
+            self.diagnostics.push(Diagnostic::suppressed(Stage::Parse, self.suppressed, self.max_errors));
+        }
+        statements
+    }
+
+    // True if any syntax error was reported while parsing
+    pub fn has_error(&self) -> bool {
+        self.had_error
+    }
+
+    // Syntax errors found so far
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    // Whether the tokens form a program rather than a single expression: expressions
+    // never contain ';' and never start with a statement keyword or a block
+    pub fn looks_like_program(&self) -> bool {
+        let starts_statement = matches!(
+            self.peek().token_type,
+            TokenType::VAR | TokenType::FUN | TokenType::CLASS | TokenType::PRINT | TokenType::RETURN | TokenType::LEFT_BRACE
+        );
+        starts_statement || self.tokens.iter().any(|token| token.token_type == TokenType::SEMICOLON)
     }
 
     // Parse a single expression for the 'evaluate' command
@@ -30,15 +115,89 @@ impl Parser {
         self.expression()
     }
 
-    // Declaration → variable declaration | statement
+    // Declaration → class declaration | function declaration | variable declaration | statement.
+    // A declaration with a syntax error is skipped, see synchronize.
     fn declaration(&mut self) -> Option<Stmt> {
-        if self.match_token(&[TokenType::VAR]) {
+        let declaration = self.declaration_kind();
+        if declaration.is_none() {
+            self.synchronize();
+        }
+        declaration
+    }
+
+    fn declaration_kind(&mut self) -> Option<Stmt> {
+        if self.match_token(&[TokenType::CLASS]) {
+            self.class_declaration()
+        } else if self.match_token(&[TokenType::FUN]) {
+            Some(Stmt::Function(self.function("function")?))
+        } else if self.match_token(&[TokenType::VAR]) {
             self.var_declaration()
         } else {
             self.statement()
         }
     }
 
+    // Class declaration (e.g., `class Point { var y = 0; init(x) { this.x = x; } }`)
+    fn class_declaration(&mut self) -> Option<Stmt> {
+        let name = self.consume(TokenType::IDENTIFIER, "Expect class name.")?.clone();
+        self.consume(TokenType::LEFT_BRACE, "Expect '{' before class body.")?;
+
+        let mut fields = Vec::new();
+        let mut methods = Vec::new();
+        let mut class_methods = Vec::new();
+        while !self.check(TokenType::RIGHT_BRACE) && !self.is_at_end() {
+            if self.match_token(&[TokenType::VAR]) {
+                self.require_extended("Class fields");
+                let name = self.consume(TokenType::IDENTIFIER, "Expect field name.")?.clone();
+                let initializer = if self.match_token(&[TokenType::EQUAL]) {
+                    Some(self.expression()?)
+                } else {
+                    None
+                };
+                self.consume(TokenType::SEMICOLON, "Expect ';' after field declaration.")?;
+                fields.push(FieldDecl { name, initializer });
+            } else if self.match_token(&[TokenType::CLASS]) {
+                self.require_extended("Class methods");
+                class_methods.push(self.function("method")?);
+            } else {
+                methods.push(self.function("method")?);
+            }
+        }
+        self.consume(TokenType::RIGHT_BRACE, "Expect '}' after class body.")?;
+        Some(Stmt::Class(Arc::new(ClassDecl { name, fields, methods, class_methods })))
+    }
+
+    // Function or method (e.g., `add(a, b) { return a + b; }` after `fun`). With the
+    // extensions, a method without a parameter list is a getter.
+    fn function(&mut self, kind: &str) -> Option<Arc<FunctionDecl>> {
+        let name = self.consume(TokenType::IDENTIFIER, &format!("Expect {} name.", kind))?.clone();
+        let getters = self.extensions && self.language == LanguageLevel::Extended;
+        if kind == "method" && getters && self.match_token(&[TokenType::LEFT_BRACE]) {
+            let body = self.nested(|parser| parser.block())?;
+            return Some(Arc::new(FunctionDecl { name, params: Vec::new(), body, getter: true }));
+        }
+        self.consume(TokenType::LEFT_PAREN, &format!("Expect '(' after {} name.", kind))?;
+
+        let mut params = Vec::new();
+        if !self.check(TokenType::RIGHT_PAREN) {
+            loop {
+                if params.len() >= 255 {
+                    self.error("Can't have more than 255 parameters.");
+                }
+                params.push(self.consume(TokenType::IDENTIFIER, "Expect parameter name.")?.clone());
+                if !self.match_token(&[TokenType::COMMA]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after parameters.")?;
+
+        self.consume(TokenType::LEFT_BRACE, &format!("Expect '{{' before {} body.", kind))?;
+        let body = self.nested(|parser| parser.block())?;
+        log::debug!("function declaration. name: {}, params: {}", name, params.len());
+        Some(Arc::new(FunctionDecl { name, params, body, getter: false }))
+    }
+
     // Variable declaration (e.g., `var a = 5;`)
     fn var_declaration(&mut self) -> Option<Stmt> {
         
@@ -66,43 +225,241 @@ impl Parser {
         }
 
         // Ensure the block is closed with a `}`
-        if self.is_at_end() || !self.match_token(&[TokenType::RIGHT_BRACE]) {
-            self.error("Expect '}' after block.");
-            return None;
-        }
-
+        self.consume(TokenType::RIGHT_BRACE, "Expect '}' after block.")?;
         Some(statements)
     }
 
-    // Statement → print statement | expression statement
+    // Statement → print statement | return statement | for-in statement | defer statement | block | expression statement
     fn statement(&mut self) -> Option<Stmt> {
         if self.match_token(&[TokenType::PRINT]) {
             self.print_statement()
+        } else if self.match_token(&[TokenType::FOR]) {
+            self.for_in_statement()
+        } else if self.match_token(&[TokenType::RETURN]) {
+            self.return_statement()
         } else if self.match_token(&[TokenType::LEFT_BRACE]) {
             // If it's a block statement, return a block
-            Some(Stmt::Block(self.block()?))
+            Some(Stmt::Block(self.nested(|parser| parser.block())?))
+        } else if self.at_defer() {
+            self.defer_statement()
+        } else if self.language == LanguageLevel::Extended
+            && self.check(TokenType::IDENTIFIER)
+            && self.check_next(TokenType::IDENTIFIER)
+            && !self.at_match()
+        {
+            self.adjacent_identifiers()
         } else {
             self.expression_statement()
         }
     }
 
+    // `foo bar;` is never valid: most likely a missing ';' or a mistyped keyword
+    // (`vr x = 1;`). One error spanning both names, at the second. The book's Lox
+    // reports the plain missing ';' instead.
+    fn adjacent_identifiers(&mut self) -> Option<Stmt> {
+        let first = self.advance().clone();
+        let second = self.peek().clone();
+        let message = format!("Unexpected identifier after '{}'. Expect ';' after expression.", first.lexeme);
+        self.report(Diagnostic::error(Stage::Parse, message, second.line).near(&second).from(&first));
+        None
+    }
+
     // Print statement (e.g., `print 5;`)
     fn print_statement(&mut self) -> Option<Stmt> {
         log::debug!("print statement");
-        // Attempt to parse the expression following the 'print' keyword
-        if let Some(expr) = self.expression() {
-            self.consume(TokenType::SEMICOLON, "Expect ';' after value.")?;
-            Some(Stmt::Print(expr))
+        let expr = self.expression()?;
+        self.consume(TokenType::SEMICOLON, "Expect ';' after value.")?;
+        Some(Stmt::Print(expr))
+    }
+
+    // Return statement (e.g., `return a + b;`)
+    fn return_statement(&mut self) -> Option<Stmt> {
+        let keyword = self.previous().clone();
+        let value = if !self.check(TokenType::SEMICOLON) {
+            Some(self.expression()?)
         } else {
-            // Handle missing expression (like 'print;' without an expression)
-            self.error("Expected expression after 'print'");
             None
+        };
+
+        self.consume(TokenType::SEMICOLON, "Expect ';' after return value.")?;
+        Some(Stmt::Return { keyword, value })
+    }
+
+    // forIn → "for" "(" "var" IDENTIFIER "in" expression ")" statement
+    // `in` is only special here, so it can still name a variable elsewhere.
+    fn for_in_statement(&mut self) -> Option<Stmt> {
+        let keyword = self.previous().clone();
+        self.require_extended("For-in loops");
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'for'.")?;
+        self.consume(TokenType::VAR, "Expect 'var' after 'for ('.")?;
+        let name = self.consume(TokenType::IDENTIFIER, "Expect variable name.")?.clone();
+        if !(self.check(TokenType::IDENTIFIER) && self.peek().lexeme == "in") {
+            self.error("Expect 'in' after loop variable.");
+            return None;
         }
+        self.advance();
+        let iterable = self.expression()?;
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after for clauses.")?;
+        let body = self.nested(|parser| parser.statement())?;
+        Some(Stmt::ForIn { keyword, name, iterable, body: Box::new(body) })
     }
 
-    fn error(&self, message: &str) {
-        eprintln!("[line {}] Error: {}", self.peek().line, message);
-        process::exit(65);
+    // `defer` is only a keyword with the extensions, and only before a name or `this`:
+    // `defer = 1;` and `defer(x);` are still an assignment and a call
+    fn at_defer(&self) -> bool {
+        self.extensions
+            && self.language == LanguageLevel::Extended
+            && self.check(TokenType::IDENTIFIER)
+            && self.peek().lexeme == "defer"
+            && (self.check_next(TokenType::IDENTIFIER) || self.check_next(TokenType::THIS))
+    }
+
+    // deferStatement → "defer" expression ";"
+    fn defer_statement(&mut self) -> Option<Stmt> {
+        let keyword = self.advance().clone();
+        let expression = self.expression()?;
+        self.consume(TokenType::SEMICOLON, "Expect ';' after deferred expression.")?;
+        Some(Stmt::Defer { keyword, expression })
+    }
+
+    // Like `defer`, `match` is only a keyword with the extensions, and only before a
+    // subject starting with a name, `this` or a literal: `match(x)` is still a call
+    fn at_match(&self) -> bool {
+        let subject = [
+            TokenType::IDENTIFIER,
+            TokenType::THIS,
+            TokenType::NUMBER,
+            TokenType::STRING,
+            TokenType::TRUE,
+            TokenType::FALSE,
+            TokenType::NIL,
+        ];
+        self.extensions
+            && self.language == LanguageLevel::Extended
+            && self.check(TokenType::IDENTIFIER)
+            && self.peek().lexeme == "match"
+            && subject.into_iter().any(|token_type| self.check_next(token_type))
+    }
+
+    // match → "match" expression "{" pattern "=>" expression ( "," pattern "=>" expression )* ","? "}"
+    fn match_expression(&mut self) -> Option<Expr> {
+        let keyword = self.advance().clone();
+        let subject = self.expression()?;
+        self.consume(TokenType::LEFT_BRACE, "Expect '{' after match value.")?;
+        let mut arms = Vec::new();
+        loop {
+            let pattern = self.pattern()?;
+            // `=>` scans as `=` then `>`
+            self.consume(TokenType::EQUAL, "Expect '=>' after pattern.")?;
+            self.consume(TokenType::GREATER, "Expect '=>' after pattern.")?;
+            let body = self.expression()?;
+            arms.push(MatchArm { pattern, body });
+            if !self.match_token(&[TokenType::COMMA]) || self.check(TokenType::RIGHT_BRACE) {
+                break;
+            }
+        }
+        self.consume(TokenType::RIGHT_BRACE, "Expect '}' after match arms.")?;
+        Some(Expr::Match { keyword, subject: Box::new(subject), arms })
+    }
+
+    // pattern → "_" | IDENTIFIER | NUMBER | STRING | "true" | "false" | "nil"
+    //         | "[" ( pattern ( "," pattern )* )? "]"
+    //         | "{" ( STRING ":" pattern ( "," STRING ":" pattern )* )? "}"
+    fn pattern(&mut self) -> Option<Pattern> {
+        if self.match_token(&[TokenType::IDENTIFIER]) {
+            let name = self.previous().clone();
+            return Some(if name.lexeme == "_" { Pattern::Wildcard(name) } else { Pattern::Binding(name) });
+        }
+        let literals = [TokenType::NUMBER, TokenType::STRING, TokenType::TRUE, TokenType::FALSE, TokenType::NIL];
+        if literals.into_iter().any(|token_type| self.check(token_type)) {
+            return Some(Pattern::Literal(self.primary()?));
+        }
+        if self.match_token(&[TokenType::LEFT_BRACKET]) {
+            let bracket = self.previous().clone();
+            let elements = self.nested(|parser| parser.pattern_list(TokenType::RIGHT_BRACKET, Self::pattern))?;
+            self.consume(TokenType::RIGHT_BRACKET, "Expect ']' after array pattern.")?;
+            return Some(Pattern::Array { bracket, elements });
+        }
+        if self.match_token(&[TokenType::LEFT_BRACE]) {
+            let brace = self.previous().clone();
+            let entries = self.nested(|parser| {
+                parser.pattern_list(TokenType::RIGHT_BRACE, |parser| {
+                    let key = parser.consume(TokenType::STRING, "Expect string key in map pattern.")?.clone();
+                    parser.consume(TokenType::COLON, "Expect ':' after map pattern key.")?;
+                    Some((key, parser.pattern()?))
+                })
+            })?;
+            self.consume(TokenType::RIGHT_BRACE, "Expect '}' after map pattern.")?;
+            return Some(Pattern::Map { brace, entries });
+        }
+        self.error("Expect pattern.");
+        None
+    }
+
+    // Comma-separated items up to, not including, `end`
+    fn pattern_list<T>(&mut self, end: TokenType, mut item: impl FnMut(&mut Self) -> Option<T>) -> Option<Vec<T>> {
+        let mut items = Vec::new();
+        if !self.check(end) {
+            loop {
+                items.push(item(self)?);
+                if !self.match_token(&[TokenType::COMMA]) {
+                    break;
+                }
+            }
+        }
+        Some(items)
+    }
+
+    // Syntax the book's Lox doesn't have, just matched: an error at LanguageLevel::Book.
+    // Parsing goes on as usual, so the rest of the construct causes no further errors.
+    fn require_extended(&mut self, what: &str) {
+        if self.language == LanguageLevel::Book {
+            let token = self.previous();
+            let message = format!("{} are not part of the book's Lox; use --lang=extended.", what);
+            self.report(Diagnostic::error(Stage::Parse, message, token.line).near(token));
+        }
+    }
+
+    // Record a syntax error at the current token. Parsing goes on; rules that can't
+    // return None, and the enclosing declaration is skipped.
+    fn error(&mut self, message: &str) {
+        self.report(self.error_here(message));
+    }
+
+    fn error_here(&self, message: &str) -> Diagnostic {
+        let token = self.peek();
+        Diagnostic::error(Stage::Parse, message, token.line).near(token)
+    }
+
+    fn report(&mut self, diagnostic: Diagnostic) {
+        self.had_error = true;
+        if self.diagnostics.len() < self.max_errors {
+            self.diagnostics.push(diagnostic);
+        } else {
+            self.suppressed += 1;
+        }
+    }
+
+    // Skip to the likely start of the next statement: past a ';', or up to a keyword
+    // that begins a declaration or statement. Always moves past the offending token,
+    // so the parser can't stall on it.
+    fn synchronize(&mut self) {
+        self.advance();
+        while !self.is_at_end() {
+            if self.previous().token_type == TokenType::SEMICOLON {
+                return;
+            }
+            match self.peek().token_type {
+                TokenType::CLASS
+                | TokenType::FUN
+                | TokenType::VAR
+                | TokenType::FOR
+                | TokenType::PRINT
+                | TokenType::RETURN => return,
+                _ => {}
+            }
+            self.advance();
+        }
     }
 
     // Expression statement (e.g., `5 + 3;`)
@@ -114,47 +471,77 @@ impl Parser {
 
     // expression → assignment
     fn expression(&mut self) -> Option<Expr> {
-        self.assignment()
+        self.nested(|parser| parser.assignment())
     }
 
+    // Run a rule one nesting level deeper, failing once the limit is reached
+    fn nested<T>(&mut self, rule: impl FnOnce(&mut Self) -> Option<T>) -> Option<T> {
+        if self.depth >= self.max_depth {
+            self.error(&format!("Too much nesting (limit is {}).", self.max_depth));
+            return None;
+        }
+        self.depth += 1;
+        let result = rule(self);
+        self.depth -= 1;
+        result
+    }
+
+    // assignment → ( call "." IDENTIFIER | call "[" expression "]" | IDENTIFIER ) "=" assignment
+    //            | equality
+    // The target is parsed as an ordinary expression first, so any chain of calls,
+    // property accesses and indexes can lead up to it (`a.b[2](x).c = 5`); only its
+    // last step decides whether it can be assigned to.
     fn assignment(&mut self) -> Option<Expr> {
-        let expr = self.equality();
-    
-        if self.match_token(&[TokenType::EQUAL]) {
-            let _equals = self.previous().clone();
-            let value = self.assignment(); // Recursively call assignment to parse the right-hand side
-    
-            if let Some(Expr::Variable(name)) = expr {
-                log::debug!("assigning to: {}", name);
-                return Some(Expr::Assign { name, value: Box::new(value?) });
+        let expr = self.equality()?;
+
+        if self.check(TokenType::EQUAL) {
+            // Reported at the '=', which the book does without skipping the statement
+            if !matches!(expr, Expr::Variable(..) | Expr::Index { .. } | Expr::Get { .. }) {
+                let mut diagnostic = self.error_here("Invalid assignment target.");
+                // `1 = x` is more likely a comparison than an assignment
+                if matches!(expr, Expr::Literal { .. }) {
+                    let (start, end) = self.peek().span();
+                    diagnostic = diagnostic.with_fix("use '==' to compare", TextEdit { start, end, text: "==".to_string() });
+                }
+                self.report(diagnostic);
             }
-    
-            self.error("Invalid assignment target.");
+            self.advance();
+            let value = Box::new(self.nested(|parser| parser.assignment())?); // Recursively call assignment to parse the right-hand side
+
+            return match expr {
+                Expr::Variable(name, _) => {
+                    log::debug!("assigning to: {}", name);
+                    Some(Expr::Assign { name, value, slot: LocalSlot::default() })
+                }
+                Expr::Index { object, bracket, index } => Some(Expr::SetIndex { object, bracket, index, value }),
+                Expr::Get { object, name } => Some(Expr::Set { object, name, value }),
+                expr => Some(expr),
+            };
         }
-    
-        expr
+
+        Some(expr)
     }
     
     // equality → comparison ( ( "!=" | "==" ) comparison )*
     fn equality(&mut self) -> Option<Expr> {
-        let mut expr = self.comparison();
+        let mut expr = self.comparison()?;
 
         while self.match_token(&[TokenType::BANG_EQUAL, TokenType::EQUAL_EQUAL]) {
             let operator = self.previous().clone();
-            let right = self.comparison();
-            expr = Some(Expr::Binary {
-                left: Box::new(expr?),
+            let right = self.comparison()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
                 operator,
-                right: Box::new(right?),
-            });
+                right: Box::new(right),
+            };
         }
 
-        expr
+        Some(expr)
     }
 
     // comparison → term ( ( ">" | ">=" | "<" | "<=" ) term )*
     fn comparison(&mut self) -> Option<Expr> {
-        let mut expr = self.term();
+        let mut expr = self.term()?;
 
         while self.match_token(&[
             TokenType::GREATER,
@@ -163,102 +550,193 @@ impl Parser {
             TokenType::LESS_EQUAL,
         ]) {
             let operator = self.previous().clone();
-            let right = self.term();
-            expr = Some(Expr::Binary {
-                left: Box::new(expr?),
+            let right = self.term()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
                 operator,
-                right: Box::new(right?),
-            });
+                right: Box::new(right),
+            };
         }
 
-        expr
+        Some(expr)
     }
 
     // term → factor ( ( "-" | "+" ) factor )*
     fn term(&mut self) -> Option<Expr> {
-        let mut expr = self.factor();
+        let mut expr = self.factor()?;
 
         while self.match_token(&[TokenType::PLUS, TokenType::MINUS]) {
             let operator = self.previous().clone();
-            let right = self.factor();
-            expr = Some(Expr::Binary {
-                left: Box::new(expr?),
+            let right = self.factor()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
                 operator,
-                right: Box::new(right?),
-            });
+                right: Box::new(right),
+            };
         }
 
-        expr
+        Some(expr)
     }
 
     // factor → unary ( ( "/" | "*" ) unary )*
     fn factor(&mut self) -> Option<Expr> {
-        let mut expr = self.unary();
+        let mut expr = self.unary()?;
 
         while self.match_token(&[TokenType::STAR, TokenType::SLASH]) {
             let operator = self.previous().clone();
-            let right = self.unary();
-            expr = Some(Expr::Binary {
-                left: Box::new(expr?),
+            let right = self.unary()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
                 operator,
-                right: Box::new(right?),
-            });
+                right: Box::new(right),
+            };
         }
 
-        expr
+        Some(expr)
     }
 
-    // unary → ( "!" | "-" ) unary | primary
+    // unary → ( "!" | "-" ) unary | call
     fn unary(&mut self) -> Option<Expr> {
         if self.match_token(&[TokenType::BANG, TokenType::MINUS]) {
             let operator = self.previous().clone();
-            let right = self.unary();
+            let right = self.nested(|parser| parser.unary());
             return Some(Expr::Unary {
                 operator,
                 right: Box::new(right?),
             });
         }
 
-        self.primary()
+        self.call()
     }
 
-    // primary → NUMBER | STRING | "true" | "false" | "nil" | "(" expression ")"
+    // call → primary ( "(" arguments? ")" | "[" subscript "]" | "." IDENTIFIER )*
+    fn call(&mut self) -> Option<Expr> {
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.match_token(&[TokenType::LEFT_PAREN]) {
+                expr = self.finish_call(expr)?;
+            } else if self.match_token(&[TokenType::LEFT_BRACKET]) {
+                expr = self.finish_subscript(expr)?;
+            } else if self.match_token(&[TokenType::DOT]) {
+                let name = self.consume(TokenType::IDENTIFIER, "Expect property name after '.'.")?.clone();
+                expr = Expr::Get {
+                    object: Box::new(expr),
+                    name,
+                };
+            } else {
+                break;
+            }
+        }
+
+        Some(expr)
+    }
+
+    // subscript → expression | expression? ":" expression?
+    fn finish_subscript(&mut self, object: Expr) -> Option<Expr> {
+        let start = if self.check(TokenType::COLON) { None } else { Some(Box::new(self.expression()?)) };
+        if !self.match_token(&[TokenType::COLON]) {
+            let bracket = self.consume(TokenType::RIGHT_BRACKET, "Expect ']' after index.")?.clone();
+            return Some(Expr::Index {
+                object: Box::new(object),
+                bracket,
+                index: start?,
+            });
+        }
+        let end = if self.check(TokenType::RIGHT_BRACKET) { None } else { Some(Box::new(self.expression()?)) };
+        let bracket = self.consume(TokenType::RIGHT_BRACKET, "Expect ']' after slice.")?.clone();
+        Some(Expr::Slice {
+            object: Box::new(object),
+            bracket,
+            start,
+            end,
+        })
+    }
+
+    // arguments → expression ( "," expression )*
+    fn finish_call(&mut self, callee: Expr) -> Option<Expr> {
+        let mut arguments = Vec::new();
+        if !self.check(TokenType::RIGHT_PAREN) {
+            loop {
+                if arguments.len() >= 255 {
+                    self.error("Can't have more than 255 arguments.");
+                }
+                arguments.push(self.expression()?);
+                if !self.match_token(&[TokenType::COMMA]) {
+                    break;
+                }
+            }
+        }
+
+        let paren = self.consume(TokenType::RIGHT_PAREN, "Expect ')' after arguments.")?.clone();
+        Some(Expr::Call {
+            callee: Box::new(callee),
+            paren,
+            arguments,
+        })
+    }
+
+    // primary → NUMBER | STRING | "true" | "false" | "nil" | "this" | IDENTIFIER | "[" elements? "]" | "(" expression ")"
     fn primary(&mut self) -> Option<Expr> {
         if self.match_token(&[TokenType::NUMBER]) {
             // Parse the number into a LiteralValue::NumberLiteral
             let value = self.previous().literal.clone()?.parse::<f64>().ok()?;
-            return Some(Expr::Literal(LiteralValue::NumberLiteral(value)));
+            return Some(Expr::Literal { value: LiteralValue::NumberLiteral(value), token: self.previous().clone() });
         }
 
         if self.match_token(&[TokenType::STRING]) {
             let value = self.previous().literal.clone()?;
-            return Some(Expr::Literal(LiteralValue::StringLiteral(value)));
+            return Some(Expr::Literal { value: LiteralValue::string(value), token: self.previous().clone() });
         }
 
         if self.match_token(&[TokenType::TRUE]) {
-            return Some(Expr::Literal(LiteralValue::BooleanLiteral(true)));
+            return Some(Expr::Literal { value: LiteralValue::BooleanLiteral(true), token: self.previous().clone() });
         }
 
         if self.match_token(&[TokenType::FALSE]) {
-            return Some(Expr::Literal(LiteralValue::BooleanLiteral(false)));
+            return Some(Expr::Literal { value: LiteralValue::BooleanLiteral(false), token: self.previous().clone() });
         }
 
         if self.match_token(&[TokenType::NIL]) {
-            return Some(Expr::Literal(LiteralValue::Nil));
+            return Some(Expr::Literal { value: LiteralValue::Nil, token: self.previous().clone() });
+        }
+
+        if self.match_token(&[TokenType::THIS]) {
+            return Some(Expr::This(self.previous().clone(), LocalSlot::default()));
+        }
+
+        if self.at_match() {
+            return self.match_expression();
         }
 
         if self.match_token(&[TokenType::IDENTIFIER]) {
             // If we see an identifier, return it as an Expr::Variable
             let name = self.previous().clone();
-            return Some(Expr::Variable(name));
+            return Some(Expr::Variable(name, LocalSlot::default()));
+        }
+
+        if self.match_token(&[TokenType::LEFT_BRACKET]) {
+            let bracket = self.previous().clone();
+            let mut elements = Vec::new();
+            if !self.check(TokenType::RIGHT_BRACKET) {
+                loop {
+                    elements.push(self.expression()?);
+                    if !self.match_token(&[TokenType::COMMA]) {
+                        break;
+                    }
+                }
+            }
+            self.consume(TokenType::RIGHT_BRACKET, "Expect ']' after array elements.")?;
+            return Some(Expr::Array { bracket, elements });
         }
 
         if self.match_token(&[TokenType::LEFT_PAREN]) {
-            let expr = self.expression();
+            let expr = self.expression()?;
             self.consume(TokenType::RIGHT_PAREN, "Expect ')' after expression.")?;
-            return Some(Expr::Grouping(Box::new(expr?)));
+            return Some(Expr::Grouping(Box::new(expr)));
         }
 
+        self.error("Expect expression.");
         None
     }
 
@@ -285,6 +763,7 @@ impl Parser {
         if !self.is_at_end() {
             self.current += 1;
         }
+        crash::set_line(self.previous().line);
         self.previous()
     }
 
@@ -296,6 +775,17 @@ impl Parser {
         &self.tokens[self.current]
     }
 
+    // The token `offset` places after the current one, or EOF past the end
+    fn peek_at(&self, offset: usize) -> &Token {
+        let last = self.tokens.len() - 1;
+        &self.tokens[(self.current + offset).min(last)]
+    }
+
+    // Like check, one token ahead
+    fn check_next(&self, token_type: TokenType) -> bool {
+        !self.is_at_end() && self.peek_at(1).token_type == token_type
+    }
+
     fn previous(&self) -> &Token {
         &self.tokens[self.current - 1]
     }
@@ -305,7 +795,21 @@ impl Parser {
             return Some(self.advance());
         }
 
-        eprintln!("{}", message);
+        // A missing ';' or closing bracket most likely belongs right after the last
+        // token that parsed, not where the error is found
+        let mut diagnostic = self.error_here(message);
+        let closer = match token_type {
+            TokenType::SEMICOLON => Some(";"),
+            TokenType::RIGHT_PAREN => Some(")"),
+            TokenType::RIGHT_BRACKET => Some("]"),
+            TokenType::RIGHT_BRACE => Some("}"),
+            _ => None,
+        };
+        if let (Some(closer), true) = (closer, self.current > 0) {
+            let end = self.previous().span().1;
+            diagnostic = diagnostic.with_fix(format!("insert '{}'", closer), TextEdit { start: end, end, text: closer.to_string() });
+        }
+        self.report(diagnostic);
         None
     }
 }
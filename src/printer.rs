@@ -0,0 +1,353 @@
+use std::fmt::Write;
+
+use crate::ast;
+use crate::expr::{Expr, Pattern};
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::stmt::{ClassDecl, FunctionDecl, Stmt};
+use crate::token::TokenType;
+
+const INDENT: &str = "  ";
+
+// Binding strength of an expression, loosest first. A child is parenthesized only
+// when it binds more loosely than its position needs, so the printed source parses
+// back to the same tree.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Precedence {
+    Assignment,
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Unary,
+    Call, // Calls, property access, indexing and slicing
+    Primary,
+}
+
+fn precedence(expr: &Expr) -> Precedence {
+    match expr {
+        Expr::Assign { .. } | Expr::Set { .. } | Expr::SetIndex { .. } => Precedence::Assignment,
+        Expr::Binary { operator, .. } => match operator.token_type {
+            TokenType::EQUAL_EQUAL | TokenType::BANG_EQUAL => Precedence::Equality,
+            TokenType::PLUS | TokenType::MINUS => Precedence::Term,
+            TokenType::STAR | TokenType::SLASH => Precedence::Factor,
+            _ => Precedence::Comparison,
+        },
+        Expr::Unary { .. } => Precedence::Unary,
+        Expr::Call { .. } | Expr::Get { .. } | Expr::Index { .. } | Expr::Slice { .. } => Precedence::Call,
+        Expr::Grouping(inner) => precedence(inner),
+        Expr::Array { .. } | Expr::Literal { .. } | Expr::This(..) | Expr::Variable(..) | Expr::Match { .. } => Precedence::Primary,
+    }
+}
+
+// Lox source for a program, one statement per line, indented by nesting. Literals are
+// kept as written; parentheses only where precedence needs them, so redundant ones
+// in the input are dropped. Comments aren't in the tree and are lost.
+pub fn to_source(statements: &[Stmt]) -> String {
+    let mut out = String::new();
+    for (i, stmt) in statements.iter().enumerate() {
+        // Declarations are set off from their neighbors by a blank line
+        let declaration = |stmt: &Stmt| matches!(stmt, Stmt::Function(_) | Stmt::Class(_));
+        if i > 0 && (declaration(stmt) || declaration(&statements[i - 1])) {
+            out.push('\n');
+        }
+        write_stmt(&mut out, stmt, 0);
+        out.push('\n');
+    }
+    out
+}
+
+// Print a program and parse the result, which must give the same program back (see
+// ast::same_program). The error says where it didn't: a bug in the printer, or in
+// the parser. For `fmt --check` and `generate --round-trip`.
+pub fn check_round_trip(statements: &[Stmt]) -> Result<(), String> {
+    let source = to_source(statements);
+    let mut scanner = Scanner::new(source.clone());
+    scanner.scan_tokens();
+    if let Some(error) = scanner.diagnostics().first() {
+        return Err(format!("printed source doesn't scan: {}\n{}", error.to_text(), source));
+    }
+    let mut parser = Parser::new(scanner.get_tokens().to_vec());
+    parser.set_extensions(true); // Getters only parse with the extensions
+    let reparsed = parser
+        .parse_statements()
+        .map_err(|errors| format!("printed source doesn't parse: {}\n{}", errors[0].to_text(), source))?;
+    if ast::same_program(statements, &reparsed) {
+        return Ok(());
+    }
+    // The first statement that differs, or the first one only one side has
+    let i = statements
+        .iter()
+        .zip(&reparsed)
+        .position(|(a, b)| !ast::same_stmt(a, b))
+        .unwrap_or(statements.len().min(reparsed.len()));
+    let show = |statements: &[Stmt]| statements.get(i).map_or("nothing".to_string(), Stmt::to_string);
+    Err(format!(
+        "statement {} changed when printed and parsed again\n  was: {}\n  now: {}",
+        i + 1,
+        show(statements),
+        show(&reparsed)
+    ))
+}
+
+// Lox source for one expression, e.g. `a * (b + c)`
+pub fn expr_to_source(expr: &Expr) -> String {
+    let mut out = String::new();
+    write_expr(&mut out, expr, Precedence::Assignment);
+    out
+}
+
+// Write a statement at the given indentation, without a trailing newline
+fn write_stmt(out: &mut String, stmt: &Stmt, depth: usize) {
+    out.push_str(&INDENT.repeat(depth));
+    match stmt {
+        Stmt::Expression(expr) => {
+            write_expr(out, expr, Precedence::Assignment);
+            out.push(';');
+        }
+        Stmt::Print(expr) => {
+            out.push_str("print ");
+            write_expr(out, expr, Precedence::Assignment);
+            out.push(';');
+        }
+        Stmt::Var { name, initializer } => {
+            write!(out, "var {}", name.lexeme).unwrap();
+            if let Some(initializer) = initializer {
+                out.push_str(" = ");
+                write_expr(out, initializer, Precedence::Assignment);
+            }
+            out.push(';');
+        }
+        Stmt::Block(statements) => write_block(out, statements, depth),
+        Stmt::Function(declaration) => {
+            out.push_str("fun ");
+            write_function(out, declaration, depth);
+        }
+        Stmt::Class(declaration) => write_class(out, declaration, depth),
+        Stmt::Return { value, .. } => {
+            out.push_str("return");
+            if let Some(value) = value {
+                out.push(' ');
+                write_expr(out, value, Precedence::Assignment);
+            }
+            out.push(';');
+        }
+        Stmt::ForIn { name, iterable, body, .. } => {
+            write!(out, "for (var {} in ", name.lexeme).unwrap();
+            write_expr(out, iterable, Precedence::Assignment);
+            out.push_str(") ");
+            // The body goes on the same line, so it is written without indentation
+            let mut body_source = String::new();
+            write_stmt(&mut body_source, body, depth);
+            out.push_str(body_source.trim_start());
+        }
+        Stmt::Defer { expression, .. } => {
+            out.push_str("defer ");
+            write_expr(out, expression, Precedence::Assignment);
+            out.push(';');
+        }
+    }
+}
+
+// `{`, the statements one level deeper, `}` at the current level
+fn write_block(out: &mut String, statements: &[Stmt], depth: usize) {
+    if statements.is_empty() {
+        out.push_str("{}");
+        return;
+    }
+    out.push_str("{\n");
+    for stmt in statements {
+        write_stmt(out, stmt, depth + 1);
+        out.push('\n');
+    }
+    out.push_str(&INDENT.repeat(depth));
+    out.push('}');
+}
+
+// `name(params) { body }`, or `name { body }` for a getter
+fn write_function(out: &mut String, declaration: &FunctionDecl, depth: usize) {
+    out.push_str(&declaration.name.lexeme);
+    if !declaration.getter {
+        let params: Vec<&str> = declaration.params.iter().map(|param| param.lexeme.as_str()).collect();
+        write!(out, "({})", params.join(", ")).unwrap();
+    }
+    out.push(' ');
+    write_block(out, &declaration.body, depth);
+}
+
+// Members in source order, whatever their kind
+fn write_class(out: &mut String, declaration: &ClassDecl, depth: usize) {
+    write!(out, "class {} {{", declaration.name.lexeme).unwrap();
+    let mut members: Vec<(usize, String)> = Vec::new();
+    for field in &declaration.fields {
+        let mut member = format!("var {}", field.name.lexeme);
+        if let Some(initializer) = &field.initializer {
+            member.push_str(" = ");
+            write_expr(&mut member, initializer, Precedence::Assignment);
+        }
+        member.push(';');
+        members.push((field.name.start, member));
+    }
+    for (methods, prefix) in [(&declaration.methods, ""), (&declaration.class_methods, "class ")] {
+        for method in methods {
+            let mut member = prefix.to_string();
+            write_function(&mut member, method, depth + 1);
+            members.push((method.name.start, member));
+        }
+    }
+    members.sort_by_key(|(start, _)| *start);
+
+    if members.is_empty() {
+        out.push('}');
+        return;
+    }
+    out.push('\n');
+    for (_, member) in members {
+        writeln!(out, "{}{}", INDENT.repeat(depth + 1), member).unwrap();
+    }
+    out.push_str(&INDENT.repeat(depth));
+    out.push('}');
+}
+
+// Write an expression in a position that needs at least `needed` precedence,
+// parenthesizing it if it binds more loosely
+fn write_expr(out: &mut String, expr: &Expr, needed: Precedence) {
+    if let Expr::Grouping(inner) = expr {
+        return write_expr(out, inner, needed);
+    }
+    if precedence(expr) < needed {
+        out.push('(');
+        write_expr(out, expr, Precedence::Assignment);
+        out.push(')');
+        return;
+    }
+
+    match expr {
+        Expr::Assign { name, value, .. } => {
+            write!(out, "{} = ", name.lexeme).unwrap();
+            write_expr(out, value, Precedence::Assignment);
+        }
+        Expr::Set { object, name, value } => {
+            write_expr(out, object, Precedence::Call);
+            write!(out, ".{} = ", name.lexeme).unwrap();
+            write_expr(out, value, Precedence::Assignment);
+        }
+        Expr::SetIndex { object, index, value, .. } => {
+            write_expr(out, object, Precedence::Call);
+            out.push('[');
+            write_expr(out, index, Precedence::Assignment);
+            out.push_str("] = ");
+            write_expr(out, value, Precedence::Assignment);
+        }
+        Expr::Binary { left, operator, right } => {
+            // Left-associative: a right operand at the same level needs parentheses
+            let own = precedence(expr);
+            write_expr(out, left, own);
+            write!(out, " {} ", operator.lexeme).unwrap();
+            write_expr(out, right, next(own));
+        }
+        Expr::Unary { operator, right } => {
+            out.push_str(&operator.lexeme);
+            write_expr(out, right, Precedence::Unary);
+        }
+        Expr::Call { callee, arguments, .. } => {
+            write_expr(out, callee, Precedence::Call);
+            out.push('(');
+            write_list(out, arguments);
+            out.push(')');
+        }
+        Expr::Get { object, name } => {
+            write_expr(out, object, Precedence::Call);
+            write!(out, ".{}", name.lexeme).unwrap();
+        }
+        Expr::Index { object, index, .. } => {
+            write_expr(out, object, Precedence::Call);
+            out.push('[');
+            write_expr(out, index, Precedence::Assignment);
+            out.push(']');
+        }
+        Expr::Slice { object, start, end, .. } => {
+            write_expr(out, object, Precedence::Call);
+            out.push('[');
+            if let Some(start) = start {
+                write_expr(out, start, Precedence::Assignment);
+            }
+            out.push(':');
+            if let Some(end) = end {
+                write_expr(out, end, Precedence::Assignment);
+            }
+            out.push(']');
+        }
+        Expr::Array { elements, .. } => {
+            out.push('[');
+            write_list(out, elements);
+            out.push(']');
+        }
+        Expr::Literal { token, .. } => out.push_str(&token.lexeme),
+        Expr::This(..) => out.push_str("this"),
+        Expr::Variable(name, _) => out.push_str(&name.lexeme),
+        Expr::Match { subject, arms, .. } => {
+            out.push_str("match ");
+            write_expr(out, subject, Precedence::Assignment);
+            out.push_str(" {");
+            for (i, arm) in arms.iter().enumerate() {
+                out.push_str(if i == 0 { " " } else { ", " });
+                write_pattern(out, &arm.pattern);
+                out.push_str(" => ");
+                write_expr(out, &arm.body, Precedence::Assignment);
+            }
+            out.push_str(" }");
+        }
+        Expr::Grouping(_) => unreachable!("groupings are unwrapped above"),
+    }
+}
+
+fn write_pattern(out: &mut String, pattern: &Pattern) {
+    match pattern {
+        Pattern::Wildcard(token) | Pattern::Binding(token) => out.push_str(&token.lexeme),
+        Pattern::Literal(literal) => write_expr(out, literal, Precedence::Primary),
+        Pattern::Array { elements, .. } => {
+            out.push('[');
+            for (i, element) in elements.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_pattern(out, element);
+            }
+            out.push(']');
+        }
+        Pattern::Map { entries, .. } => {
+            out.push('{');
+            for (i, (key, pattern)) in entries.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write!(out, "{}: ", key.lexeme).unwrap();
+                write_pattern(out, pattern);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn write_list(out: &mut String, exprs: &[Expr]) {
+    for (i, expr) in exprs.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write_expr(out, expr, Precedence::Assignment);
+    }
+}
+
+// The level just above a binary operator's, for its right operand
+fn next(precedence: Precedence) -> Precedence {
+    match precedence {
+        Precedence::Assignment => Precedence::Equality,
+        Precedence::Equality => Precedence::Comparison,
+        Precedence::Comparison => Precedence::Term,
+        Precedence::Term => Precedence::Factor,
+        Precedence::Factor => Precedence::Unary,
+        Precedence::Unary => Precedence::Call,
+        Precedence::Call | Precedence::Primary => Precedence::Primary,
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+// Name of the implicit outermost frame in folded stacks
+const SCRIPT_FRAME: &str = "<script>";
+
+#[derive(Debug, Clone, Default)]
+pub struct FunctionStats {
+    pub calls: u64,
+    pub total: Duration,      // Inclusive time, counting recursive calls once
+    pub self_time: Duration,  // Time not spent in callees
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LineStats {
+    pub count: u64,
+    pub total: Duration,
+}
+
+struct Frame {
+    name: String,
+    start: Instant,
+    child_time: Duration,
+}
+
+// Records per-function and per-line timings through the interpreter's call and
+// statement hooks (`run --profile`)
+#[derive(Default)]
+pub struct Profiler {
+    functions: HashMap<String, FunctionStats>,
+    lines: HashMap<usize, LineStats>,
+    folded: HashMap<String, Duration>, // "a;b;c" call stack -> self time
+    stack: Vec<Frame>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler::default()
+    }
+
+    pub fn enter(&mut self, name: &str) {
+        self.stack.push(Frame {
+            name: name.to_string(),
+            start: Instant::now(),
+            child_time: Duration::ZERO,
+        });
+    }
+
+    // Must be called once for every `enter`, including when the call fails
+    pub fn exit(&mut self) {
+        let frame = match self.stack.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+        let elapsed = frame.start.elapsed();
+        let self_time = elapsed.saturating_sub(frame.child_time);
+
+        let mut path = String::from(SCRIPT_FRAME);
+        for outer in &self.stack {
+            path.push(';');
+            path.push_str(&outer.name);
+        }
+        path.push(';');
+        path.push_str(&frame.name);
+        *self.folded.entry(path).or_default() += self_time;
+
+        let recursive = self.stack.iter().any(|outer| outer.name == frame.name);
+        let stats = self.functions.entry(frame.name).or_default();
+        stats.calls += 1;
+        stats.self_time += self_time;
+        if !recursive {
+            stats.total += elapsed;
+        }
+
+        if let Some(parent) = self.stack.last_mut() {
+            parent.child_time += elapsed;
+        }
+    }
+
+    pub fn record_line(&mut self, line: usize, elapsed: Duration) {
+        let stats = self.lines.entry(line).or_default();
+        stats.count += 1;
+        stats.total += elapsed;
+    }
+
+    pub fn functions(&self) -> &HashMap<String, FunctionStats> {
+        &self.functions
+    }
+
+    pub fn lines(&self) -> &HashMap<usize, LineStats> {
+        &self.lines
+    }
+
+    // Human-readable report, functions and lines sorted by total time
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        let mut functions: Vec<_> = self.functions.iter().collect();
+        functions.sort_by(|a, b| b.1.total.cmp(&a.1.total).then(a.0.cmp(b.0)));
+        writeln!(out, "{:>10} {:>12} {:>12}  function", "calls", "total ms", "self ms").unwrap();
+        for (name, stats) in functions {
+            writeln!(out, "{:>10} {:>12.3} {:>12.3}  {}", stats.calls, millis(stats.total), millis(stats.self_time), name).unwrap();
+        }
+
+        let mut lines: Vec<_> = self.lines.iter().collect();
+        lines.sort_by(|a, b| b.1.total.cmp(&a.1.total).then(a.0.cmp(b.0)));
+        writeln!(out).unwrap();
+        writeln!(out, "{:>10} {:>12}  line", "count", "total ms").unwrap();
+        for (line, stats) in lines {
+            writeln!(out, "{:>10} {:>12.3}  {}", stats.count, millis(stats.total), line).unwrap();
+        }
+        out
+    }
+
+    // Folded stacks ("frame;frame;frame microseconds" per line), the input format of
+    // flamegraph.pl and inferno
+    pub fn folded_stacks(&self) -> String {
+        let mut stacks: Vec<_> = self.folded.iter().collect();
+        stacks.sort();
+        let mut out = String::new();
+        for (path, time) in stacks {
+            writeln!(out, "{} {}", path, time.as_micros()).unwrap();
+        }
+        out
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
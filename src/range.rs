@@ -0,0 +1,88 @@
+use std::sync::OnceLock;
+
+use crate::class::{HostClass, LoxClass, LoxInstance};
+use crate::expr::LiteralValue;
+use crate::function::{Arity, NativeContext};
+use crate::interpreter::RuntimeError;
+
+// The numbers from `start` up to (not including) `end`, `step` apart. Nothing is
+// materialized: for-in asks the range for an iterator, which computes each number
+// as it is needed, so `range(0, 1000000)` costs the same as `range(0, 3)`.
+#[derive(Debug, Clone, Copy)]
+struct Range {
+    start: f64,
+    end: f64,
+    step: f64,
+}
+
+impl Range {
+    // The `index`th number, or None past the end. Computed from the start each time so
+    // fractional steps don't accumulate rounding errors.
+    fn nth(&self, index: u64) -> Option<f64> {
+        let value = self.start + index as f64 * self.step;
+        let within = if self.step > 0.0 { value < self.end } else { value > self.end };
+        within.then_some(value)
+    }
+}
+
+struct RangeIterator {
+    range: Range,
+    index: u64,
+}
+
+// range(start, end, step = 1) -> a Range, iterable with for-in. A negative step
+// counts down; a range that can't reach its end is empty.
+pub fn range(context: &mut NativeContext, arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    if arguments.len() > 3 {
+        return Err(context.error(format!("range() expects 2 or 3 arguments but got {}.", arguments.len())));
+    }
+    let mut numbers = [0.0, 0.0, 1.0];
+    for (number, argument) in numbers.iter_mut().zip(arguments) {
+        match argument {
+            LiteralValue::NumberLiteral(n) if n.is_finite() => *number = *n,
+            _ => return Err(context.error("range() expects finite numbers.")),
+        }
+    }
+    let [start, end, step] = numbers;
+    if step == 0.0 {
+        return Err(context.error("range() expects a non-zero step."));
+    }
+    let instance = LoxInstance::with_host_data(range_class(), Range { start, end, step });
+    Ok(LiteralValue::Instance(instance))
+}
+
+// Not registered as a global: ranges only come from range()
+fn range_class() -> LoxClass {
+    static CLASS: OnceLock<LoxClass> = OnceLock::new();
+    let class = CLASS.get_or_init(|| {
+        let class = HostClass::new("Range", Arity::Fixed(0), |context, _| {
+            Err::<Range, _>(context.error("Ranges are made with range()."))
+        })
+        .method("iter", Arity::Fixed(0), |_, this, _| {
+            let range = this.with_data(|range: &mut Range| *range).expect("Range instances hold a Range");
+            let iterator = LoxInstance::with_host_data(iterator_class(), RangeIterator { range, index: 0 });
+            Ok(LiteralValue::Instance(iterator))
+        });
+        LoxClass::host(class)
+    });
+    class.clone()
+}
+
+fn iterator_class() -> LoxClass {
+    static CLASS: OnceLock<LoxClass> = OnceLock::new();
+    let class = CLASS.get_or_init(|| {
+        let class = HostClass::new("RangeIterator", Arity::Fixed(0), |context, _| {
+            Err::<RangeIterator, _>(context.error("Range iterators are made with iter()."))
+        })
+        .method("next", Arity::Fixed(0), |_, this, _| {
+            let next = this.with_data(|iterator: &mut RangeIterator| {
+                let value = iterator.range.nth(iterator.index);
+                iterator.index += u64::from(value.is_some());
+                value
+            });
+            Ok(next.flatten().map_or(LiteralValue::Nil, LiteralValue::NumberLiteral))
+        });
+        LoxClass::host(class)
+    });
+    class.clone()
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use crate::diagnostic::Diagnostic;
+use crate::document::TextEdit;
+use crate::parser::Parser;
+use crate::resolver::{Reference, Resolver, SymbolKind};
+use crate::scanner::Scanner;
+use crate::semantic;
+use crate::token::TokenType;
+
+// Scan, parse and resolve a program for the editing commands, which refuse to work
+// on a file with errors
+pub fn resolve_source(source: &str) -> Result<Vec<Reference>, Vec<Diagnostic>> {
+    let mut scanner = Scanner::new(source.to_string());
+    scanner.scan_tokens();
+    if scanner.has_error() {
+        return Err(scanner.diagnostics().to_vec());
+    }
+    let mut parser = Parser::new(scanner.get_tokens().to_vec());
+    let statements = parser.parse_statements()?;
+    let mut resolver = Resolver::new();
+    resolver.resolve(&statements);
+    if resolver.has_error() {
+        return Err(resolver.diagnostics().to_vec());
+    }
+    Ok(resolver.references())
+}
+
+// Edits renaming the variable, function or class named at a 1-based line and column,
+// declaration and uses alike. Properties and methods are looked up at runtime and
+// can't be renamed safely. The rename is refused if it would make any name in the
+// program, renamed or not, refer to a different declaration.
+pub fn rename(source: &str, line: usize, column: usize, new_name: &str) -> Result<Vec<TextEdit>, String> {
+    let references = resolve_source(source).map_err(|_| "The file has errors; fix them before renaming.".to_string())?;
+    let target = semantic::reference_at(&references, line, column).ok_or_else(|| format!("No name at {}:{}.", line, column))?;
+    let old_name = &target.name.lexeme;
+    if matches!(target.kind, SymbolKind::Property | SymbolKind::Method) {
+        return Err(format!("Can't rename '{}': properties and methods are looked up at runtime.", old_name));
+    }
+    let declaration = target.declaration.as_ref().ok_or_else(|| format!("'{}' is not declared in this file.", old_name))?;
+    if !is_identifier(new_name) {
+        return Err(format!("'{}' is not a valid name.", new_name));
+    }
+
+    let edits: Vec<TextEdit> = references
+        .iter()
+        .filter(|reference| reference.declaration.as_ref().is_some_and(|other| other.start == declaration.start))
+        .map(|reference| TextEdit {
+            start: reference.name.start,
+            end: reference.name.start + reference.name.len(),
+            text: new_name.to_string(),
+        })
+        .collect();
+
+    let collision = || format!("Renaming '{}' to '{}' would change what other names refer to.", old_name, new_name);
+    let renamed = resolve_source(&apply(source, &edits)).map_err(|_| collision())?;
+    if bindings(&renamed) != bindings(&references) {
+        return Err(collision());
+    }
+    Ok(edits)
+}
+
+// Apply non-overlapping edits, given in source order
+pub fn apply(source: &str, edits: &[TextEdit]) -> String {
+    let mut result = source.to_string();
+    for edit in edits.iter().rev() {
+        result.replace_range(edit.start..edit.end, &edit.text);
+    }
+    result
+}
+
+// A single identifier that is not a reserved word
+fn is_identifier(name: &str) -> bool {
+    let mut scanner = Scanner::new(name.to_string());
+    scanner.scan_tokens();
+    let tokens = scanner.get_tokens();
+    !scanner.has_error() && tokens.len() == 2 && tokens[0].token_type == TokenType::IDENTIFIER && tokens[0].lexeme == name
+}
+
+// Shape of the binding table: for each name occurrence, its kind and which
+// occurrence (by position in the list) declares it
+fn bindings(references: &[Reference]) -> Vec<(SymbolKind, Option<usize>)> {
+    let positions: HashMap<usize, usize> = references.iter().enumerate().map(|(i, reference)| (reference.name.start, i)).collect();
+    references
+        .iter()
+        .map(|reference| {
+            let declaration = reference.declaration.as_ref().and_then(|declaration| positions.get(&declaration.start).copied());
+            (reference.kind, declaration)
+        })
+        .collect()
+}
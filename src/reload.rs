@@ -0,0 +1,56 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::expr::LiteralValue;
+use crate::stmt::Stmt;
+
+// Which top-level functions and classes a reload (Interpreter::reload) added, changed
+// or removed. A definition counts as changed when its code differs; moving it to
+// other lines does not change it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReloadDiff {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl ReloadDiff {
+    pub fn new(before: &BTreeMap<String, String>, after: &BTreeMap<String, String>) -> Self {
+        let mut diff = ReloadDiff::default();
+        for (name, code) in after {
+            match before.get(name) {
+                None => diff.added.push(name.clone()),
+                Some(previous) if previous != code => diff.changed.push(name.clone()),
+                Some(_) => {}
+            }
+        }
+        diff.removed = before.keys().filter(|name| !after.contains_key(*name)).cloned().collect();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+// One line per definition, like a diff: `+ added`, `~ changed`, `- removed`
+impl fmt::Display for ReloadDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (sign, names) in [('+', &self.added), ('~', &self.changed), ('-', &self.removed)] {
+            for name in names {
+                writeln!(f, "{} {}", sign, name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// The code of a function or class the script declared, as compared by ReloadDiff.
+// None for other values, including natives and host classes.
+pub fn definition(value: &LiteralValue) -> Option<String> {
+    match value {
+        LiteralValue::Function(function) => Some(format!("{:#}", function.declaration)),
+        LiteralValue::Class(class) => Some(format!("{:#}", Stmt::Class(class.declaration()?.clone()))),
+        _ => None,
+    }
+}
@@ -0,0 +1,60 @@
+use std::fmt::Write;
+use std::time::Duration;
+
+use crate::diagnostic::{Diagnostic, Reporter};
+
+// Summary of one command, for tools that wrap the interpreter (`--report=<path>`)
+#[derive(Debug, Clone, Default)]
+pub struct RunReport {
+    pub exit_code: i32,
+    pub diagnostics: Vec<Diagnostic>,
+    pub output_bytes: usize,
+    pub error_output_bytes: usize,
+    pub statements: usize, // Statements executed
+    pub peak_call_depth: usize,
+    pub phases: Vec<(&'static str, Duration)>, // In the order they ran; phases that didn't run are left out
+}
+
+impl RunReport {
+    // A phase that runs again (once per file) adds to its earlier time
+    pub fn phase(&mut self, name: &'static str, elapsed: Duration) {
+        match self.phases.iter_mut().find(|(phase, _)| *phase == name) {
+            Some((_, total)) => *total += elapsed,
+            None => self.phases.push((name, elapsed)),
+        }
+    }
+
+    // Per-phase durations for `--timings`, in the style of the `--stats` report
+    pub fn timings(&self) -> String {
+        let mut out = String::new();
+        for (name, elapsed) in &self.phases {
+            writeln!(out, "{:>12.3}  {} ms", elapsed.as_secs_f64() * 1000.0, name).unwrap();
+        }
+        let total: Duration = self.phases.iter().map(|(_, elapsed)| *elapsed).sum();
+        writeln!(out, "{:>12.3}  total ms", total.as_secs_f64() * 1000.0).unwrap();
+        out
+    }
+
+    // Diagnostics are in the same form as `--diagnostics=json`; times are in milliseconds
+    pub fn to_json(&self, reporter: &Reporter) -> String {
+        let mut phases = String::new();
+        for (i, (name, elapsed)) in self.phases.iter().enumerate() {
+            if i > 0 {
+                phases.push(',');
+            }
+            write!(phases, "\"{}\":{}", name, elapsed.as_secs_f64() * 1000.0).unwrap();
+        }
+        let total: Duration = self.phases.iter().map(|(_, elapsed)| *elapsed).sum();
+        format!(
+            "{{\"exit_code\":{},\"diagnostics\":{},\"output_bytes\":{},\"error_output_bytes\":{},\"statements\":{},\"peak_call_depth\":{},\"phases_ms\":{{{}}},\"total_ms\":{}}}",
+            self.exit_code,
+            reporter.to_json(&self.diagnostics),
+            self.output_bytes,
+            self.error_output_bytes,
+            self.statements,
+            self.peak_call_depth,
+            phases,
+            total.as_secs_f64() * 1000.0
+        )
+    }
+}
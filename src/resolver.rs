@@ -0,0 +1,563 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::diagnostic::{Diagnostic, Severity, Stage, DEFAULT_MAX_ERRORS};
+use crate::expr::{Expr, LocalSlot};
+use crate::options::LanguageLevel;
+use crate::stmt::{FunctionDecl, Stmt};
+use crate::token::{Token, TokenType};
+
+// Optional warnings reported by the resolver
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lint {
+    Shadowing,       // A local declaration hides a variable from an enclosing scope
+    UnusedParameter, // A function parameter is never read
+}
+
+impl Lint {
+    pub const ALL: [Lint; 2] = [Lint::Shadowing, Lint::UnusedParameter];
+
+    // Name used in `--warn=<name>` CLI flags
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::Shadowing => "shadowing",
+            Lint::UnusedParameter => "unused-parameter",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::ALL.into_iter().find(|lint| lint.name() == name)
+    }
+}
+
+// Which lints are enabled, and whether they are reported as errors. Nothing is
+// enabled by default.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Lints {
+    pub shadowing: bool,
+    pub unused_parameter: bool,
+    pub deny_warnings: bool,
+}
+
+impl Lints {
+    pub fn enabled(&self, lint: Lint) -> bool {
+        match lint {
+            Lint::Shadowing => self.shadowing,
+            Lint::UnusedParameter => self.unused_parameter,
+        }
+    }
+
+    pub fn set(&mut self, lint: Lint, enabled: bool) {
+        match lint {
+            Lint::Shadowing => self.shadowing = enabled,
+            Lint::UnusedParameter => self.unused_parameter = enabled,
+        }
+    }
+}
+
+// Lints switched off by `// lox-ignore: <lint>, ...` comments (`all` for every lint).
+// A comment after code covers its own line; a comment on a line of its own covers
+// the next line.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Suppressions {
+    lines: HashMap<usize, Vec<Lint>>,
+}
+
+impl Suppressions {
+    // Read the COMMENT tokens of a scan (ScanOptions::comments). Unknown lint names
+    // are returned as warnings.
+    pub fn from_comments(tokens: &[Token]) -> (Suppressions, Vec<Diagnostic>) {
+        let mut suppressions = Suppressions::default();
+        let mut warnings = Vec::new();
+        for (i, comment) in tokens.iter().enumerate().filter(|(_, token)| token.token_type == TokenType::COMMENT) {
+            let after_code = i > 0 && tokens[i - 1].line == comment.line;
+            let line = if after_code { comment.line } else { comment.line + 1 };
+            let Some(names) = comment.lexeme.trim_start_matches('/').trim().strip_prefix("lox-ignore:") else {
+                continue;
+            };
+            for name in names.split(',').map(str::trim) {
+                let lints = match (name, Lint::from_name(name)) {
+                    ("all", _) => Lint::ALL.to_vec(),
+                    (_, Some(lint)) => vec![lint],
+                    (_, None) => {
+                        let message = format!("Unknown lint '{}' in lox-ignore comment.", name);
+                        warnings.push(Diagnostic::warning(Stage::Resolve, message, comment.line).at(comment));
+                        continue;
+                    }
+                };
+                suppressions.lines.entry(line).or_default().extend(lints);
+            }
+        }
+        (suppressions, warnings)
+    }
+
+    pub fn covers(&self, lint: Lint, line: usize) -> bool {
+        self.lines.get(&line).is_some_and(|lints| lints.contains(&lint))
+    }
+}
+
+// What a name denotes, as far as the resolver can tell statically
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    Parameter,
+    Local,  // Variable declared in a block or function body
+    Global, // Top-level variable, or a name with no declaration (natives, typos)
+    Function,
+    Class,
+    Method,
+    Property, // Field declarations and `object.name` accesses
+}
+
+impl SymbolKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            SymbolKind::Parameter => "parameter",
+            SymbolKind::Local => "local",
+            SymbolKind::Global => "global",
+            SymbolKind::Function => "function",
+            SymbolKind::Class => "class",
+            SymbolKind::Method => "method",
+            SymbolKind::Property => "property",
+        }
+    }
+}
+
+// One occurrence of a name in the source: a declaration (`declaration` is the name
+// itself) or a use. Properties are looked up at runtime, so only their declarations
+// as fields or methods have a `declaration`.
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub name: Token,
+    pub kind: SymbolKind,
+    pub declaration: Option<Token>,
+}
+
+impl Reference {
+    pub fn is_declaration(&self) -> bool {
+        self.declaration.as_ref().is_some_and(|declaration| declaration.start == self.name.start)
+    }
+}
+
+// Kind of function body being resolved, for `return` checks
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Method,
+    Initializer,
+}
+
+// A local variable as seen by the resolver
+struct Binding {
+    defined: bool,   // False while the variable's own initializer is being resolved
+    read: bool,
+    declaration: Token,
+    kind: SymbolKind,
+    slot: usize, // Position in its scope at runtime, in declaration order
+}
+
+// Static pass over the parsed program, run before interpretation, that reports scope
+// errors the runtime would otherwise accept silently, plus the enabled lints. The top
+// level may redefine a variable, as in the book.
+pub struct Resolver {
+    // One map per enclosing local scope
+    scopes: Vec<HashMap<String, Binding>>,
+    globals: HashSet<String>, // Declared so far, for the shadowing lint
+    // First top-level declaration of each global; uses may come before it
+    global_declarations: HashMap<String, (Token, SymbolKind)>,
+    references: Vec<Reference>,
+    current_function: FunctionType,
+    in_class: bool,
+    language: LanguageLevel,
+    lints: Lints,
+    suppressions: Suppressions,
+    diagnostics: Vec<Diagnostic>,
+    max_errors: usize,
+    suppressed: usize, // Errors past max_errors, counted but not kept
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Resolver {
+            scopes: Vec::new(),
+            globals: HashSet::new(),
+            global_declarations: HashMap::new(),
+            references: Vec::new(),
+            current_function: FunctionType::None,
+            in_class: false,
+            language: LanguageLevel::default(),
+            lints: Lints::default(),
+            suppressions: Suppressions::default(),
+            diagnostics: Vec::new(),
+            max_errors: DEFAULT_MAX_ERRORS,
+            suppressed: 0,
+        }
+    }
+
+    pub fn with_lints(lints: Lints) -> Self {
+        Resolver {
+            lints,
+            ..Resolver::new()
+        }
+    }
+
+    // At LanguageLevel::Book, `return` at the top level is an error, as in the book.
+    // The extended language allows it, to end the script early.
+    pub fn set_language(&mut self, language: LanguageLevel) {
+        self.language = language;
+    }
+
+    pub fn set_suppressions(&mut self, suppressions: Suppressions) {
+        self.suppressions = suppressions;
+    }
+
+    // Errors kept before the rest are only counted, in a closing note
+    pub fn set_max_errors(&mut self, max_errors: usize) {
+        self.max_errors = max_errors;
+    }
+
+    pub fn resolve(&mut self, statements: &[Stmt]) {
+        let suppressed = self.suppressed;
+        self.resolve_stmts(statements);
+        if self.suppressed > suppressed {
+            self.diagnostics.push(Diagnostic::suppressed(Stage::Resolve, self.suppressed - suppressed, self.max_errors));
+        }
+    }
+
+    fn resolve_stmts(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            self.resolve_stmt(stmt);
+        }
+    }
+
+    pub fn has_error(&self) -> bool {
+        self.suppressed > 0 || self.diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    // Every name occurrence resolved so far, in source order. Uses of globals are
+    // linked to the global's first top-level declaration.
+    pub fn references(&self) -> Vec<Reference> {
+        let mut references: Vec<Reference> = self
+            .references
+            .iter()
+            .map(|reference| match (&reference.declaration, self.global_declarations.get(&reference.name.lexeme)) {
+                (None, Some((declaration, kind))) if reference.kind == SymbolKind::Global => Reference {
+                    kind: *kind,
+                    declaration: Some(declaration.clone()),
+                    ..reference.clone()
+                },
+                _ => reference.clone(),
+            })
+            .collect();
+        references.sort_by_key(|reference| reference.name.start);
+        references
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expression(expr) | Stmt::Print(expr) => self.resolve_expr(expr),
+            Stmt::Var { name, initializer } => {
+                let kind = if self.scopes.is_empty() { SymbolKind::Global } else { SymbolKind::Local };
+                self.declare(name, kind);
+                if let Some(initializer) = initializer {
+                    self.resolve_expr(initializer);
+                }
+                self.define(name);
+            }
+            Stmt::Block(statements) => {
+                self.scopes.push(HashMap::new());
+                self.resolve_stmts(statements);
+                self.end_scope();
+            }
+            Stmt::ForIn { name, iterable, body, .. } => {
+                self.resolve_expr(iterable);
+                // The loop variable lives in a scope of its own around the body
+                self.scopes.push(HashMap::new());
+                self.declare(name, SymbolKind::Local);
+                self.define(name);
+                self.resolve_stmt(body);
+                self.end_scope();
+            }
+            Stmt::Function(declaration) => {
+                // Defined before the body so the function can call itself
+                self.declare(&declaration.name, SymbolKind::Function);
+                self.define(&declaration.name);
+                self.resolve_function(declaration, FunctionType::Function);
+            }
+            Stmt::Class(declaration) => {
+                self.declare(&declaration.name, SymbolKind::Class);
+                self.define(&declaration.name);
+
+                let enclosing_class = std::mem::replace(&mut self.in_class, true);
+                // Methods close over a scope that defines `this`
+                let mut scope = HashMap::new();
+                scope.insert(
+                    "this".to_string(),
+                    Binding {
+                        defined: true,
+                        read: true,
+                        declaration: declaration.name.clone(),
+                        kind: SymbolKind::Local,
+                        slot: 0,
+                    },
+                );
+                self.scopes.push(scope);
+                for field in &declaration.fields {
+                    self.record(&field.name, SymbolKind::Property, Some(&field.name));
+                    if let Some(initializer) = &field.initializer {
+                        self.resolve_expr(initializer);
+                    }
+                }
+                for method in &declaration.methods {
+                    if method.getter && method.name.lexeme == "init" {
+                        self.error(&method.name, "An initializer can't be a getter.");
+                    }
+                    self.record(&method.name, SymbolKind::Method, Some(&method.name));
+                    let kind = if method.name.lexeme == "init" {
+                        FunctionType::Initializer
+                    } else {
+                        FunctionType::Method
+                    };
+                    self.resolve_function(method, kind);
+                }
+                // In a class method, `this` is the class
+                for method in &declaration.class_methods {
+                    self.record(&method.name, SymbolKind::Method, Some(&method.name));
+                    self.resolve_function(method, FunctionType::Method);
+                }
+                self.scopes.pop();
+                self.in_class = enclosing_class;
+            }
+            Stmt::Defer { keyword, expression } => {
+                if self.scopes.is_empty() {
+                    self.error(keyword, "Can't use 'defer' outside of a block.");
+                }
+                self.resolve_expr(expression);
+            }
+            Stmt::Return { keyword, value } => {
+                if self.current_function == FunctionType::None && self.language == LanguageLevel::Book {
+                    self.error(keyword, "Can't return from top-level code.");
+                }
+                if let Some(value) = value {
+                    if self.current_function == FunctionType::Initializer {
+                        self.error(keyword, "Can't return a value from an initializer.");
+                    }
+                    self.resolve_expr(value);
+                }
+            }
+        }
+    }
+
+    fn resolve_function(&mut self, declaration: &FunctionDecl, kind: FunctionType) {
+        let enclosing_function = std::mem::replace(&mut self.current_function, kind);
+        self.scopes.push(HashMap::new());
+        for param in &declaration.params {
+            self.declare(param, SymbolKind::Parameter);
+            self.define(param);
+        }
+        self.resolve_stmts(&declaration.body);
+        self.end_scope();
+        self.current_function = enclosing_function;
+    }
+
+    // Pop the innermost scope, reporting parameters that were never read
+    fn end_scope(&mut self) {
+        let Some(scope) = self.scopes.pop() else {
+            return;
+        };
+        let mut unused: Vec<&Token> = scope
+            .values()
+            .filter(|binding| !binding.read && binding.kind == SymbolKind::Parameter)
+            .map(|binding| &binding.declaration)
+            .collect();
+        unused.sort_by_key(|param| param.start);
+        for param in unused {
+            let message = format!("Parameter '{}' is never read.", param.lexeme);
+            self.lint(Lint::UnusedParameter, param, message);
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Variable(name, slot) => {
+                let in_own_initializer = self
+                    .scopes
+                    .last()
+                    .and_then(|scope| scope.get(&name.lexeme))
+                    .is_some_and(|binding| !binding.defined);
+                if in_own_initializer {
+                    self.error(name, "Can't read local variable in its own initializer.");
+                }
+                if let Some(binding) = self.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(&name.lexeme)) {
+                    binding.read = true;
+                }
+                self.resolve_local(&name.lexeme, slot);
+                self.record_use(name);
+            }
+            Expr::Assign { name, value, slot } => {
+                self.resolve_expr(value);
+                self.resolve_local(&name.lexeme, slot);
+                self.record_use(name);
+            }
+            Expr::Binary { left, right, .. } => {
+                self.resolve_expr(left);
+                self.resolve_expr(right);
+            }
+            Expr::Call { callee, arguments, .. } => {
+                self.resolve_expr(callee);
+                for argument in arguments {
+                    self.resolve_expr(argument);
+                }
+            }
+            Expr::Array { elements, .. } => {
+                for element in elements {
+                    self.resolve_expr(element);
+                }
+            }
+            Expr::Index { object, index, .. } => {
+                self.resolve_expr(object);
+                self.resolve_expr(index);
+            }
+            Expr::Slice { object, start, end, .. } => {
+                self.resolve_expr(object);
+                for bound in start.iter().chain(end) {
+                    self.resolve_expr(bound);
+                }
+            }
+            Expr::SetIndex { object, index, value, .. } => {
+                self.resolve_expr(object);
+                self.resolve_expr(index);
+                self.resolve_expr(value);
+            }
+            Expr::Get { object, name } => {
+                self.resolve_expr(object);
+                self.record(name, SymbolKind::Property, None);
+            }
+            Expr::Set { object, name, value } => {
+                self.resolve_expr(object);
+                self.record(name, SymbolKind::Property, None);
+                self.resolve_expr(value);
+            }
+            Expr::This(keyword, slot) => {
+                if !self.in_class {
+                    self.error(keyword, "Can't use 'this' outside of a class.");
+                }
+                self.resolve_local("this", slot);
+            }
+            Expr::Match { subject, arms, .. } => {
+                self.resolve_expr(subject);
+                // Each arm's bindings live in a scope of their own around its body
+                for arm in arms {
+                    self.scopes.push(HashMap::new());
+                    for name in arm.pattern.bindings() {
+                        self.declare(name, SymbolKind::Local);
+                        self.define(name);
+                    }
+                    self.resolve_expr(&arm.body);
+                    self.end_scope();
+                }
+            }
+            Expr::Grouping(inner) | Expr::Unary { right: inner, .. } => self.resolve_expr(inner),
+            Expr::Literal { .. } => {}
+        }
+    }
+
+    fn declare(&mut self, name: &Token, kind: SymbolKind) {
+        self.record(name, kind, Some(name));
+        let Some((scope, enclosing)) = self.scopes.split_last_mut() else {
+            self.globals.insert(name.lexeme.clone());
+            self.global_declarations.entry(name.lexeme.clone()).or_insert_with(|| (name.clone(), kind));
+            return;
+        };
+        if scope.contains_key(&name.lexeme) {
+            self.error(name, "Already a variable with this name in this scope.");
+            return;
+        }
+        let shadows = enclosing.iter().any(|outer| outer.contains_key(&name.lexeme)) || self.globals.contains(&name.lexeme);
+        let slot = scope.len();
+        scope.insert(
+            name.lexeme.clone(),
+            Binding {
+                defined: false,
+                read: false,
+                declaration: name.clone(),
+                kind,
+                slot,
+            },
+        );
+        if shadows {
+            let message = format!("Variable '{}' shadows a variable in an enclosing scope.", name.lexeme);
+            self.lint(Lint::Shadowing, name, message);
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(binding) = self.scopes.last_mut().and_then(|scope| scope.get_mut(&name.lexeme)) {
+            binding.defined = true;
+        }
+    }
+
+    fn record(&mut self, name: &Token, kind: SymbolKind, declaration: Option<&Token>) {
+        self.references.push(Reference {
+            name: name.clone(),
+            kind,
+            declaration: declaration.cloned(),
+        });
+    }
+
+    // Point a local variable's use at its scope and slot, or mark it global when no
+    // enclosing scope declares it yet
+    fn resolve_local(&self, name: &str, slot: &LocalSlot) {
+        let local = self.scopes.iter().rev().enumerate().find_map(|(depth, scope)| Some((depth, scope.get(name)?.slot)));
+        match local {
+            Some((depth, index)) => slot.set(depth, index),
+            None => slot.set_global(),
+        }
+    }
+
+    // A variable read or assignment: bound to the innermost local declaration, or to a
+    // global linked up by `references`
+    fn record_use(&mut self, name: &Token) {
+        let local = self.scopes.iter().rev().find_map(|scope| scope.get(&name.lexeme));
+        let (kind, declaration) = match local {
+            Some(binding) => (binding.kind, Some(binding.declaration.clone())),
+            None => (SymbolKind::Global, None),
+        };
+        self.record(name, kind, declaration.as_ref());
+    }
+
+    fn error(&mut self, name: &Token, message: &str) {
+        self.report(Diagnostic::error(Stage::Resolve, message, name.line).near(name));
+    }
+
+    fn report(&mut self, diagnostic: Diagnostic) {
+        let errors = self.diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Error).count();
+        if diagnostic.severity == Severity::Error && errors >= self.max_errors {
+            self.suppressed += 1;
+        } else {
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    // Report an enabled lint as a warning, or as an error with `deny_warnings`
+    fn lint(&mut self, lint: Lint, name: &Token, message: String) {
+        if !self.lints.enabled(lint) || self.suppressions.covers(lint, name.line) {
+            return;
+        }
+        let diagnostic = if self.lints.deny_warnings {
+            Diagnostic::error(Stage::Resolve, message, name.line)
+        } else {
+            Diagnostic::warning(Stage::Resolve, message, name.line)
+        };
+        self.report(diagnostic.at(name));
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
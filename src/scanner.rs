@@ -1,8 +1,27 @@
 use std::collections::HashMap;
 
+use crate::diagnostic::{Diagnostic, Stage};
+use crate::number;
+use crate::options::LanguageLevel;
 use crate::token::{Token, TokenType};
 use log::{debug, info};  // Import log macros
 
+// Scanner settings; the defaults scan exactly like the book
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    // Treat a newline inside a string as the end of an unterminated string and resume
+    // scanning on the next line, so later lines still produce tokens and errors.
+    // This rules out multi-line strings, which otherwise swallow the rest of the file.
+    pub recover_strings: bool,
+    // Stop scanning with an error once this many tokens have been produced
+    pub max_tokens: Option<usize>,
+    // Reject string literals longer than this many bytes
+    pub max_string_length: Option<usize>,
+    // Produce COMMENT tokens instead of skipping comments, for tools that read them
+    // (lint suppressions). The parser drops them.
+    pub comments: bool,
+}
+
 pub struct Scanner {
     source: String,
     tokens: Vec<Token>,
@@ -10,7 +29,10 @@ pub struct Scanner {
     current: usize,
     line: usize, // Track the current line number
     error_occurred: bool,
+    diagnostics: Vec<Diagnostic>,
     keywords: HashMap<String, TokenType>,
+    options: ScanOptions,
+    language: LanguageLevel,
 }
 
 impl Scanner {
@@ -40,16 +62,35 @@ impl Scanner {
             current: 0,
             line: 1,
             error_occurred: false,
+            diagnostics: Vec::new(),
             keywords,  // Initialize the keywords map
+            options: ScanOptions::default(),
+            language: LanguageLevel::default(),
         }
     }
 
+    pub fn set_options(&mut self, options: ScanOptions) {
+        self.options = options;
+    }
+
+    // At LanguageLevel::Book, the characters only the extensions use are unexpected
+    pub fn set_language(&mut self, language: LanguageLevel) {
+        self.language = language;
+    }
+
     /// Main loop for scanning tokens.
     pub fn scan_tokens(&mut self) {
         // Continue scanning tokens until scan_token returns None
-        while self.scan_token().is_some() {}
+        while self.scan_token().is_some() {
+            if let Some(max) = self.options.max_tokens.filter(|max| self.tokens.len() > *max) {
+                self.tokens.truncate(max);
+                self.error_message(&format!("Too many tokens (limit is {}).", max));
+                break;
+            }
+        }
         info!("Reached end of file. Adding EOF token.");
-        self.tokens.push(Token::new(TokenType::EOF, String::new(), None, self.line));
+        self.start = self.current;
+        self.push_token(Token::new(TokenType::EOF, String::new(), None, self.line));
     }
 
     /// Scans the next token, returning `Some(())` if a token was found, or `None` if end of file is reached.
@@ -60,13 +101,19 @@ impl Scanner {
         debug!("Scanning token at line {}, character: '{}'", self.line, c);
 
         match c {
+            '[' | ']' | ':' if self.language == LanguageLevel::Book => {
+                self.error_message(&format!("Unexpected character: {}", c));
+            }
             '(' => self.add_token(TokenType::LEFT_PAREN),
             ')' => self.add_token(TokenType::RIGHT_PAREN),
             '{' => self.add_token(TokenType::LEFT_BRACE),
             '}' => self.add_token(TokenType::RIGHT_BRACE),
+            '[' => self.add_token(TokenType::LEFT_BRACKET),
+            ']' => self.add_token(TokenType::RIGHT_BRACKET),
             '*' => self.add_token(TokenType::STAR),
             '.' => self.add_token(TokenType::DOT),
             ',' => self.add_token(TokenType::COMMA),
+            ':' => self.add_token(TokenType::COLON),
             '+' => self.add_token(TokenType::PLUS),
             '-' => self.add_token(TokenType::MINUS),
             ';' => self.add_token(TokenType::SEMICOLON),
@@ -102,8 +149,16 @@ impl Scanner {
             }
             '/' =>{
                 if self.match_next('/') {
-                    self.skip_to_end_of_line(); // Skip comment to the end of the line
-                    debug!("Skipped comment to end of line.");
+                    if self.options.comments {
+                        while self.peek().is_some_and(|c| c != '\n') {
+                            self.advance();
+                        }
+                        self.add_token(TokenType::COMMENT);
+                    }
+                    else {
+                        self.skip_to_end_of_line(); // Skip comment to the end of the line
+                        debug!("Skipped comment to end of line.");
+                    }
                 }
                 else{
                     self.add_token(TokenType::SLASH);
@@ -123,7 +178,7 @@ impl Scanner {
                 debug!("Space character encountered, no action taken");
             }
             _ => {
-                if c.is_digit(10) {
+                if c.is_ascii_digit() {
                     self.scan_number(); // Handle number literals
                 } 
                 else if c.is_alphabetic() || c == '_' {
@@ -152,7 +207,19 @@ impl Scanner {
     fn add_token(&mut self, token_type: TokenType) {
         let text = self.source[self.start..self.current].to_string();
         debug!("Adding token: {:?}, lexeme: {}", token_type, text);
-        self.tokens.push(Token::new(token_type, text, None, self.line));
+        self.push_token(Token::new(token_type, text, None, self.line));
+    }
+
+    // Record a token positioned at the current lexeme
+    fn push_token(&mut self, token: Token) {
+        let column = self.column_at(self.start);
+        self.tokens.push(token.at(self.start, column));
+    }
+
+    // 1-based column, in characters, of a byte offset
+    fn column_at(&self, offset: usize) -> usize {
+        let line_start = self.source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        self.source[line_start..offset].chars().count() + 1
     }
 
     fn is_at_end(&self) -> bool {
@@ -178,7 +245,7 @@ impl Scanner {
     fn add_token_with_literal(&mut self, token_type: TokenType, literal: Option<String>) {
         let text = self.source[self.start..self.current].to_string();
         debug!("Adding token with literal: {:?}, lexeme: {}, literal: {:?}", token_type, text, literal);
-        self.tokens.push(Token::new(token_type, text, literal, self.line));
+        self.push_token(Token::new(token_type, text, literal, self.line));
     }
 
     /// Scan an identifier or reserved word
@@ -196,7 +263,7 @@ impl Scanner {
 
         // Check if it's a reserved word
         if let Some(token_type) = self.keywords.get(&lexeme) {
-            self.add_token(token_type.clone());
+            self.add_token(*token_type);
         } else {
             self.add_token(TokenType::IDENTIFIER);
         }
@@ -206,7 +273,7 @@ impl Scanner {
     fn scan_number(&mut self) {
         // Consume digits for the integer part
         while let Some(c) = self.peek() {
-            if c.is_digit(10) {
+            if c.is_ascii_digit() {
                 self.advance();
             } else {
                 break;
@@ -214,14 +281,12 @@ impl Scanner {
         }
     
         // Check if there's a fractional part (e.g., 1234.5678)
-        let mut has_fractional_part = false;
         if let Some('.') = self.peek() {
             if let Some(next) = self.peek_next() {
-                if next.is_digit(10) {
+                if next.is_ascii_digit() {
                     self.advance(); // Consume the '.'
-                    has_fractional_part = true;
                     while let Some(c) = self.peek() {
-                        if c.is_digit(10) {
+                        if c.is_ascii_digit() {
                             self.advance(); // Consume the rest of the number
                         } else {
                             break;
@@ -234,24 +299,9 @@ impl Scanner {
         // Extract the lexeme
         let lexeme = self.source[self.start..self.current].to_string();
     
-        // Parse the literal value as f64
-        let literal_value: f64 = lexeme.parse::<f64>().unwrap();
-    
-        // If there is a fractional part, check if it's all zeros
-        let literal_str = if has_fractional_part && lexeme.contains('.') {
-            let parts: Vec<&str> = lexeme.split('.').collect();
-            let fractional_part = parts[1];
-    
-            // If the fractional part is all zeros, treat it as an integer (e.g., "200.00" -> "200.0")
-            if fractional_part.chars().all(|c| c == '0') {
-                format!("{:.1}", literal_value)  // Format as "x.0"
-            } else {
-                literal_value.to_string()  // Otherwise, keep the full precision
-            }
-        } else {
-            // If there's no fractional part, it's an integer
-            format!("{:.1}", literal_value)
-        };
+        // The literal is the value in canonical form, e.g. "200.00" -> "200.0"
+        let literal_value = number::parse(&lexeme).unwrap();
+        let literal_str = number::canonical(literal_value);
     
         // Add the token with the original lexeme and formatted literal
         self.add_token_with_literal(TokenType::NUMBER, Some(literal_str));
@@ -275,15 +325,24 @@ impl Scanner {
             if c == '"' {
                 // Closing quote found, add the string token
                 let value_with_quotes = self.source[self.start.. self.current].to_string();
+                let length = self.current - self.start - 2;
+                if let Some(max) = self.options.max_string_length.filter(|max| length > *max) {
+                    self.error_message(&format!("String literal too long (limit is {} bytes).", max));
+                    return;
+                }
                 let value_without_quotes = self.source[self.start + 1..self.current - 1].to_string(); // Exclude quotes
                 debug!("Adding string token, lexeme: {}, literal: {}", value_with_quotes.clone(), value_without_quotes.clone());
-                self.tokens.push(Token::new(
+                self.push_token(Token::new(
                     TokenType::STRING,
                     value_with_quotes.clone(),    // Lexeme (string with quotes)
                     Some(value_without_quotes),  // Literal value (the actual string content)
                     self.line,     
                 ));
                 return;
+            } else if c == '\n' && self.options.recover_strings {
+                // Leave the newline to be scanned normally
+                self.current -= 1;
+                break;
             } else if c == '\n' {
                 self.line += 1; // Handle multi-line strings
             }
@@ -304,9 +363,13 @@ impl Scanner {
         }
     }
 
-    /// Error reporting for specific messages
+    /// Error reporting for specific messages, located at the current lexeme
     fn error_message(&mut self, message: &str) {
-        eprintln!("[line {}] Error: {}", self.line, message);
+        self.diagnostics.push(Diagnostic {
+            column: Some(self.column_at(self.start)),
+            span: Some((self.start, self.current)),
+            ..Diagnostic::error(Stage::Scan, message, self.line)
+        });
         self.error_occurred = true;
     }
 
@@ -314,7 +377,82 @@ impl Scanner {
         self.error_occurred
     }
 
+    /// Lexical errors found so far, in source order
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
     pub fn get_tokens(&self) -> &Vec<Token> {
         &self.tokens
     }
 }
+
+// Source text rebuilt from tokens alone: each lexeme at its line and column, with
+// newlines and spaces in between. Exact when the tokens were scanned with
+// ScanOptions::comments and the source has no tabs, carriage returns or trailing
+// spaces outside tokens; check_reconstruction says whether it is for a given source.
+pub fn source_from_tokens(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    let (mut line, mut column) = (1, 1);
+    for token in tokens {
+        // A token's line is the one it ends on, which differs for multi-line strings
+        let newlines = token.lexeme.matches('\n').count();
+        while line < token.line.saturating_sub(newlines) {
+            out.push('\n');
+            line += 1;
+            column = 1;
+        }
+        while column < token.column {
+            out.push(' ');
+            column += 1;
+        }
+        out.push_str(&token.lexeme);
+        match token.lexeme.rfind('\n') {
+            Some(last) => {
+                line += newlines;
+                column = token.lexeme[last + 1..].chars().count() + 1;
+            }
+            None => column += token.lexeme.chars().count(),
+        }
+    }
+    out
+}
+
+// Check that the tokens scanned from `source` rebuild it exactly with
+// source_from_tokens, so tools can edit tokens and write the file back without
+// losing anything. The error names the first place that can't be rebuilt: text in
+// no token (a comment scanned without ScanOptions::comments, an unexpected
+// character) or whitespace that isn't spaces before a token or newlines.
+pub fn check_reconstruction(source: &str, tokens: &[Token]) -> Result<(), String> {
+    let mut end = 0;
+    for token in tokens {
+        if token.start < end || source.get(token.start..token.start + token.len()) != Some(token.lexeme.as_str()) {
+            return Err(format!("[line {}] Token '{}' doesn't match the source.", token.line, token.lexeme));
+        }
+        if let Some(offset) = source[end..token.start].find(|c: char| !c.is_whitespace()).map(|i| end + i) {
+            return Err(format!(
+                "[line {}] Text outside any token: '{}'.",
+                line_at(source, offset),
+                source[offset..].chars().next().unwrap()
+            ));
+        }
+        end = token.start + token.len();
+    }
+    if let Some(offset) = source[end..].find(|c: char| !c.is_whitespace()).map(|i| end + i) {
+        return Err(format!("[line {}] Text after the last token.", line_at(source, offset)));
+    }
+
+    let rebuilt = source_from_tokens(tokens);
+    match source.bytes().zip(rebuilt.bytes()).position(|(a, b)| a != b) {
+        None if source.len() == rebuilt.len() => Ok(()),
+        difference => {
+            let offset = difference.unwrap_or(source.len().min(rebuilt.len()));
+            let message = "Whitespace can't be rebuilt from the tokens (a tab, carriage return or trailing space).";
+            Err(format!("[line {}] {}", line_at(source, offset), message))
+        }
+    }
+}
+
+fn line_at(source: &str, offset: usize) -> usize {
+    source.as_bytes()[..offset].iter().filter(|&&byte| byte == b'\n').count() + 1
+}
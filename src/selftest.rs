@@ -0,0 +1,47 @@
+use crate::conformance::Check;
+
+// One CodeCrafters stage, with fixture programs bundled from fixtures/selftest. Each
+// runs without errors; `<name>.out` holds what it must print.
+#[derive(Debug, Clone, Copy)]
+pub struct Stage {
+    pub name: &'static str,
+    pub fixtures: &'static [Check],
+}
+
+macro_rules! fixture {
+    ($command:literal, $name:literal) => {
+        Check {
+            stage: $name,
+            command: $command,
+            source: include_str!(concat!("../fixtures/selftest/", $name, ".lox")),
+            stdout: include_str!(concat!("../fixtures/selftest/", $name, ".out")),
+            stderr: "",
+            exit_code: 0,
+        }
+    };
+}
+
+// In the order the tester goes through them: a stage only makes sense once the ones
+// before it pass
+pub const STAGES: &[Stage] = &[
+    Stage { name: "tokenize punctuation", fixtures: &[fixture!("tokenize", "tokenize_punctuation")] },
+    Stage { name: "tokenize strings", fixtures: &[fixture!("tokenize", "tokenize_strings")] },
+    Stage { name: "tokenize numbers", fixtures: &[fixture!("tokenize", "tokenize_numbers")] },
+    Stage { name: "tokenize identifiers", fixtures: &[fixture!("tokenize", "tokenize_identifiers")] },
+    Stage {
+        name: "parse expressions",
+        fixtures: &[fixture!("parse", "parse_arithmetic"), fixture!("parse", "parse_comparison")],
+    },
+    Stage { name: "evaluate unary", fixtures: &[fixture!("evaluate", "evaluate_unary")] },
+    Stage {
+        name: "evaluate binary",
+        fixtures: &[
+            fixture!("evaluate", "evaluate_arithmetic"),
+            fixture!("evaluate", "evaluate_strings"),
+            fixture!("evaluate", "evaluate_comparison"),
+        ],
+    },
+    Stage { name: "run statements", fixtures: &[fixture!("run", "run_statements")] },
+    Stage { name: "run functions", fixtures: &[fixture!("run", "run_functions")] },
+    Stage { name: "run classes", fixtures: &[fixture!("run", "run_classes")] },
+];
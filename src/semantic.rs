@@ -0,0 +1,117 @@
+use crate::resolver::{Reference, SymbolKind};
+use crate::token::{Token, TokenType};
+
+// A token and the class an editor colors it by: a lexical class such as "keyword"
+// or "string", or for a resolved name its SymbolKind ("parameter", "class", ...)
+#[derive(Debug, Clone)]
+pub struct Highlight {
+    pub token: Token,
+    pub class: &'static str,
+    pub declaration: bool, // The name is being declared here
+}
+
+// Classify every token but EOF. Identifiers found in `references` get their symbol
+// kind; the rest are plain "identifier"s, so passing no references gives lexical
+// highlighting.
+pub fn highlight(tokens: &[Token], references: &[Reference]) -> Vec<Highlight> {
+    let mut references = references.iter().peekable();
+    let mut highlights = Vec::with_capacity(tokens.len());
+    for token in tokens.iter().filter(|token| token.token_type != TokenType::EOF) {
+        while references.next_if(|reference| reference.name.start < token.start).is_some() {}
+        let reference = references.next_if(|reference| reference.name.start == token.start);
+        let class = match (token.token_type, reference) {
+            (TokenType::IDENTIFIER, Some(reference)) => reference.kind.name(),
+            (TokenType::IDENTIFIER, None) => "identifier",
+            (TokenType::STRING, _) => "string",
+            (TokenType::NUMBER, _) => "number",
+            (token_type, _) if token_type.is_keyword() => "keyword",
+            (token_type, _) if token_type.is_operator() => "operator",
+            _ => "punctuation",
+        };
+        highlights.push(Highlight {
+            token: token.clone(),
+            class,
+            declaration: reference.is_some_and(Reference::is_declaration),
+        });
+    }
+    highlights
+}
+
+// Legend of lsp_semantic_tokens, to announce in the server's capabilities
+pub const LSP_TOKEN_TYPES: [&str; 6] = ["parameter", "variable", "function", "class", "method", "property"];
+pub const LSP_TOKEN_MODIFIERS: [&str; 1] = ["declaration"];
+
+fn lsp_token_type(kind: SymbolKind) -> u32 {
+    match kind {
+        SymbolKind::Parameter => 0,
+        SymbolKind::Local | SymbolKind::Global => 1,
+        SymbolKind::Function => 2,
+        SymbolKind::Class => 3,
+        SymbolKind::Method => 4,
+        SymbolKind::Property => 5,
+    }
+}
+
+// The `data` of an LSP SemanticTokens response: five integers per resolved name
+// (line delta, start delta, length, type, modifiers), with positions in the UTF-16
+// code units the protocol counts in
+pub fn lsp_semantic_tokens(source: &str, references: &[Reference]) -> Vec<u32> {
+    let mut data = Vec::with_capacity(references.len() * 5);
+    let (mut previous_line, mut previous_start) = (0, 0);
+    for reference in references {
+        let name = &reference.name;
+        let line_start = source[..name.start].rfind('\n').map_or(0, |i| i + 1);
+        let line = name.line as u32 - 1;
+        let start = source[line_start..name.start].encode_utf16().count() as u32;
+        let delta_start = if line == previous_line { start - previous_start } else { start };
+        data.extend([
+            line - previous_line,
+            delta_start,
+            name.lexeme.encode_utf16().count() as u32,
+            lsp_token_type(reference.kind),
+            u32::from(reference.is_declaration()),
+        ]);
+        (previous_line, previous_start) = (line, start);
+    }
+    data
+}
+
+// The name occurrence covering a 1-based line and column
+pub fn reference_at(references: &[Reference], line: usize, column: usize) -> Option<&Reference> {
+    references.iter().find(|reference| {
+        let name = &reference.name;
+        name.line == line && (name.column..name.column + name.lexeme.chars().count()).contains(&column)
+    })
+}
+
+// Every occurrence of the symbol `target` names, its declaration included. Properties
+// and methods are matched by name, since which one a property access finds is only
+// known at runtime.
+pub fn find_references<'a>(references: &'a [Reference], target: &Reference) -> Vec<&'a Reference> {
+    references.iter().filter(|reference| same_symbol(reference, target)).collect()
+}
+
+// Where the symbol `target` names is declared: one place for a variable, function or
+// class, every field or method of that name for a property
+pub fn find_definitions<'a>(references: &'a [Reference], target: &Reference) -> Vec<&'a Reference> {
+    references
+        .iter()
+        .filter(|reference| reference.is_declaration() && same_symbol(reference, target))
+        .collect()
+}
+
+fn is_member(kind: SymbolKind) -> bool {
+    matches!(kind, SymbolKind::Property | SymbolKind::Method)
+}
+
+fn same_symbol(reference: &Reference, target: &Reference) -> bool {
+    if is_member(target.kind) {
+        return is_member(reference.kind) && reference.name.lexeme == target.name.lexeme;
+    }
+    match (&reference.declaration, &target.declaration) {
+        (Some(declaration), Some(target_declaration)) => declaration.start == target_declaration.start,
+        // Undeclared globals, such as natives, are the same if they have the same name
+        (None, None) => reference.name.lexeme == target.name.lexeme,
+        _ => false,
+    }
+}
@@ -0,0 +1,67 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+// Allocation counters behind `run --stats`. They are relaxed atomics, cheap enough to
+// stay on all the time, and count for the whole process rather than one interpreter.
+static ENVIRONMENTS: AtomicUsize = AtomicUsize::new(0);
+static VALUES_CLONED: AtomicUsize = AtomicUsize::new(0);
+static STATEMENTS: AtomicUsize = AtomicUsize::new(0);
+static LIVE_OBJECTS: AtomicUsize = AtomicUsize::new(0);
+static PEAK_LIVE_OBJECTS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn environment_created() {
+    ENVIRONMENTS.fetch_add(1, Ordering::Relaxed);
+    object_created();
+}
+
+pub fn value_cloned() {
+    VALUES_CLONED.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn statement_executed() {
+    STATEMENTS.fetch_add(1, Ordering::Relaxed);
+}
+
+// Heap objects are environments, arrays, maps and instances
+pub fn object_created() {
+    let live = LIVE_OBJECTS.fetch_add(1, Ordering::Relaxed) + 1;
+    PEAK_LIVE_OBJECTS.fetch_max(live, Ordering::Relaxed);
+}
+
+pub fn object_freed() {
+    LIVE_OBJECTS.fetch_sub(1, Ordering::Relaxed);
+}
+
+// Counter values at one point in time
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Stats {
+    pub environments: usize, // Environments allocated, including clones
+    pub values_cloned: usize,
+    pub statements: usize, // Statements executed
+    pub live_objects: usize,
+    pub peak_live_objects: usize,
+}
+
+impl Stats {
+    pub fn snapshot() -> Self {
+        Stats {
+            environments: ENVIRONMENTS.load(Ordering::Relaxed),
+            values_cloned: VALUES_CLONED.load(Ordering::Relaxed),
+            statements: STATEMENTS.load(Ordering::Relaxed),
+            live_objects: LIVE_OBJECTS.load(Ordering::Relaxed),
+            peak_live_objects: PEAK_LIVE_OBJECTS.load(Ordering::Relaxed),
+        }
+    }
+
+    // Human-readable report of a run that took `elapsed`
+    pub fn report(&self, elapsed: Duration) -> String {
+        let mut out = String::new();
+        writeln!(out, "{:>12}  environments allocated", self.environments).unwrap();
+        writeln!(out, "{:>12}  values cloned", self.values_cloned).unwrap();
+        writeln!(out, "{:>12}  statements executed", self.statements).unwrap();
+        writeln!(out, "{:>12}  peak live objects", self.peak_live_objects).unwrap();
+        writeln!(out, "{:>12.3}  total ms", elapsed.as_secs_f64() * 1000.0).unwrap();
+        out
+    }
+}
@@ -1,10 +1,168 @@
-use crate::expr::Expr;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::expr::{Expr, Node};
 use crate::token::Token;
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub enum Stmt {
     Expression(Expr),                   // An expression statement
     Print(Expr),                        // A print statement
     Var { name: Token, initializer: Option<Expr> },  // Variable declaration
     Block(Vec<Stmt>),
+    Function(Arc<FunctionDecl>),        // Function declaration, shared with the runtime function values
+    Class(Arc<ClassDecl>), // Shared with the runtime class, which initializes the fields
+    Return { keyword: Token, value: Option<Expr> },
+    // `for (var name in iterable) body`, with `name` bound anew on each pass
+    ForIn { keyword: Token, name: Token, iterable: Expr, body: Box<Stmt> },
+    // `defer expression;`: the expression is evaluated when the enclosing block exits
+    Defer { keyword: Token, expression: Expr },
+}
+
+impl Stmt {
+    // Source line the statement starts on (0 if it has no tokens, e.g. an empty block)
+    pub fn line(&self) -> usize {
+        match self {
+            Stmt::Expression(expr) | Stmt::Print(expr) => expr.line(),
+            Stmt::Var { name, .. } => name.line,
+            Stmt::Block(statements) => statements.first().map_or(0, Stmt::line),
+            Stmt::Function(declaration) => declaration.name.line,
+            Stmt::Class(declaration) => declaration.name.line,
+            Stmt::Return { keyword, .. } | Stmt::ForIn { keyword, .. } | Stmt::Defer { keyword, .. } => keyword.line,
+        }
+    }
+
+    // Byte range from the statement's first token in the tree to its last (None for an
+    // empty block). Leading keywords (`print`, `var`, `fun`, `class`), `;` and braces
+    // are not in the tree.
+    pub fn span(&self) -> Option<(usize, usize)> {
+        match self {
+            Stmt::Expression(expr) | Stmt::Print(expr) => Some(expr.span()),
+            Stmt::Var { name, initializer } => Some(through(name, initializer.as_ref().map(Expr::span))),
+            Stmt::Block(statements) => {
+                let start = statements.iter().find_map(Stmt::span)?.0;
+                let end = statements.iter().rev().find_map(Stmt::span)?.1;
+                Some((start, end))
+            }
+            Stmt::Function(declaration) => Some(declaration.span()),
+            Stmt::Class(declaration) => Some(declaration.span()),
+            Stmt::Return { keyword, value } => Some(through(keyword, value.as_ref().map(Expr::span))),
+            Stmt::ForIn { keyword, iterable, body, .. } => Some(through(keyword, body.span().or(Some(iterable.span())))),
+            Stmt::Defer { keyword, expression } => Some(through(keyword, Some(expression.span()))),
+        }
+    }
+}
+
+impl FunctionDecl {
+    pub fn span(&self) -> (usize, usize) {
+        let params = self.params.last().map(Token::span);
+        let body = self.body.iter().rev().find_map(Stmt::span);
+        through(&self.name, body.or(params))
+    }
+}
+
+impl ClassDecl {
+    pub fn span(&self) -> (usize, usize) {
+        let fields = self.fields.iter().map(FieldDecl::span);
+        let methods = self.methods.iter().chain(&self.class_methods).map(|method| method.span());
+        through(&self.name, fields.chain(methods).max_by_key(|span| span.1))
+    }
+}
+
+impl FieldDecl {
+    pub fn span(&self) -> (usize, usize) {
+        through(&self.name, self.initializer.as_ref().map(Expr::span))
+    }
+}
+
+// From a token to the end of what follows it, if anything
+fn through(first: &Token, rest: Option<(usize, usize)>) -> (usize, usize) {
+    let (start, end) = first.span();
+    (start, rest.map_or(end, |rest| rest.1.max(end)))
+}
+
+// `fun name(params) { body }`
+#[derive(Debug, Clone)]
+pub struct FunctionDecl {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+    pub getter: bool, // `name { body }` in a class: runs on property access
+}
+
+// `class Name { var field = 0; method() { ... } class staticMethod() { ... } ... }`
+#[derive(Debug, Clone)]
+pub struct ClassDecl {
+    pub name: Token,
+    pub fields: Vec<FieldDecl>, // Initialized on every new instance, before `init` runs
+    pub methods: Vec<Arc<FunctionDecl>>,
+    pub class_methods: Vec<Arc<FunctionDecl>>, // Called on the class itself: `Math.square(3)`
+}
+
+// `var name = initializer;` in a class body
+#[derive(Debug, Clone)]
+pub struct FieldDecl {
+    pub name: Token,
+    pub initializer: Option<Expr>,
+}
+
+// Statement-level AST, in the same parenthesized style as Expr; `{:#}` keeps literals
+// as written there too
+impl fmt::Display for Stmt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let source = f.alternate();
+        match self {
+            Stmt::Expression(expr) => write!(f, "(expr {})", Node(expr, source)),
+            Stmt::Print(expr) => write!(f, "(print {})", Node(expr, source)),
+            Stmt::Var { name, initializer: Some(initializer) } => {
+                write!(f, "(var {} = {})", name.lexeme, Node(initializer, source))
+            }
+            Stmt::Var { name, initializer: None } => write!(f, "(var {})", name.lexeme),
+            Stmt::Block(statements) => {
+                write!(f, "(block")?;
+                for stmt in statements {
+                    write!(f, " {}", Node(stmt, source))?;
+                }
+                write!(f, ")")
+            }
+            Stmt::Function(declaration) => write!(f, "{}", Node(declaration.as_ref(), source)),
+            Stmt::Class(declaration) => {
+                write!(f, "(class {}", declaration.name.lexeme)?;
+                for field in &declaration.fields {
+                    match &field.initializer {
+                        Some(initializer) => write!(f, " (var {} = {})", field.name.lexeme, Node(initializer, source))?,
+                        None => write!(f, " (var {})", field.name.lexeme)?,
+                    }
+                }
+                for method in &declaration.methods {
+                    write!(f, " {}", Node(method.as_ref(), source))?;
+                }
+                for method in &declaration.class_methods {
+                    write!(f, " (class {})", Node(method.as_ref(), source))?;
+                }
+                write!(f, ")")
+            }
+            Stmt::Return { value: Some(value), .. } => write!(f, "(return {})", Node(value, source)),
+            Stmt::Return { value: None, .. } => write!(f, "(return)"),
+            Stmt::ForIn { name, iterable, body, .. } => {
+                write!(f, "(for {} {} {})", name.lexeme, Node(iterable, source), Node(body.as_ref(), source))
+            }
+            Stmt::Defer { expression, .. } => write!(f, "(defer {})", Node(expression, source)),
+        }
+    }
+}
+
+impl fmt::Display for FunctionDecl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let params: Vec<&str> = self.params.iter().map(|param| param.lexeme.as_str()).collect();
+        if self.getter {
+            write!(f, "(getter {}", self.name.lexeme)?;
+        } else {
+            write!(f, "(fun {} ({})", self.name.lexeme, params.join(" "))?;
+        }
+        for stmt in &self.body {
+            write!(f, " {}", Node(stmt, f.alternate()))?;
+        }
+        write!(f, ")")
+    }
 }
@@ -0,0 +1,47 @@
+use std::sync::OnceLock;
+
+use crate::class::{HostClass, LoxClass, LoxInstance};
+use crate::expr::LiteralValue;
+use crate::function::{Arity, NativeContext};
+use crate::interpreter::RuntimeError;
+
+// stringBuilder() -> an empty StringBuilder, for text made of many pieces. A loop that
+// does `s = s + piece` copies all of `s` each time, since the variable still holds the
+// old string; append() only copies the piece:
+//
+//     var out = stringBuilder();
+//     for (var line in lines) out.append(line).append("\n");
+//     print out.build();
+pub fn string_builder(_context: &mut NativeContext, _arguments: &[LiteralValue]) -> Result<LiteralValue, RuntimeError> {
+    Ok(LiteralValue::Instance(LoxInstance::with_host_data(builder_class(), String::new())))
+}
+
+// Not registered as a global: builders only come from stringBuilder()
+fn builder_class() -> LoxClass {
+    static CLASS: OnceLock<LoxClass> = OnceLock::new();
+    let class = CLASS.get_or_init(|| {
+        let class = HostClass::new("StringBuilder", Arity::Fixed(0), |context, _| {
+            Err::<String, _>(context.error("String builders are made with stringBuilder()."))
+        })
+        // append(v) -> the builder, with v added as print would show it
+        .method("append", Arity::Fixed(1), |context, this, arguments| {
+            // Converted before the text is locked: a toString() may use the builder
+            let line = context.line();
+            let piece = context.interpreter().literal_to_string(arguments[0].clone(), line)?;
+            this.with_data(|text: &mut String| text.push_str(&piece)).expect("StringBuilder instances hold a String");
+            Ok(LiteralValue::Instance(this.clone()))
+        })
+        // build() -> the text so far; the builder can go on appending
+        .method("build", Arity::Fixed(0), |_, this, _| {
+            let text = this.with_data(|text: &mut String| text.clone()).expect("StringBuilder instances hold a String");
+            Ok(LiteralValue::string(text))
+        })
+        // length() -> number of characters so far
+        .method("length", Arity::Fixed(0), |_, this, _| {
+            let length = this.with_data(|text: &mut String| text.chars().count()).expect("StringBuilder instances hold a String");
+            Ok(LiteralValue::NumberLiteral(length as f64))
+        });
+        LoxClass::host(class)
+    });
+    class.clone()
+}
@@ -0,0 +1,52 @@
+// "Did you mean ...?" suggestions for misspelled names
+
+// Levenshtein distance, counted in characters
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+// The candidate closest to `name`, if any is close enough to be a plausible typo:
+// about a third of the name's length, rounded up (so a swapped pair of letters in a
+// four-letter name still counts). Ties go to the first candidate in sorted order, so
+// suggestions are deterministic.
+pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let limit = name.chars().count().div_ceil(3);
+    let mut best: Option<(usize, &str)> = None;
+    for candidate in candidates {
+        if candidate == name {
+            continue;
+        }
+        let distance = edit_distance(name, candidate);
+        if distance > limit {
+            continue;
+        }
+        let better = match best {
+            Some((best_distance, best_name)) => (distance, candidate) < (best_distance, best_name),
+            None => true,
+        };
+        if better {
+            best = Some((distance, candidate));
+        }
+    }
+    best.map(|(_, candidate)| candidate)
+}
+
+// " Did you mean 'x'?" for appending to an error message, or "" without a suggestion
+pub fn did_you_mean<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> String {
+    match closest(name, candidates) {
+        Some(candidate) => format!(" Did you mean '{}'?", candidate),
+        None => String::new(),
+    }
+}
@@ -1,7 +1,7 @@
 use std::fmt;
 
 // token.rs
-#[allow(non_camel_case_types)]
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TokenType {
     // Single-character tokens
@@ -9,9 +9,12 @@ pub enum TokenType {
     RIGHT_PAREN,   // )
     LEFT_BRACE,    // {
     RIGHT_BRACE,   // }
+    LEFT_BRACKET,  // [
+    RIGHT_BRACKET, // ]
     STAR,          // *
     DOT,           // .
     COMMA,         // ,
+    COLON,         // :
     PLUS,          // +
     MINUS,         // -
     SEMICOLON,     // ;
@@ -50,6 +53,93 @@ pub enum TokenType {
     
     // End of file
     EOF,
+
+    // `// ...` up to the end of the line, only when ScanOptions::comments is set
+    COMMENT,
+}
+
+impl TokenType {
+    pub const ALL: [TokenType; 43] = [
+        TokenType::LEFT_PAREN, TokenType::RIGHT_PAREN, TokenType::LEFT_BRACE, TokenType::RIGHT_BRACE,
+        TokenType::LEFT_BRACKET, TokenType::RIGHT_BRACKET, TokenType::STAR, TokenType::DOT, TokenType::COMMA,
+        TokenType::COLON, TokenType::PLUS, TokenType::MINUS, TokenType::SEMICOLON, TokenType::EQUAL,
+        TokenType::EQUAL_EQUAL, TokenType::BANG, TokenType::BANG_EQUAL, TokenType::LESS, TokenType::LESS_EQUAL,
+        TokenType::GREATER, TokenType::GREATER_EQUAL, TokenType::SLASH, TokenType::IDENTIFIER, TokenType::STRING,
+        TokenType::NUMBER, TokenType::AND, TokenType::CLASS, TokenType::ELSE, TokenType::FALSE, TokenType::FOR,
+        TokenType::FUN, TokenType::IF, TokenType::NIL, TokenType::OR, TokenType::PRINT, TokenType::RETURN,
+        TokenType::SUPER, TokenType::THIS, TokenType::TRUE, TokenType::VAR, TokenType::WHILE, TokenType::EOF,
+        TokenType::COMMENT,
+    ];
+
+    // Reserved words, `and` through `while`
+    pub fn is_keyword(self) -> bool {
+        matches!(
+            self,
+            TokenType::AND
+                | TokenType::CLASS
+                | TokenType::ELSE
+                | TokenType::FALSE
+                | TokenType::FOR
+                | TokenType::FUN
+                | TokenType::IF
+                | TokenType::NIL
+                | TokenType::OR
+                | TokenType::PRINT
+                | TokenType::RETURN
+                | TokenType::SUPER
+                | TokenType::THIS
+                | TokenType::TRUE
+                | TokenType::VAR
+                | TokenType::WHILE
+        )
+    }
+
+    // Arithmetic, comparison, logical and assignment operators. `and`/`or` are
+    // keywords as well as operators.
+    pub fn is_operator(self) -> bool {
+        matches!(
+            self,
+            TokenType::PLUS
+                | TokenType::MINUS
+                | TokenType::STAR
+                | TokenType::SLASH
+                | TokenType::EQUAL
+                | TokenType::EQUAL_EQUAL
+                | TokenType::BANG
+                | TokenType::BANG_EQUAL
+                | TokenType::LESS
+                | TokenType::LESS_EQUAL
+                | TokenType::GREATER
+                | TokenType::GREATER_EQUAL
+                | TokenType::AND
+                | TokenType::OR
+        )
+    }
+
+    // Brackets, braces, parentheses and separators
+    pub fn is_punctuation(self) -> bool {
+        matches!(
+            self,
+            TokenType::LEFT_PAREN
+                | TokenType::RIGHT_PAREN
+                | TokenType::LEFT_BRACE
+                | TokenType::RIGHT_BRACE
+                | TokenType::LEFT_BRACKET
+                | TokenType::RIGHT_BRACKET
+                | TokenType::DOT
+                | TokenType::COMMA
+                | TokenType::COLON
+                | TokenType::SEMICOLON
+        )
+    }
+
+    // Tokens that denote a value by themselves: numbers, strings, `true`, `false`, `nil`
+    pub fn is_literal(self) -> bool {
+        matches!(
+            self,
+            TokenType::NUMBER | TokenType::STRING | TokenType::TRUE | TokenType::FALSE | TokenType::NIL
+        )
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -58,6 +148,8 @@ pub struct Token {
     pub lexeme: String,
     pub literal: Option<String>, 
     pub line: usize,
+    pub start: usize,  // Byte offset of the lexeme in the source
+    pub column: usize, // 1-based, in characters
 }
 
 impl Token {
@@ -67,8 +159,32 @@ impl Token {
             lexeme,
             literal,
             line,
+            start: 0,
+            column: 0,
         }
     }
+
+    // Length of the lexeme in bytes
+    pub fn len(&self) -> usize {
+        self.lexeme.len()
+    }
+
+    // Only EOF has an empty lexeme
+    pub fn is_empty(&self) -> bool {
+        self.lexeme.is_empty()
+    }
+
+    // Byte range of the lexeme in the source
+    pub fn span(&self) -> (usize, usize) {
+        (self.start, self.start + self.len())
+    }
+
+    // Set where the token was found in the source
+    pub fn at(mut self, start: usize, column: usize) -> Self {
+        self.start = start;
+        self.column = column;
+        self
+    }
 }
 
 // Implement Display for Token to format it as "<TokenType> <Lexeme> <Literal>"
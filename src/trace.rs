@@ -0,0 +1,84 @@
+use std::collections::BTreeMap;
+
+use crate::cache::{Decoder, Encoder};
+
+const MAGIC: &[u8; 4] = b"LOXT";
+const VERSION: u32 = 1;
+
+// One executed statement and the variables written while it was the latest to start.
+// Values are kept as text (see watch::describe), since runtime objects can't be saved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub line: usize,
+    pub depth: usize, // Active calls when the statement ran
+    pub writes: Vec<(String, String)>,
+}
+
+// Recording of a run for `run --record=<path>`, stepped through by `replay`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trace {
+    pub steps: Vec<Step>,
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Trace::default()
+    }
+
+    pub fn record_statement(&mut self, line: usize, depth: usize) {
+        self.steps.push(Step { line, depth, writes: Vec::new() });
+    }
+
+    pub fn record_write(&mut self, name: &str, value: String) {
+        if let Some(step) = self.steps.last_mut() {
+            step.writes.push((name.to_string(), value));
+        }
+    }
+
+    // Variables written up to and including step `index`, with their latest values.
+    // Variables are told apart by name only, so a shadowed name shows its innermost write.
+    pub fn state_at(&self, index: usize) -> BTreeMap<&str, &str> {
+        let mut state = BTreeMap::new();
+        for step in self.steps.iter().take(index + 1) {
+            for (name, value) in &step.writes {
+                state.insert(name.as_str(), value.as_str());
+            }
+        }
+        state
+    }
+}
+
+// The trace file: the program's source, so replay can show each line, then the steps
+pub fn encode(trace: &Trace, source: &str) -> Vec<u8> {
+    let mut encoder = Encoder { out: MAGIC.to_vec() };
+    encoder.u32(VERSION);
+    encoder.string(source);
+    encoder.usize(trace.steps.len());
+    for step in &trace.steps {
+        encoder.usize(step.line);
+        encoder.usize(step.depth);
+        encoder.usize(step.writes.len());
+        for (name, value) in &step.writes {
+            encoder.string(name);
+            encoder.string(value);
+        }
+    }
+    encoder.out
+}
+
+// None if the bytes are not a trace of this version
+pub fn decode(bytes: &[u8]) -> Option<(String, Trace)> {
+    let mut decoder = Decoder { bytes, position: 0 };
+    if decoder.take(MAGIC.len())? != MAGIC || decoder.u32()? != VERSION {
+        return None;
+    }
+    let source = decoder.string()?;
+    let steps = decoder.list(|decoder| {
+        Some(Step {
+            line: decoder.usize()?,
+            depth: decoder.usize()?,
+            writes: decoder.list(|decoder| Some((decoder.string()?, decoder.string()?)))?,
+        })
+    })?;
+    (decoder.position == bytes.len()).then_some((source, Trace { steps }))
+}
@@ -0,0 +1,169 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Arc;
+
+use crate::array::LoxArray;
+use crate::expr::LiteralValue;
+use crate::map::LoxMap;
+
+// Conversions between Rust data and Lox values, so embedders can write
+//
+//     interpreter.define_global("config", to_value(HashMap::from([("depth", 3.0)])));
+//     let names: Vec<String> = from_value(interpreter.get_global("names").unwrap())?;
+//
+// Going into Lox always succeeds: Vec becomes an array, a string-keyed map a map and
+// None nil. Coming back checks the value's type and fails with a ConversionError.
+// Arrays and maps are copied, so the result no longer aliases the script's value.
+
+pub fn to_value(value: impl Into<LiteralValue>) -> LiteralValue {
+    value.into()
+}
+
+pub fn from_value<T: TryFrom<LiteralValue, Error = ConversionError>>(value: LiteralValue) -> Result<T, ConversionError> {
+    T::try_from(value)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConversionError {
+    pub expected: &'static str,
+    pub found: &'static str, // See LiteralValue::type_name
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Expected {}, found {}.", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+impl From<f64> for LiteralValue {
+    fn from(value: f64) -> Self {
+        LiteralValue::NumberLiteral(value)
+    }
+}
+
+impl From<bool> for LiteralValue {
+    fn from(value: bool) -> Self {
+        LiteralValue::BooleanLiteral(value)
+    }
+}
+
+impl From<String> for LiteralValue {
+    fn from(value: String) -> Self {
+        LiteralValue::string(value)
+    }
+}
+
+impl From<&str> for LiteralValue {
+    fn from(value: &str) -> Self {
+        LiteralValue::string(value)
+    }
+}
+
+impl<T: Into<LiteralValue>> From<Vec<T>> for LiteralValue {
+    fn from(value: Vec<T>) -> Self {
+        LiteralValue::Array(LoxArray::new(value.into_iter().map(Into::into).collect()))
+    }
+}
+
+impl<K: Into<String>, T: Into<LiteralValue>> From<HashMap<K, T>> for LiteralValue {
+    fn from(value: HashMap<K, T>) -> Self {
+        LiteralValue::Map(LoxMap::new(value.into_iter().map(|(key, value)| (key.into(), value.into())).collect()))
+    }
+}
+
+impl<K: Into<String>, T: Into<LiteralValue>> From<BTreeMap<K, T>> for LiteralValue {
+    fn from(value: BTreeMap<K, T>) -> Self {
+        LiteralValue::Map(LoxMap::new(value.into_iter().map(|(key, value)| (key.into(), value.into())).collect()))
+    }
+}
+
+impl<T: Into<LiteralValue>> From<Option<T>> for LiteralValue {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(LiteralValue::Nil, Into::into)
+    }
+}
+
+fn mismatch(expected: &'static str, value: &LiteralValue) -> ConversionError {
+    ConversionError { expected, found: value.type_name() }
+}
+
+impl TryFrom<LiteralValue> for f64 {
+    type Error = ConversionError;
+
+    fn try_from(value: LiteralValue) -> Result<Self, Self::Error> {
+        match value {
+            LiteralValue::NumberLiteral(n) => Ok(n),
+            other => Err(mismatch("number", &other)),
+        }
+    }
+}
+
+impl TryFrom<LiteralValue> for bool {
+    type Error = ConversionError;
+
+    // Only booleans convert; use Interpreter::is_truthy for Lox truthiness
+    fn try_from(value: LiteralValue) -> Result<Self, Self::Error> {
+        match value {
+            LiteralValue::BooleanLiteral(b) => Ok(b),
+            other => Err(mismatch("boolean", &other)),
+        }
+    }
+}
+
+impl TryFrom<LiteralValue> for String {
+    type Error = ConversionError;
+
+    fn try_from(value: LiteralValue) -> Result<Self, Self::Error> {
+        match value {
+            LiteralValue::StringLiteral(s) => Ok(Arc::unwrap_or_clone(s)),
+            other => Err(mismatch("string", &other)),
+        }
+    }
+}
+
+impl<T: TryFrom<LiteralValue, Error = ConversionError>> TryFrom<LiteralValue> for Vec<T> {
+    type Error = ConversionError;
+
+    fn try_from(value: LiteralValue) -> Result<Self, Self::Error> {
+        match value {
+            LiteralValue::Array(array) => array.to_vec().into_iter().map(T::try_from).collect(),
+            other => Err(mismatch("array", &other)),
+        }
+    }
+}
+
+impl<T: TryFrom<LiteralValue, Error = ConversionError>> TryFrom<LiteralValue> for HashMap<String, T> {
+    type Error = ConversionError;
+
+    fn try_from(value: LiteralValue) -> Result<Self, Self::Error> {
+        match value {
+            LiteralValue::Map(map) => map.entries().into_iter().map(|(key, value)| Ok((key, T::try_from(value)?))).collect(),
+            other => Err(mismatch("map", &other)),
+        }
+    }
+}
+
+impl<T: TryFrom<LiteralValue, Error = ConversionError>> TryFrom<LiteralValue> for BTreeMap<String, T> {
+    type Error = ConversionError;
+
+    fn try_from(value: LiteralValue) -> Result<Self, Self::Error> {
+        match value {
+            LiteralValue::Map(map) => map.entries().into_iter().map(|(key, value)| Ok((key, T::try_from(value)?))).collect(),
+            other => Err(mismatch("map", &other)),
+        }
+    }
+}
+
+// nil is None; anything else must convert to T
+impl<T: TryFrom<LiteralValue, Error = ConversionError>> TryFrom<LiteralValue> for Option<T> {
+    type Error = ConversionError;
+
+    fn try_from(value: LiteralValue) -> Result<Self, Self::Error> {
+        match value {
+            LiteralValue::Nil => Ok(None),
+            other => T::try_from(other).map(Some),
+        }
+    }
+}
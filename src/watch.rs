@@ -0,0 +1,79 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::expr::LiteralValue;
+use crate::number;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Read,
+    Write, // An assignment, or a `var` declaration with its initial value
+}
+
+impl Access {
+    pub fn name(self) -> &'static str {
+        match self {
+            Access::Read => "read",
+            Access::Write => "write",
+        }
+    }
+}
+
+// One access to a watched variable
+#[derive(Debug, Clone)]
+pub struct WatchEvent {
+    pub name: String,
+    pub access: Access,
+    pub value: LiteralValue,            // The value read or written
+    pub previous: Option<LiteralValue>, // The value an assignment replaced; None for reads and declarations
+    pub line: usize,
+}
+
+// Short text for a value, without calling any toString() method: strings quoted,
+// objects in their debug form (`<fn name>`, `Point instance`, ...)
+pub fn describe(value: &LiteralValue) -> String {
+    match value {
+        LiteralValue::StringLiteral(s) => format!("{:?}", s),
+        LiteralValue::NumberLiteral(n) => number::display(*n),
+        LiteralValue::BooleanLiteral(b) => b.to_string(),
+        LiteralValue::Nil => "nil".to_string(),
+        LiteralValue::Function(function) => format!("{:?}", function),
+        LiteralValue::NativeFunction(native) => format!("{:?}", native),
+        LiteralValue::HostMethod(method) => format!("{:?}", method),
+        LiteralValue::Array(array) => format!("{:?}", array),
+        LiteralValue::Map(map) => format!("{:?}", map),
+        LiteralValue::Class(class) => format!("{:?}", class),
+        LiteralValue::Instance(instance) => format!("{:?}", instance),
+    }
+}
+
+pub type WatchCallback = Box<dyn FnMut(&WatchEvent) + Send + Sync>;
+
+// Variables to watch, matched by name in every scope, and what to call on each access
+pub struct Watchpoints {
+    names: BTreeSet<String>,
+    callback: WatchCallback,
+}
+
+impl Watchpoints {
+    pub fn new(names: impl IntoIterator<Item = String>, callback: WatchCallback) -> Self {
+        Watchpoints {
+            names: names.into_iter().collect(),
+            callback,
+        }
+    }
+
+    pub fn watches(&self, name: &str) -> bool {
+        self.names.contains(name)
+    }
+
+    pub fn notify(&mut self, event: &WatchEvent) {
+        (self.callback)(event);
+    }
+}
+
+impl fmt::Debug for Watchpoints {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Watchpoints").field("names", &self.names).finish_non_exhaustive()
+    }
+}
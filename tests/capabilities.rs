@@ -0,0 +1,61 @@
+// Embedders get no capabilities unless they grant them, one by one
+
+use std::env;
+use std::fs;
+use std::process;
+
+use codecrafters_interpreter::diagnostic::{DiagnosticFormat, Reporter};
+use codecrafters_interpreter::driver::Pipeline;
+use codecrafters_interpreter::interpreter::{Interpreter, RuntimeError};
+use codecrafters_interpreter::options::{Capabilities, Capability, ExecOptions};
+use codecrafters_interpreter::output::OutputBuffer;
+
+// What the program printed, or its runtime error
+fn run(source: &str, capabilities: Capabilities) -> Result<String, RuntimeError> {
+    let mut pipeline = Pipeline::new(Reporter::new(DiagnosticFormat::Text, "<test>"));
+    let statements = pipeline.load(source).unwrap();
+    pipeline.resolve(&statements).unwrap();
+    let output = OutputBuffer::new();
+    let mut interpreter = Interpreter::with_options(ExecOptions { capabilities, deterministic: true, ..ExecOptions::default() });
+    interpreter.set_output(Box::new(output.clone()));
+    interpreter.interpret(statements)?;
+    Ok(output.contents())
+}
+
+#[test]
+fn denied_by_default() {
+    for (native, call) in [
+        ("readFile", "readFile(\"lox.toml\")"),
+        ("writeFile", "writeFile(\"out.txt\", \"\")"),
+        ("env", "env(\"HOME\")"),
+        ("args", "args()"),
+        ("sleep", "sleep(0)"),
+    ] {
+        let error = run(&format!("{};", call), Capabilities::default()).unwrap_err();
+        assert!(error.message.starts_with(&format!("{}() requires the '", native)), "{}", error.message);
+    }
+}
+
+#[test]
+fn granted_one_at_a_time() {
+    let path = env::temp_dir().join(format!("lox-capabilities-{}.txt", process::id()));
+    let path = path.to_str().unwrap().replace('\\', "/");
+    let program = format!("writeFile(\"{0}\", \"saved\");\nprint readFile(\"{0}\");", path);
+
+    let mut capabilities = Capabilities::none();
+    capabilities.set(Capability::Write, true);
+    let error = run(&program, capabilities.clone()).unwrap_err();
+    assert_eq!((error.message.as_str(), error.line), ("readFile() requires the 'read' capability.", 2));
+
+    capabilities.set(Capability::Read, true);
+    assert_eq!(run(&program, capabilities).unwrap(), "saved\n");
+    let _ = fs::remove_file(&path);
+}
+
+// A deterministic run moves its virtual clock on instead of waiting
+#[test]
+fn sleep_under_deterministic() {
+    let mut capabilities = Capabilities::none();
+    capabilities.set(Capability::Sleep, true);
+    assert_eq!(run("sleep(60);\nprint clock();", capabilities).unwrap(), "60.001\n");
+}
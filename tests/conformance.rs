@@ -0,0 +1,40 @@
+// The conformance checklist and the selftest fixtures, run against the interpreter
+// cargo just built, so a change to the graded output fails `cargo test`
+
+use std::path::Path;
+
+use codecrafters_interpreter::conformance::{self, Check, CHECKLIST, UNFLAGGED};
+use codecrafters_interpreter::selftest::STAGES;
+
+fn executable() -> &'static Path {
+    Path::new(env!("CARGO_BIN_EXE_codecrafters-interpreter"))
+}
+
+fn assert_passes(check: &Check, flagged: bool) {
+    if let Err(message) = conformance::run(executable(), check, flagged) {
+        panic!("{} ({}): {}", check.stage, check.command, message);
+    }
+}
+
+#[test]
+fn checklist() {
+    for check in CHECKLIST {
+        assert_passes(check, true);
+    }
+}
+
+#[test]
+fn unflagged() {
+    for check in UNFLAGGED {
+        assert_passes(check, false);
+    }
+}
+
+#[test]
+fn selftest_fixtures() {
+    for stage in STAGES {
+        for fixture in stage.fixtures {
+            assert_passes(fixture, true);
+        }
+    }
+}
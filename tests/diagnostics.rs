@@ -0,0 +1,79 @@
+// The exact text, location and suggested fix of the parser's targeted syntax errors
+
+use codecrafters_interpreter::diagnostic::{Diagnostic, Fix};
+use codecrafters_interpreter::document::TextEdit;
+use codecrafters_interpreter::options::LanguageLevel;
+use codecrafters_interpreter::parser::Parser;
+use codecrafters_interpreter::scanner::Scanner;
+
+fn syntax_errors(source: &str, language: LanguageLevel) -> Vec<Diagnostic> {
+    let mut scanner = Scanner::new(source.to_string());
+    scanner.scan_tokens();
+    assert!(!scanner.has_error(), "doesn't scan: {}", source);
+    let mut parser = Parser::new(scanner.get_tokens().to_vec());
+    parser.set_language(language);
+    parser.parse_statements().expect_err(source)
+}
+
+// The only error of `source`, at extended Lox
+fn syntax_error(source: &str) -> Diagnostic {
+    let mut errors = syntax_errors(source, LanguageLevel::Extended);
+    assert_eq!(errors.len(), 1, "errors of {}: {:?}", source, errors);
+    errors.remove(0)
+}
+
+fn insert(message: &str, at: usize, text: &str) -> Option<Fix> {
+    Some(Fix { message: message.to_string(), edit: TextEdit { start: at, end: at, text: text.to_string() } })
+}
+
+#[test]
+fn adjacent_identifiers() {
+    let error = syntax_error("foo bar;");
+    assert_eq!(error.to_text(), "[line 1] Error at 'bar': Unexpected identifier after 'foo'. Expect ';' after expression.");
+    // Both names, so an editor underlines the whole run
+    assert_eq!((error.column, error.span), (Some(1), Some((0, 7))));
+    // A missing ';' and a mistyped keyword are as likely, so there's no fix to offer
+    assert_eq!(error.fix, None);
+}
+
+#[test]
+fn mistyped_keyword() {
+    let error = syntax_error("vr x = 1;\nprint x;");
+    assert_eq!(error.to_text(), "[line 1] Error at 'x': Unexpected identifier after 'vr'. Expect ';' after expression.");
+    assert_eq!(error.span, Some((0, 4)));
+}
+
+#[test]
+fn adjacent_identifiers_in_the_book() {
+    let errors = syntax_errors("foo bar;", LanguageLevel::Book);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].to_text(), "[line 1] Error at 'bar': Expect ';' after expression.");
+    assert_eq!(errors[0].fix, insert("insert ';'", 3, ";"));
+}
+
+#[test]
+fn missing_semicolon() {
+    let error = syntax_error("print 1\nprint 2;");
+    assert_eq!(error.to_text(), "[line 2] Error at 'print': Expect ';' after value.");
+    // Right after the `1`, not before the `print` the error is found at
+    assert_eq!(error.fix, insert("insert ';'", 7, ";"));
+}
+
+#[test]
+fn missing_closers() {
+    let error = syntax_error("print (1 + 2;");
+    assert_eq!(error.to_text(), "[line 1] Error at ';': Expect ')' after expression.");
+    assert_eq!(error.fix, insert("insert ')'", 12, ")"));
+
+    let error = syntax_error("{\n  print 1;\n");
+    assert_eq!(error.to_text(), "[line 3] Error at end: Expect '}' after block.");
+    assert_eq!(error.fix, insert("insert '}'", 12, "}"));
+}
+
+#[test]
+fn comparison_written_as_assignment() {
+    let error = syntax_error("var x = 1;\n1 = x;");
+    assert_eq!(error.to_text(), "[line 2] Error at '=': Invalid assignment target.");
+    let fix = TextEdit { start: 13, end: 14, text: "==".to_string() };
+    assert_eq!(error.fix, Some(Fix { message: "use '==' to compare".to_string(), edit: fix }));
+}
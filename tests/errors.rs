@@ -0,0 +1,75 @@
+// Programs with several syntax errors: every one is reported, in the book's format
+// and in source order, and the run exits 65 without running anything
+
+use std::path::Path;
+
+use codecrafters_interpreter::conformance::{self, Check};
+
+fn assert_reports(check: Check) {
+    let executable = Path::new(env!("CARGO_BIN_EXE_codecrafters-interpreter"));
+    if let Err(message) = conformance::run(executable, &check, true) {
+        panic!("{} ({}): {}", check.stage, check.command, message);
+    }
+}
+
+#[test]
+fn one_error_per_statement() {
+    assert_reports(Check {
+        stage: "one error per statement",
+        command: "run",
+        source: "var = 1;\nprint (2;\nvar 3;\nprint \"ok\";\n",
+        stdout: "",
+        stderr: "[line 1] Error at '=': Expect variable name.\n\
+                 [line 2] Error at ';': Expect ')' after expression.\n\
+                 [line 3] Error at '3': Expect variable name.\n",
+        exit_code: 65,
+    });
+}
+
+#[test]
+fn invalid_assignment_targets() {
+    assert_reports(Check {
+        stage: "invalid assignment targets",
+        command: "run",
+        source: "1 = 2;\n(a) = 3;\nprint a + ;\n",
+        stdout: "",
+        stderr: "[line 1] Error at '=': Invalid assignment target.\n\
+                 [line 2] Error at '=': Invalid assignment target.\n\
+                 [line 3] Error at ';': Expect expression.\n",
+        exit_code: 65,
+    });
+}
+
+#[test]
+fn errors_inside_a_block() {
+    assert_reports(Check {
+        stage: "errors inside a block",
+        command: "run",
+        source: "{\n  var = 1;\n  print 2\n}\nfun f( {}\nclass {}\n",
+        stdout: "",
+        stderr: "[line 2] Error at '=': Expect variable name.\n\
+                 [line 4] Error at '}': Expect ';' after value.\n\
+                 [line 5] Error at '{': Expect parameter name.\n\
+                 [line 6] Error at '{': Expect class name.\n\
+                 [line 7] Error at end: Expect '}' after block.\n",
+        exit_code: 65,
+    });
+}
+
+// The parser keeps the expression it found around an invalid target, but the error
+// must still stop the command
+#[test]
+fn invalid_assignment_target_in_an_expression() {
+    for command in ["parse", "evaluate"] {
+        for source in ["1 = 2", "(1) = 2", "a + b = c"] {
+            assert_reports(Check {
+                stage: "invalid assignment target in an expression",
+                command,
+                source,
+                stdout: "",
+                stderr: "[line 1] Error at '=': Invalid assignment target.\n",
+                exit_code: 65,
+            });
+        }
+    }
+}
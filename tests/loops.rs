@@ -0,0 +1,53 @@
+// for-in binds its variable afresh on every iteration, so closures made in the body
+// each keep their own element. There is no C-style `for` with a single binding.
+
+use std::path::Path;
+
+use codecrafters_interpreter::conformance::{self, Check};
+
+fn assert_prints(source: &'static str, stdout: &'static str) {
+    let executable = Path::new(env!("CARGO_BIN_EXE_codecrafters-interpreter"));
+    let check = Check { stage: "for-in", command: "run", source, stdout, stderr: "", exit_code: 0 };
+    if let Err(message) = conformance::run(executable, &check, false) {
+        panic!("{}\n{}", source, message);
+    }
+}
+
+#[test]
+fn closures_see_their_own_iteration() {
+    assert_prints(
+        "var fs = [];
+         for (var i in [1, 2, 3]) {
+           fun f() { return i; }
+           push(fs, f);
+         }
+         for (var f in fs) print f();",
+        "1\n2\n3\n",
+    );
+    assert_prints(
+        "var fs = [];
+         for (var c in \"ab\") {
+           fun f() { return c; }
+           push(fs, f);
+         }
+         print fs[1]() + fs[0]();",
+        "ba\n",
+    );
+}
+
+// A closure that changes its variable changes only its own iteration's
+#[test]
+fn iterations_dont_share_the_variable() {
+    assert_prints(
+        "var fs = [];
+         for (var i in [1, 2]) {
+           fun bump() { i = i + 10; return i; }
+           push(fs, bump);
+         }
+         print fs[0]();
+         print fs[0]();
+         print fs[1]();",
+        "11\n21\n12\n",
+    );
+    assert_prints("for (var i in [1, 2, 3]) { print i; i = 0; }", "1\n2\n3\n");
+}
@@ -0,0 +1,57 @@
+// How assignment nests and binds among the other operators, and which targets it
+// refuses
+
+use codecrafters_interpreter::diagnostic::{DiagnosticFormat, Reporter};
+use codecrafters_interpreter::driver::Pipeline;
+use codecrafters_interpreter::parser::Parser;
+use codecrafters_interpreter::scanner::Scanner;
+
+// The tree of one expression, as `parse` prints it
+fn parse(source: &str) -> String {
+    let mut scanner = Scanner::new(source.to_string());
+    scanner.scan_tokens();
+    let mut parser = Parser::new(scanner.get_tokens().to_vec());
+    let expr = parser.parse_expression();
+    assert!(!parser.has_error(), "{}: {:?}", source, parser.diagnostics());
+    expr.unwrap().to_string()
+}
+
+#[test]
+fn nested_targets() {
+    assert_eq!(parse("a.b = c = 3"), "(assign (get a b) = (assign c = 3.0))");
+    assert_eq!(parse("arr[0] = a.b = 1"), "(assign (index arr 0.0) = (assign (get a b) = 1.0))");
+    assert_eq!(parse("a.b[2](x).c = 5"), "(assign (get (call (index (get a b) 2.0) x) c) = 5.0)");
+    assert_eq!(
+        parse("a[0] = b[1] = c.d = 2"),
+        "(assign (index a 0.0) = (assign (index b 1.0) = (assign (get c d) = 2.0)))"
+    );
+}
+
+#[test]
+fn assignment_binds_loosest_and_to_the_right() {
+    assert_eq!(parse("a = b = c"), "(assign a = (assign b = c))");
+    assert_eq!(parse("a = b == c"), "(assign a = (== b c))");
+    assert_eq!(parse("a.b = -c * 2"), "(assign (get a b) = (* (- c) 2.0))");
+    assert_eq!(parse("a = 1 < 2 != true"), "(assign a = (!= (< 1.0 2.0) true))");
+}
+
+#[test]
+fn assignment_is_a_value() {
+    assert_eq!(parse("(a = 1) + 2"), "(+ (group (assign a = 1.0)) 2.0)");
+    assert_eq!(parse("f(a = 1)"), "(call f (assign a = 1.0))");
+    assert_eq!(parse("[a = 1, b.c = 2]"), "(array (assign a = 1.0) (assign (get b c) = 2.0))");
+}
+
+// Each is reported at the '=', and the expression around it is not used
+#[test]
+fn invalid_targets() {
+    for source in ["a + b = c", "(a) = 1", "1 = 2", "-a = 1", "f() = 1", "a == b = c"] {
+        let mut pipeline = Pipeline::new(Reporter::new(DiagnosticFormat::Text, "<test>"));
+        let mut scanner = Scanner::new(source.to_string());
+        scanner.scan_tokens();
+        let failure = pipeline.parse_expression(scanner.get_tokens().to_vec()).expect_err(source);
+        let messages: Vec<_> = failure.diagnostics.iter().map(|diagnostic| diagnostic.to_text()).collect();
+        assert_eq!(messages, ["[line 1] Error at '=': Invalid assignment target."], "{}", source);
+        assert_eq!(failure.code, 65);
+    }
+}
@@ -0,0 +1,119 @@
+// parse → print → parse over the fixture corpus and generated programs: the
+// printer's source for a program must parse back to the same program, and printing
+// that again must change nothing
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use codecrafters_interpreter::ast;
+use codecrafters_interpreter::expr::Expr;
+use codecrafters_interpreter::generate::{self, GenerateOptions};
+use codecrafters_interpreter::parser::Parser;
+use codecrafters_interpreter::printer;
+use codecrafters_interpreter::scanner::Scanner;
+use codecrafters_interpreter::stmt::Stmt;
+
+// Every .lox file under fixtures/, in a stable order
+fn fixtures() -> Vec<PathBuf> {
+    fn collect(dir: &Path, paths: &mut Vec<PathBuf>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                collect(&path, paths);
+            } else if path.extension().is_some_and(|extension| extension == "lox") {
+                paths.push(path);
+            }
+        }
+    }
+    let mut paths = Vec::new();
+    collect(&Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures"), &mut paths);
+    paths.sort();
+    paths
+}
+
+fn parser(source: &str) -> Parser {
+    let mut scanner = Scanner::new(source.to_string());
+    scanner.scan_tokens();
+    assert!(!scanner.has_error(), "doesn't scan:\n{}", source);
+    let mut parser = Parser::new(scanner.get_tokens().to_vec());
+    parser.set_extensions(true);
+    parser
+}
+
+// A program, or a single expression as the parse and evaluate fixtures hold
+fn assert_round_trips(path: &Path, source: &str) {
+    let mut parser = parser(source);
+    if parser.looks_like_program() {
+        let statements = parser.parse_statements().unwrap_or_else(|_| panic!("{} doesn't parse", path.display()));
+        if let Err(message) = printer::check_round_trip(&statements) {
+            panic!("{}: {}", path.display(), message);
+        }
+        let printed = printer::to_source(&statements);
+        let reprinted = printer::to_source(&parse_program(&printed));
+        assert_eq!(printed, reprinted, "{}: printing is not stable", path.display());
+    } else {
+        let expr = parser.parse_expression().unwrap_or_else(|| panic!("{} doesn't parse", path.display()));
+        let printed = printer::expr_to_source(&expr);
+        let reparsed = parse_expression(&printed);
+        assert!(ast::same_expr(&expr, &reparsed), "{}: `{}` parsed back differently", path.display(), printed);
+        assert_eq!(printed, printer::expr_to_source(&reparsed), "{}: printing is not stable", path.display());
+    }
+}
+
+fn parse_program(source: &str) -> Vec<Stmt> {
+    parser(source).parse_statements().unwrap_or_else(|_| panic!("printed source doesn't parse:\n{}", source))
+}
+
+fn parse_expression(source: &str) -> Expr {
+    let mut parser = parser(source);
+    let expr = parser.parse_expression();
+    assert!(!parser.has_error(), "printed source doesn't parse: {}", source);
+    expr.unwrap()
+}
+
+#[test]
+fn fixture_corpus() {
+    let paths = fixtures();
+    assert!(!paths.is_empty());
+    for path in paths {
+        // The tokenize fixtures are token soup, not programs
+        if path.file_name().unwrap().to_string_lossy().starts_with("tokenize_") {
+            continue;
+        }
+        assert_round_trips(&path, &fs::read_to_string(&path).unwrap());
+    }
+}
+
+// Fixed seeds, so a failure reproduces with `generate --seed=N`. The deeper programs
+// nest blocks, calls and operators the fixtures don't.
+#[test]
+fn generated_programs() {
+    for options in [GenerateOptions::default(), GenerateOptions { depth: 6, ..GenerateOptions::default() }] {
+        for seed in 0..100 {
+            let source = generate::generate(seed, &options);
+            let statements = parser(&source)
+                .parse_statements()
+                .unwrap_or_else(|_| panic!("seed {} (depth {}) doesn't parse:\n{}", seed, options.depth, source));
+            if let Err(message) = printer::check_round_trip(&statements) {
+                panic!("seed {} (depth {}): {}", seed, options.depth, message);
+            }
+        }
+    }
+}
+
+#[test]
+fn only_needed_parentheses() {
+    for (source, printed) in [
+        ("(1 + 2) * 3", "(1 + 2) * 3"),
+        ("1 + (2 * 3)", "1 + 2 * 3"),
+        ("(a - b) - c", "a - b - c"),
+        ("a - (b - c)", "a - (b - c)"),
+        ("((a))", "a"),
+        ("-(a + b)", "-(a + b)"),
+        ("(a.b)(c)", "a.b(c)"),
+        ("a = (b = c)", "a = b = c"),
+        ("(1 < 2) == (3 < 4)", "1 < 2 == 3 < 4"),
+    ] {
+        assert_eq!(printer::expr_to_source(&parse_expression(source)), printed, "printing `{}`", source);
+    }
+}
@@ -0,0 +1,76 @@
+// Interpreters move between threads, the way a server embeds one per request
+
+use std::thread;
+
+use codecrafters_interpreter::diagnostic::{DiagnosticFormat, Reporter};
+use codecrafters_interpreter::driver::Pipeline;
+use codecrafters_interpreter::expr::LiteralValue;
+use codecrafters_interpreter::interpreter::Interpreter;
+use codecrafters_interpreter::output::OutputBuffer;
+use codecrafters_interpreter::stmt::Stmt;
+
+// Closures over a local, instances and a method bound to one
+const PROGRAM: &str = "
+fun makeCounter() {
+  var count = 0;
+  fun increment() {
+    count = count + 1;
+    return count;
+  }
+  return increment;
+}
+
+class Greeter {
+  init(name) { this.name = name; }
+  greet() { return \"hello \" + this.name; }
+}
+
+var counter = makeCounter();
+counter();
+print counter();
+var greet = Greeter(\"thread\").greet;
+print greet();
+";
+
+fn resolved(source: &str) -> Vec<Stmt> {
+    let mut pipeline = Pipeline::new(Reporter::new(DiagnosticFormat::Text, "<test>"));
+    let statements = pipeline.load(source).unwrap();
+    pipeline.resolve(&statements).unwrap();
+    statements
+}
+
+#[test]
+fn interpreter_moves_to_a_thread_and_back() {
+    let output = OutputBuffer::new();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(output.clone()));
+    let statements = resolved(PROGRAM);
+
+    let worker = thread::spawn(move || interpreter.interpret(statements).map(|()| interpreter));
+    let mut interpreter = worker.join().unwrap().unwrap();
+    assert_eq!(output.contents(), "2\nhello thread\n");
+
+    // The closure made on the worker still counts from where it left off
+    let counter = interpreter.get_global("counter").unwrap();
+    let count = interpreter.call(&counter, Vec::new()).unwrap();
+    assert_eq!(count, LiteralValue::NumberLiteral(3.0));
+}
+
+#[test]
+fn one_interpreter_per_thread() {
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let statements = resolved(PROGRAM);
+            thread::spawn(move || {
+                let output = OutputBuffer::new();
+                let mut interpreter = Interpreter::new();
+                interpreter.set_output(Box::new(output.clone()));
+                interpreter.interpret(statements).unwrap();
+                output.contents()
+            })
+        })
+        .collect();
+    for worker in workers {
+        assert_eq!(worker.join().unwrap(), "2\nhello thread\n");
+    }
+}